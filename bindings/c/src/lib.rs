@@ -34,6 +34,8 @@ pub enum CssResult {
     InvalidInputString,
     /// Invalid cache size.
     InvalidCacheSize,
+    /// The stylesheet location is not allowed by the resolver policy.
+    ForbiddenStylesheet,
 }
 
 impl From<InlineError> for CssResult {
@@ -43,6 +45,8 @@ impl From<InlineError> for CssResult {
             InlineError::Network { .. } => CssResult::RemoteStylesheetNotAvailable,
             InlineError::ParseError(_) => CssResult::InternalSelectorParseError,
             InlineError::MissingStyleSheet { .. } => CssResult::MissingStylesheet,
+            InlineError::PolicyViolation { .. } => CssResult::ForbiddenStylesheet,
+            _ => CssResult::IoError,
         }
     }
 }
@@ -266,6 +270,7 @@ impl TryFrom<&CssInlinerOptions> for InlineOptions<'_> {
             remove_inlined_selectors: value.remove_inlined_selectors,
             apply_width_attributes: value.apply_width_attributes,
             apply_height_attributes: value.apply_height_attributes,
+            ..Default::default()
         })
     }
 }
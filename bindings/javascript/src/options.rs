@@ -144,6 +144,7 @@ impl TryFrom<Options> for css_inline::InlineOptions<'_> {
             remove_inlined_selectors: value.remove_inlined_selectors.unwrap_or(false),
             apply_width_attributes: value.apply_width_attributes.unwrap_or(false),
             apply_height_attributes: value.apply_height_attributes.unwrap_or(false),
+            ..Default::default()
         })
     }
 }
@@ -46,9 +46,7 @@ impl From<InlineErrorWrapper> for PyErr {
             rust_inline::InlineError::ParseError(message) => {
                 InlineError::new_err(message.to_string())
             }
-            rust_inline::InlineError::MissingStyleSheet { .. } => {
-                InlineError::new_err(error.0.to_string())
-            }
+            _ => InlineError::new_err(error.0.to_string()),
         }
     }
 }
@@ -132,6 +130,7 @@ macro_rules! inliner {
             remove_inlined_selectors: $remove_inlined_selectors.unwrap_or(false),
             apply_width_attributes: $apply_width_attributes.unwrap_or(false),
             apply_height_attributes: $apply_height_attributes.unwrap_or(false),
+            ..Default::default()
         };
         rust_inline::CSSInliner::new(options)
     }};
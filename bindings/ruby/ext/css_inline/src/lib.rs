@@ -123,6 +123,7 @@ fn parse_options<Req>(
         remove_inlined_selectors: kwargs.remove_inlined_selectors.unwrap_or(false),
         apply_width_attributes: kwargs.apply_width_attributes.unwrap_or(false),
         apply_height_attributes: kwargs.apply_height_attributes.unwrap_or(false),
+        ..Default::default()
     })
 }
 
@@ -170,9 +171,7 @@ impl From<InlineErrorWrapper> for magnus::Error {
             rust_inline::InlineError::ParseError(message) => {
                 magnus::Error::new(ruby.exception_arg_error(), message.to_string())
             }
-            rust_inline::InlineError::MissingStyleSheet { .. } => {
-                magnus::Error::new(ruby.exception_arg_error(), error.0.to_string())
-            }
+            _ => magnus::Error::new(ruby.exception_arg_error(), error.0.to_string()),
         }
     }
 }
@@ -9,7 +9,10 @@ use std::{
 };
 
 /// Inlining error
+///
+/// New variants may be added in minor releases.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum InlineError {
     /// Missing stylesheet file.
     MissingStyleSheet {
@@ -29,6 +32,13 @@ pub enum InlineError {
     },
     /// Syntax errors or unsupported selectors.
     ParseError(Cow<'static, str>),
+    /// The stylesheet location is not allowed by the resolver policy.
+    PolicyViolation {
+        /// Why the location is not allowed.
+        reason: Cow<'static, str>,
+        /// The stylesheet location.
+        location: String,
+    },
 }
 
 impl From<io::Error> for InlineError {
@@ -43,7 +53,9 @@ impl Error for InlineError {
             InlineError::IO(error) => Some(error),
             #[cfg(feature = "http")]
             InlineError::Network { error, .. } => Some(error),
-            InlineError::MissingStyleSheet { .. } | InlineError::ParseError(_) => None,
+            InlineError::MissingStyleSheet { .. }
+            | InlineError::ParseError(_)
+            | InlineError::PolicyViolation { .. } => None,
         }
     }
 }
@@ -58,6 +70,9 @@ impl Display for InlineError {
             Self::MissingStyleSheet { path } => {
                 f.write_fmt(format_args!("Missing stylesheet file: {path}"))
             }
+            Self::PolicyViolation { reason, location } => {
+                f.write_fmt(format_args!("{reason}: {location}"))
+            }
         }
     }
 }
@@ -148,6 +148,18 @@ pub(super) fn should_ignore(attributes: &[html5ever::Attribute]) -> bool {
         .any(|a| a.name.local == *CSS_INLINE_ATTRIBUTE && &*a.value == "ignore")
 }
 
+/// Whether a `rel` attribute value marks a link as a stylesheet. Alternate stylesheets are excluded.
+pub(super) fn is_stylesheet_rel(rel: &str) -> bool {
+    let mut is_stylesheet = false;
+    for token in rel.split_ascii_whitespace() {
+        if token.eq_ignore_ascii_case("alternate") {
+            return false;
+        }
+        is_stylesheet |= token.eq_ignore_ascii_case("stylesheet");
+    }
+    is_stylesheet
+}
+
 impl Attributes {
     pub(crate) fn new(mut attributes: Vec<html5ever::Attribute>) -> Attributes {
         let mut class = None;
@@ -195,7 +207,7 @@ impl Attributes {
 
 #[cfg(test)]
 mod tests {
-    use super::Class;
+    use super::{is_stylesheet_rel, Class};
     use selectors::attr::CaseSensitivity;
     use test_case::test_case;
 
@@ -208,4 +220,13 @@ mod tests {
         assert!(!class.has_class(b"c", CaseSensitivity::CaseSensitive));
         assert!(!class.has_class(b"C", CaseSensitivity::AsciiCaseInsensitive));
     }
+
+    #[test_case("stylesheet", true)]
+    #[test_case(" preload  StyleSheet ", true)]
+    #[test_case("alternate stylesheet", false)]
+    #[test_case("stylesheets", false)]
+    #[test_case("", false)]
+    fn test_is_stylesheet_rel(rel: &str, expected: bool) {
+        assert_eq!(is_stylesheet_rel(rel), expected);
+    }
 }
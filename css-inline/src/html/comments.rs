@@ -0,0 +1,97 @@
+//! Conditional comments used by Outlook, e.g. `<!--[if mso]><table><![endif]-->`.
+
+const ENDIF: &str = "<![endif]";
+
+/// Whether the comment text is a part of a conditional comment.
+///
+/// It covers both downlevel-hidden comments and markers of downlevel-revealed ones:
+/// `<!--[if !mso]><!-->` and `<!--<![endif]-->`.
+pub(crate) fn is_conditional(text: &str) -> bool {
+    let text = text.trim();
+    starts_with_if(text) || text == ENDIF
+}
+
+fn starts_with_if(text: &str) -> bool {
+    text.get(..3)
+        .is_some_and(|start| start.eq_ignore_ascii_case("[if"))
+}
+
+/// Split the text of a downlevel-hidden conditional comment into its condition
+/// (e.g. `[if mso]>`) and HTML content.
+pub(crate) fn split_downlevel_hidden(text: &str) -> Option<(&str, &str)> {
+    if !starts_with_if(text) {
+        return None;
+    }
+    let content = text.strip_suffix(ENDIF)?;
+    let condition_end = content.find("]>")?.checked_add(2)?;
+    Some((&content[..condition_end], &content[condition_end..]))
+}
+
+/// Whether both HTML snippets contain the same sequence of start and end tags.
+///
+/// The HTML parser closes unclosed elements and adds implied ones, which breaks markup split
+/// across multiple conditional comments, e.g. "ghost tables". Implied `tbody` elements are
+/// ignored as they do not change the table structure.
+pub(crate) fn have_same_tags(left: &str, right: &str) -> bool {
+    tags(left).eq(tags(right))
+}
+
+/// Iterate over lowercase tag names, prefixed with `/` for end tags.
+fn tags(html: &str) -> impl Iterator<Item = String> + '_ {
+    html.split('<').skip(1).filter_map(|tag| {
+        let is_end = tag.starts_with('/');
+        let name: String = tag
+            .trim_start_matches('/')
+            .chars()
+            .take_while(char::is_ascii_alphanumeric)
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        if name.is_empty() || name == "tbody" {
+            None
+        } else if is_end {
+            Some(format!("/{name}"))
+        } else {
+            Some(name)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{have_same_tags, is_conditional, split_downlevel_hidden};
+    use test_case::test_case;
+
+    #[test_case("[if mso]><table><![endif]", true)]
+    #[test_case("[IF gte mso 9]><xml></xml><![endif]", true)]
+    #[test_case("[if !mso]><!", true)]
+    #[test_case("<![endif]", true)]
+    #[test_case(" Regular comment ", false)]
+    fn test_is_conditional(text: &str, expected: bool) {
+        assert_eq!(is_conditional(text), expected);
+    }
+
+    #[test_case("[if mso]><table></table><![endif]", Some(("[if mso]>", "<table></table>")))]
+    #[test_case("[if (gte mso 9)|(IE)]><p>A</p><![endif]", Some(("[if (gte mso 9)|(IE)]>", "<p>A</p>")))]
+    #[test_case("[if !mso]><!", None)]
+    #[test_case("<![endif]", None)]
+    #[test_case("Regular comment", None)]
+    fn test_split_downlevel_hidden(text: &str, expected: Option<(&str, &str)>) {
+        assert_eq!(split_downlevel_hidden(text), expected);
+    }
+
+    #[test_case("<td>A</td>", "<td style=\"color: red;\">A</td>", true)]
+    #[test_case(
+        "<table><tr><td>",
+        "<table><tbody><tr><td></td></tr></tbody></table>",
+        false
+    )]
+    #[test_case("<p>A<br>B</p>", "<p>A<br>B</p>", true)]
+    #[test_case(
+        "<table><tr><td></td></tr></table>",
+        "<table><tbody><tr><td></td></tr></tbody></table>",
+        true
+    )]
+    fn test_have_same_tags(left: &str, right: &str, expected: bool) {
+        assert_eq!(have_same_tags(left, right), expected);
+    }
+}
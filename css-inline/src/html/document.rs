@@ -1,17 +1,24 @@
 use super::{
+    comments,
     element::Element,
     iter::{select, Select},
     node::{Node, NodeData, NodeId},
     parser,
-    selectors::ParseError,
+    presentational::AttributeMapping,
+    propagation::PropertyPropagation,
+    selectors::Selectors,
     serializer::serialize_to,
     InliningMode,
 };
-use crate::{html::DocumentStyleMap, InlineError};
+use crate::{
+    html::{DocumentStyleMap, PseudoElementStyleMap},
+    InlineError, MediaEnvironment,
+};
 use html5ever::{local_name, tendril::StrTendril, LocalName};
 use rustc_hash::FxHashMap;
 use selectors::context::SelectorCaches;
 use std::{fmt, fmt::Formatter, io::Write, iter::successors};
+use url::Url;
 
 /// Minimum input size (bytes) to build element indexes.
 /// For smaller inputs, linear scan is faster than hash map operations.
@@ -51,6 +58,11 @@ pub(crate) struct Document {
     /// Ids of `link` nodes, specifically those with the `rel` attribute value set as `stylesheet`.
     /// They represent the locations (URLs) of all linked stylesheet resources in the document.
     linked_stylesheets: Vec<NodeId>,
+    /// Ids of `style` and `link` nodes that are disabled or whose `media` does not match.
+    /// They are not inlined and are kept in the output untouched.
+    inapplicable_stylesheets: Vec<NodeId>,
+    /// Id of the first `base` element with the `href` attribute.
+    base: Option<NodeId>,
     /// Whether element indexes are enabled for this document.
     use_indexes: bool,
     /// Index: ID attribute value -> `NodeId` (IDs should be unique per document).
@@ -93,6 +105,8 @@ impl Document {
             elements: Vec::with_capacity(capacity),
             styles: Vec::new(),
             linked_stylesheets: Vec::new(),
+            inapplicable_stylesheets: Vec::new(),
+            base: None,
             use_indexes,
             by_id: FxHashMap::default(),
             by_class: FxHashMap::default(),
@@ -132,6 +146,81 @@ impl Document {
         })
     }
 
+    /// The `href` attribute value of the first `base` element.
+    pub(crate) fn base_href(&self) -> Option<&str> {
+        self.base.and_then(|node_id| {
+            self[node_id]
+                .as_element()
+                .and_then(|data| data.attributes.get(local_name!("href")))
+        })
+    }
+
+    /// Record the document's `base` element. Only the first one is used.
+    pub(super) fn set_base(&mut self, node: NodeId) {
+        if self.base.is_none() {
+            self.base = Some(node);
+        }
+    }
+
+    /// Exclude disabled stylesheets and ones whose `media` attribute does not match
+    /// `environment` from inlining. They are either kept untouched or removed from the document.
+    pub(crate) fn exclude_inapplicable_stylesheets(
+        &mut self,
+        environment: &MediaEnvironment,
+        remove: bool,
+    ) {
+        let mut inapplicable = Vec::new();
+        for stylesheets in [&mut self.styles, &mut self.linked_stylesheets] {
+            stylesheets.retain(|node_id| {
+                let Some(element) = self.nodes[node_id.get()].as_element() else {
+                    return true;
+                };
+                let applies = !element.attributes.contains(local_name!("disabled"))
+                    && element
+                        .attributes
+                        .get(local_name!("media"))
+                        .is_none_or(|media| environment.matches(media));
+                if !applies {
+                    inapplicable.push(*node_id);
+                }
+                applies
+            });
+        }
+        if remove {
+            for node_id in inapplicable {
+                self.detach(node_id);
+            }
+        } else {
+            self.inapplicable_stylesheets = inapplicable;
+        }
+    }
+
+    /// Whether the node is a stylesheet excluded from inlining that should be kept untouched.
+    pub(super) fn is_inapplicable_stylesheet(&self, node: NodeId) -> bool {
+        self.inapplicable_stylesheets.contains(&node)
+    }
+
+    /// Ids of comment nodes that are parts of conditional comments, e.g. `<!--[if mso]>`.
+    pub(crate) fn conditional_comments(&self) -> Vec<NodeId> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, node)| match &node.data {
+                NodeData::Comment { text } if comments::is_conditional(text) => {
+                    Some(NodeId::new(idx))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Replace the text of a comment node.
+    pub(crate) fn set_comment_text(&mut self, node: NodeId, new_text: &str) {
+        if let NodeData::Comment { text } = &mut self[node].data {
+            *text = new_text.into();
+        }
+    }
+
     /// Add a new linked stylesheet location.
     pub(super) fn add_linked_stylesheet(&mut self, node: NodeId) {
         self.linked_stylesheets.push(node);
@@ -390,38 +479,46 @@ impl Document {
 
     /// Serialize the document to HTML string.
     #[allow(clippy::too_many_arguments, clippy::fn_params_excessive_bools)]
-    pub(crate) fn serialize<W: Write>(
+    pub(crate) fn serialize<'i, W: Write>(
         &self,
         writer: &mut W,
-        styles: DocumentStyleMap<'_>,
+        styles: DocumentStyleMap<'i>,
+        pseudo_styles: PseudoElementStyleMap<'i>,
         keep_style_tags: bool,
         keep_link_tags: bool,
         minify_css: bool,
         at_rules: Option<&String>,
         mode: InliningMode,
-        apply_width_attributes: bool,
-        apply_height_attributes: bool,
+        attribute_mappings: &[AttributeMapping],
+        resolve_css_variables: bool,
+        url_base: Option<&Url>,
+        xhtml: bool,
+        propagation: Option<&PropertyPropagation>,
     ) -> Result<(), InlineError> {
         serialize_to(
             self,
             writer,
             styles,
+            pseudo_styles,
             keep_style_tags,
             keep_link_tags,
             minify_css,
             at_rules,
             mode,
-            apply_width_attributes,
-            apply_height_attributes,
+            attribute_mappings,
+            resolve_css_variables,
+            url_base,
+            xhtml,
+            propagation,
         )
     }
 
     /// Filter this node iterator to elements matching the given selectors.
-    pub(crate) fn select<'a, 'b, 'c>(
+    pub(crate) fn select<'a, 's, 'c>(
         &'a self,
-        selectors: &'b str,
+        selectors: &'s Selectors,
         caches: &'c mut SelectorCaches,
-    ) -> Result<Select<'a, 'c>, ParseError<'b>> {
+    ) -> Select<'a, 's, 'c> {
         select(self, selectors, caches)
     }
 }
@@ -479,13 +576,17 @@ mod tests {
         doc.serialize(
             &mut buffer,
             vec![None; doc.nodes.len()],
+            [Vec::new(), Vec::new()],
             false,
             false,
             false,
             None,
             InliningMode::Document,
+            &[],
             false,
+            None,
             false,
+            None,
         )
         .expect("Failed to serialize");
         buffer
@@ -91,8 +91,14 @@ impl<'a> Element<'a> {
     #[allow(clippy::inline_always)]
     #[inline(always)]
     pub(crate) fn matches(&self, selector: &Selector, cache: &mut SelectorCaches) -> bool {
+        // Selectors with pseudo-elements, like `a::before`, match their originating elements
+        let mode = if selector.has_pseudo_element() {
+            matching::MatchingMode::ForStatelessPseudoElement
+        } else {
+            matching::MatchingMode::Normal
+        };
         let mut context = matching::MatchingContext::new(
-            matching::MatchingMode::Normal,
+            mode,
             None,
             cache,
             QuirksMode::NoQuirks,
@@ -193,9 +199,8 @@ impl selectors::Element for Element<'_> {
     ) -> bool {
         use self::PseudoClass::*;
         match *pseudo {
-            Active | Focus | Hover | Enabled | Disabled | Checked | Indeterminate | Visited => {
-                false
-            }
+            Active | Focus | FocusVisible | FocusWithin | Hover | Target | Enabled | Disabled
+            | Checked | Indeterminate | Visited => false,
             AnyLink | Link => {
                 self.name().ns == ns!(html)
                     && matches!(
@@ -209,10 +214,11 @@ impl selectors::Element for Element<'_> {
 
     fn match_pseudo_element(
         &self,
-        pseudo: &PseudoElement,
+        _pseudo: &PseudoElement,
         _context: &mut matching::MatchingContext<'_, InlinerSelectors>,
     ) -> bool {
-        match *pseudo {}
+        // Pseudo-elements are not a part of the document tree
+        false
     }
 
     #[inline]
@@ -0,0 +1,68 @@
+//! Text of `::before` and `::after` pseudo-elements from the `content` property.
+use super::attributes::Attributes;
+use cssparser::{Parser, ParserInput, Token};
+use html5ever::LocalName;
+
+/// Compute the text of a pseudo-element from its `content` value.
+///
+/// Strings and `attr()` references are supported. Returns `None` if the pseudo-element is not
+/// generated (`none` or `normal`) or if the value contains anything else, like counters.
+pub(super) fn text(content: &str, attributes: &Attributes) -> Option<String> {
+    let content = content.trim();
+    let content = content
+        .strip_suffix("!important")
+        .map_or(content, str::trim_end);
+    let mut input = ParserInput::new(content);
+    let mut parser = Parser::new(&mut input);
+    let mut text = String::new();
+    let mut is_empty = true;
+    while let Ok(token) = parser.next() {
+        match token {
+            Token::QuotedString(value) => text.push_str(value),
+            Token::Function(name) if name.eq_ignore_ascii_case("attr") => {
+                let value = parser
+                    .parse_nested_block(|parser| {
+                        let name = parser.expect_ident_cloned()?;
+                        parser.expect_exhausted()?;
+                        Ok::<_, cssparser::ParseError<'_, ()>>(
+                            attributes
+                                .get(LocalName::from(name.to_ascii_lowercase()))
+                                .map(str::to_string)
+                                .unwrap_or_default(),
+                        )
+                    })
+                    .ok()?;
+                text.push_str(&value);
+            }
+            // `none`, `normal` and unsupported values like counters
+            _ => return None,
+        }
+        is_empty = false;
+    }
+    (!is_empty).then_some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::text;
+    use crate::html::attributes::Attributes;
+    use html5ever::{local_name, ns, Attribute, QualName};
+    use test_case::test_case;
+
+    #[test_case("\"→ \"", Some("→ "))]
+    #[test_case("'\\2022'", Some("\u{2022}"))]
+    #[test_case("\"\"", Some(""))]
+    #[test_case("\"[\" attr(title) \"]\" !important", Some("[Home]"))]
+    #[test_case("attr(data-missing)", Some(""))]
+    #[test_case("none", None)]
+    #[test_case("normal", None)]
+    #[test_case("counter(item)", None)]
+    #[test_case("open-quote", None)]
+    fn test_text(content: &str, expected: Option<&str>) {
+        let attributes = Attributes::new(vec![Attribute {
+            name: QualName::new(None, ns!(), local_name!("title")),
+            value: "Home".into(),
+        }]);
+        assert_eq!(text(content, &attributes).as_deref(), expected);
+    }
+}
@@ -2,8 +2,7 @@ use super::{
     document::Document,
     element::Element,
     node::{NodeData, NodeId},
-    selectors::{ParseError, SelectorAnchor, Selectors},
-    Specificity,
+    selectors::{SelectorAnchor, Selectors},
 };
 use selectors::context::SelectorCaches;
 
@@ -26,56 +25,46 @@ impl ElementSource<'_> {
     }
 }
 
-/// Compile selectors from a string and create an element iterator that yields elements matching these selectors.
+/// Create an element iterator that yields elements matching compiled selectors.
 #[inline]
-pub(crate) fn select<'a, 'b, 'c>(
+pub(crate) fn select<'a, 's, 'c>(
     document: &'a Document,
-    selectors: &'b str,
+    selectors: &'s Selectors,
     caches: &'c mut SelectorCaches,
-) -> Result<Select<'a, 'c>, ParseError<'b>> {
-    Selectors::compile(selectors).map(|selectors| {
-        // Only use indexes if they were built during parsing
-        let source = if document.has_indexes() {
-            match selectors.anchor() {
-                SelectorAnchor::Id(id) => ElementSource::Single(document.get_by_id(id.as_inner())),
-                SelectorAnchor::Class(class) => {
-                    ElementSource::Slice(document.get_by_class(class.as_inner()).iter())
-                }
-                SelectorAnchor::Tag(tag) => {
-                    ElementSource::Slice(document.get_by_tag(tag.as_inner()).iter())
-                }
-                SelectorAnchor::None => ElementSource::Slice(document.elements.iter()),
+) -> Select<'a, 's, 'c> {
+    // Only use indexes if they were built during parsing
+    let source = if document.has_indexes() {
+        match selectors.anchor() {
+            SelectorAnchor::Id(id) => ElementSource::Single(document.get_by_id(id.as_inner())),
+            SelectorAnchor::Class(class) => {
+                ElementSource::Slice(document.get_by_class(class.as_inner()).iter())
             }
-        } else {
-            ElementSource::Slice(document.elements.iter())
-        };
-        Select {
-            document,
-            caches,
-            source,
-            selectors,
+            SelectorAnchor::Tag(tag) => {
+                ElementSource::Slice(document.get_by_tag(tag.as_inner()).iter())
+            }
+            SelectorAnchor::None => ElementSource::Slice(document.elements.iter()),
         }
-    })
+    } else {
+        ElementSource::Slice(document.elements.iter())
+    };
+    Select {
+        document,
+        caches,
+        source,
+        selectors,
+    }
 }
 
 /// An element iterator adaptor that yields elements matching given selectors.
-pub(crate) struct Select<'a, 'c> {
+pub(crate) struct Select<'a, 's, 'c> {
     document: &'a Document,
     caches: &'c mut SelectorCaches,
     source: ElementSource<'a>,
     /// The selectors to be matched.
-    selectors: Selectors,
-}
-
-impl Select<'_, '_> {
-    /// Specificity of the first selector in the list of selectors.
-    #[inline]
-    pub(crate) fn specificity(&self) -> Specificity {
-        Specificity::new(self.selectors.0[0].specificity())
-    }
+    selectors: &'s Selectors,
 }
 
-impl<'a> Iterator for Select<'a, '_> {
+impl<'a> Iterator for Select<'a, '_, '_> {
     type Item = Element<'a>;
 
     #[inline]
@@ -1,16 +1,26 @@
 mod attributes;
+mod comments;
 mod document;
 mod element;
+mod generated_content;
 mod iter;
 mod node;
 mod parser;
+mod presentational;
+mod propagation;
 mod selectors;
 mod serializer;
+mod variables;
 
-pub(crate) use self::selectors::Specificity;
+pub(crate) use self::selectors::{
+    split_list as split_selector_list, ParseError, PseudoElement, Selectors, Specificity,
+};
+pub(crate) use comments::{have_same_tags, split_downlevel_hidden};
 pub(crate) use document::Document;
 pub(crate) use node::{NodeData, NodeId};
 pub(crate) use parser::InliningMode;
+pub use presentational::AttributeMapping;
+pub use propagation::PropertyPropagation;
 use smallvec::SmallVec;
 
 /// Styles for a single element: (property name, specificity, value)
@@ -18,3 +28,6 @@ pub(crate) type ElementStyleMap<'i> = SmallVec<[(&'i str, Specificity, &'i str);
 
 /// Maps node IDs to their accumulated styles.
 pub(crate) type DocumentStyleMap<'i> = Vec<Option<ElementStyleMap<'i>>>;
+
+/// Maps node IDs to accumulated styles of their pseudo-elements, by `PseudoElement::index`.
+pub(crate) type PseudoElementStyleMap<'i> = [DocumentStyleMap<'i>; 2];
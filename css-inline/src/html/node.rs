@@ -74,6 +74,8 @@ pub(crate) enum NodeData {
     Document,
     Doctype {
         name: StrTendril,
+        public_id: StrTendril,
+        system_id: StrTendril,
     },
     Text {
         text: StrTendril,
@@ -1,5 +1,5 @@
 use super::{
-    attributes::should_ignore,
+    attributes::{is_stylesheet_rel, should_ignore},
     document::Document,
     node::{ElementData, Node, NodeData, NodeId},
 };
@@ -124,8 +124,17 @@ impl Sink {
         self.push_node(NodeData::ProcessingInstruction { target, data })
     }
 
-    fn push_doctype(&self, name: StrTendril) -> NodeId {
-        self.push_node(NodeData::Doctype { name })
+    fn push_doctype(
+        &self,
+        name: StrTendril,
+        public_id: StrTendril,
+        system_id: StrTendril,
+    ) -> NodeId {
+        self.push_node(NodeData::Doctype {
+            name,
+            public_id,
+            system_id,
+        })
     }
 
     /// Append a new node or text to the document.
@@ -205,7 +214,7 @@ impl TreeSink for Sink {
                 let mut rel_stylesheet = false;
                 let mut href_non_empty = false;
                 for attr in &attrs {
-                    if attr.name.local == local_name!("rel") && &*attr.value == "stylesheet" {
+                    if attr.name.local == local_name!("rel") && is_stylesheet_rel(&attr.value) {
                         rel_stylesheet = true;
                     }
                     // Skip links with empty `href` attributes
@@ -221,7 +230,14 @@ impl TreeSink for Sink {
                 (false, false)
             }
         };
+        let is_base = name.expanded() == expanded_name!(html "base")
+            && attrs
+                .iter()
+                .any(|attr| attr.name.local == local_name!("href"));
         let element = self.push_element(name, attrs, inlining_ignored);
+        if is_base {
+            self.document.borrow_mut().set_base(element);
+        }
         // Collect `style` tags and linked stylesheets separately to use them for CSS inlining later.
         if is_style {
             self.document.borrow_mut().add_style(element);
@@ -266,10 +282,10 @@ impl TreeSink for Sink {
     fn append_doctype_to_document(
         &self,
         name: StrTendril,
-        _public_id: StrTendril,
-        _system_id: StrTendril,
+        public_id: StrTendril,
+        system_id: StrTendril,
     ) {
-        let node = self.push_doctype(name);
+        let node = self.push_doctype(name, public_id, system_id);
         self.document
             .borrow_mut()
             .append(NodeId::document_id(), node);
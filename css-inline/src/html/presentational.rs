@@ -0,0 +1,336 @@
+//! Legacy presentational HTML attributes generated from CSS, e.g. `bgcolor` from
+//! `background-color`. Some email clients, notably Outlook, respect only these attributes.
+use crate::InlineError;
+use html5ever::{local_name, LocalName};
+use std::io::Write;
+
+/// A mapping of a CSS property to a presentational HTML attribute.
+///
+/// Each mapping applies only to elements that support the attribute. The attribute is not
+/// written if the element already has it or if the CSS value can not be expressed with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AttributeMapping {
+    /// `width` to `width` on `table`, `td`, `th` and `img`.
+    /// Pixel values are written without units; percentages are supported on table elements.
+    Width,
+    /// `height` to `height` on `table`, `td`, `th` and `img`.
+    /// Pixel values are written without units; percentages are supported on table elements.
+    Height,
+    /// `background-color` to `bgcolor` on `body`, `table`, `tr`, `td` and `th`.
+    /// Only hex and named colors are supported.
+    BackgroundColor,
+    /// `text-align` to `align` on `div`, `p`, headings, `tr`, `td` and `th`.
+    TextAlign,
+    /// `vertical-align` to `valign` on `tr`, `td` and `th`.
+    VerticalAlign,
+    /// `border-spacing` with a single pixel value to `cellspacing` on `table`.
+    BorderSpacing,
+    /// `padding` of `td` and `th` cells to `cellpadding` on their `table`, if all of them
+    /// have the same single pixel value. The padding of the table itself is not used.
+    Padding,
+    /// The width from `border` to `border` on `table` and `img`.
+    Border,
+}
+
+impl AttributeMapping {
+    /// All available mappings.
+    pub const ALL: [AttributeMapping; 8] = [
+        AttributeMapping::Width,
+        AttributeMapping::Height,
+        AttributeMapping::BackgroundColor,
+        AttributeMapping::TextAlign,
+        AttributeMapping::VerticalAlign,
+        AttributeMapping::BorderSpacing,
+        AttributeMapping::Padding,
+        AttributeMapping::Border,
+    ];
+
+    /// The CSS property this mapping reads. For [`AttributeMapping::Padding`], it is read
+    /// from the cells of the table.
+    pub(super) fn property(self) -> &'static str {
+        match self {
+            AttributeMapping::Width => "width",
+            AttributeMapping::Height => "height",
+            AttributeMapping::BackgroundColor => "background-color",
+            AttributeMapping::TextAlign => "text-align",
+            AttributeMapping::VerticalAlign => "vertical-align",
+            AttributeMapping::BorderSpacing => "border-spacing",
+            AttributeMapping::Padding => "padding",
+            AttributeMapping::Border => "border",
+        }
+    }
+
+    /// The HTML attribute this mapping writes.
+    pub(super) fn attribute(self) -> LocalName {
+        match self {
+            AttributeMapping::Width => local_name!("width"),
+            AttributeMapping::Height => local_name!("height"),
+            AttributeMapping::BackgroundColor => local_name!("bgcolor"),
+            AttributeMapping::TextAlign => local_name!("align"),
+            AttributeMapping::VerticalAlign => local_name!("valign"),
+            AttributeMapping::BorderSpacing => local_name!("cellspacing"),
+            AttributeMapping::Padding => local_name!("cellpadding"),
+            AttributeMapping::Border => local_name!("border"),
+        }
+    }
+
+    /// Check if an element supports the attribute.
+    pub(super) fn applies_to(self, element: &LocalName) -> bool {
+        match self {
+            AttributeMapping::Width | AttributeMapping::Height => {
+                is_table_element(element) || *element == local_name!("img")
+            }
+            AttributeMapping::BackgroundColor => {
+                is_table_element(element)
+                    || matches!(*element, local_name!("body") | local_name!("tr"))
+            }
+            AttributeMapping::TextAlign => matches!(
+                *element,
+                local_name!("div")
+                    | local_name!("p")
+                    | local_name!("h1")
+                    | local_name!("h2")
+                    | local_name!("h3")
+                    | local_name!("h4")
+                    | local_name!("h5")
+                    | local_name!("h6")
+                    | local_name!("tr")
+                    | local_name!("td")
+                    | local_name!("th")
+            ),
+            AttributeMapping::VerticalAlign => matches!(
+                *element,
+                local_name!("tr") | local_name!("td") | local_name!("th")
+            ),
+            AttributeMapping::BorderSpacing | AttributeMapping::Padding => {
+                *element == local_name!("table")
+            }
+            AttributeMapping::Border => {
+                matches!(*element, local_name!("table") | local_name!("img"))
+            }
+        }
+    }
+
+    /// Convert a CSS value to the attribute value.
+    pub(super) fn convert<'a>(
+        self,
+        value: &'a str,
+        element: &LocalName,
+    ) -> Option<AttributeValue<'a>> {
+        let value = value.trim();
+        let value = value
+            .strip_suffix("!important")
+            .map_or(value, str::trim_end);
+        match self {
+            AttributeMapping::Width | AttributeMapping::Height => {
+                extract_dimension_value(value, is_table_element(element))
+            }
+            AttributeMapping::BackgroundColor => {
+                is_legacy_color(value).then_some(AttributeValue::Verbatim(value))
+            }
+            AttributeMapping::TextAlign => ["left", "right", "center", "justify"]
+                .into_iter()
+                .find(|keyword| keyword.eq_ignore_ascii_case(value))
+                .map(AttributeValue::Keyword),
+            AttributeMapping::VerticalAlign => ["top", "middle", "bottom", "baseline"]
+                .into_iter()
+                .find(|keyword| keyword.eq_ignore_ascii_case(value))
+                .map(AttributeValue::Keyword),
+            AttributeMapping::BorderSpacing | AttributeMapping::Padding => {
+                match extract_dimension_value(value, false)? {
+                    AttributeValue::Verbatim(value) => Some(AttributeValue::Verbatim(value)),
+                    _ => None,
+                }
+            }
+            AttributeMapping::Border => {
+                let mut width = None;
+                for component in value.split_ascii_whitespace() {
+                    if component.eq_ignore_ascii_case("none") {
+                        return Some(AttributeValue::Keyword("0"));
+                    }
+                    if let Some(AttributeValue::Verbatim(value)) =
+                        extract_dimension_value(component, false)
+                    {
+                        width = Some(AttributeValue::Verbatim(value));
+                    }
+                }
+                width
+            }
+        }
+    }
+}
+
+/// Check if an element is a table element (supports percentage values).
+#[inline]
+fn is_table_element(name: &LocalName) -> bool {
+    matches!(
+        *name,
+        local_name!("table") | local_name!("td") | local_name!("th")
+    )
+}
+
+/// Hex colors and color keywords, which are understood by legacy clients.
+fn is_legacy_color(value: &str) -> bool {
+    if let Some(hex) = value.strip_prefix('#') {
+        matches!(hex.len(), 3 | 6) && hex.bytes().all(|byte| byte.is_ascii_hexdigit())
+    } else {
+        !value.is_empty()
+            && value.bytes().all(|byte| byte.is_ascii_alphabetic())
+            && ![
+                "currentcolor",
+                "inherit",
+                "initial",
+                "revert",
+                "transparent",
+                "unset",
+            ]
+            .into_iter()
+            .any(|keyword| keyword.eq_ignore_ascii_case(value))
+    }
+}
+
+/// Extracted value for an HTML attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum AttributeValue<'a> {
+    /// Value written as is, e.g. a number without the `px` suffix
+    Verbatim(&'a str),
+    /// Numeric value that needs % suffix when written
+    Percent(&'a str),
+    /// A keyword, like "auto"
+    Keyword(&'static str),
+}
+
+impl AttributeValue<'_> {
+    #[inline]
+    pub(super) fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), InlineError> {
+        match self {
+            AttributeValue::Verbatim(value) => writer.write_all(value.as_bytes())?,
+            AttributeValue::Percent(n) => {
+                writer.write_all(n.as_bytes())?;
+                writer.write_all(b"%")?;
+            }
+            AttributeValue::Keyword(keyword) => writer.write_all(keyword.as_bytes())?,
+        }
+        Ok(())
+    }
+}
+
+/// Extract dimension value for HTML attribute from CSS value.
+#[inline]
+#[allow(clippy::arithmetic_side_effects)]
+fn extract_dimension_value(value: &str, allow_percent: bool) -> Option<AttributeValue<'_>> {
+    let value = value.trim();
+
+    if value.eq_ignore_ascii_case("auto") {
+        return Some(AttributeValue::Keyword("auto"));
+    }
+
+    // Find where the numeric part ends
+    let bytes = value.as_bytes();
+    let mut end = 0;
+    let mut has_dot = false;
+
+    // Handle optional leading sign
+    if bytes.first() == Some(&b'-') || bytes.first() == Some(&b'+') {
+        end = 1;
+    }
+
+    // Parse digits and optional decimal point
+    while end < bytes.len() {
+        match bytes[end] {
+            b'0'..=b'9' => end += 1,
+            b'.' if !has_dot => {
+                has_dot = true;
+                end += 1;
+            }
+            _ => break,
+        }
+    }
+
+    // Must have at least one digit
+    if end == 0 || (end == 1 && (bytes[0] == b'-' || bytes[0] == b'+')) {
+        return None;
+    }
+
+    let numeric_part = &value[..end];
+    // Trim whitespace between number and unit (e.g., "100 px") for lenient parsing
+    let unit_part = value[end..].trim();
+    // Strip `!important` suffix if present
+    let unit_part = unit_part
+        .strip_suffix("!important")
+        .map_or(unit_part, str::trim);
+
+    match unit_part {
+        // Pixel values - strip the 'px' suffix
+        "" | "px" => Some(AttributeValue::Verbatim(numeric_part)),
+        // Percentage - only allowed for table elements
+        "%" if allow_percent => Some(AttributeValue::Percent(numeric_part)),
+        // All other units are not supported
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AttributeMapping, AttributeValue};
+    use html5ever::local_name;
+    use test_case::test_case;
+
+    #[test_case(
+        AttributeMapping::Width,
+        "100px",
+        Some(AttributeValue::Verbatim("100"))
+    )]
+    #[test_case(AttributeMapping::Width, "50%", Some(AttributeValue::Percent("50")))]
+    #[test_case(AttributeMapping::Width, "10em", None)]
+    #[test_case(
+        AttributeMapping::BackgroundColor,
+        "#FFF !important",
+        Some(AttributeValue::Verbatim("#FFF"))
+    )]
+    #[test_case(
+        AttributeMapping::BackgroundColor,
+        "red",
+        Some(AttributeValue::Verbatim("red"))
+    )]
+    #[test_case(AttributeMapping::BackgroundColor, "rgb(0, 0, 0)", None)]
+    #[test_case(AttributeMapping::BackgroundColor, "transparent", None)]
+    #[test_case(AttributeMapping::BackgroundColor, "#abcd", None)]
+    #[test_case(
+        AttributeMapping::TextAlign,
+        "Center",
+        Some(AttributeValue::Keyword("center"))
+    )]
+    #[test_case(AttributeMapping::TextAlign, "start", None)]
+    #[test_case(
+        AttributeMapping::VerticalAlign,
+        "top",
+        Some(AttributeValue::Keyword("top"))
+    )]
+    #[test_case(
+        AttributeMapping::BorderSpacing,
+        "0",
+        Some(AttributeValue::Verbatim("0"))
+    )]
+    #[test_case(AttributeMapping::BorderSpacing, "2px 4px", None)]
+    #[test_case(
+        AttributeMapping::Padding,
+        "10px",
+        Some(AttributeValue::Verbatim("10"))
+    )]
+    #[test_case(AttributeMapping::Padding, "auto", None)]
+    #[test_case(
+        AttributeMapping::Border,
+        "1px solid #000",
+        Some(AttributeValue::Verbatim("1"))
+    )]
+    #[test_case(AttributeMapping::Border, "none", Some(AttributeValue::Keyword("0")))]
+    #[test_case(AttributeMapping::Border, "solid thin", None)]
+    fn test_convert(
+        mapping: AttributeMapping,
+        value: &str,
+        expected: Option<AttributeValue<'static>>,
+    ) {
+        assert_eq!(mapping.convert(value, &local_name!("table")), expected);
+    }
+}
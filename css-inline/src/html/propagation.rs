@@ -0,0 +1,229 @@
+//! Propagation of inherited CSS properties for email clients that break inheritance.
+//!
+//! Outlook's Word rendering engine and some webmail clients do not inherit properties like
+//! `font-family` or `color` into tables, so the inherited values are written explicitly.
+use html5ever::{local_name, LocalName};
+
+/// Properties propagated by default.
+const DEFAULT_PROPERTIES: [&str; 4] = ["font-family", "color", "font-size", "line-height"];
+
+/// Inherited CSS properties to write explicitly onto descendant elements.
+///
+/// A descendant gets the computed value of its parent unless it declares the property itself,
+/// either directly or via a shorthand like `font`. Relative font sizes, e.g. `1.5em` or `80%`,
+/// are resolved against the parent's font size in pixels. Relative values that can not be
+/// resolved, including line heights in `em` or `%`, are not propagated below the element that
+/// declares them.
+///
+/// Elements that browsers style with their own value of a property keep it, together with
+/// their descendants, e.g. `color` of links, `font-size` of headings or `font-family` of `code`.
+///
+/// ```rust
+/// use css_inline::{CSSInliner, PropertyPropagation};
+///
+/// let inliner = CSSInliner::options()
+///     .propagate_properties(Some(PropertyPropagation::default().table_cells_only(true)))
+///     .build();
+/// let inlined = inliner.inline(r#"<html><head><style>
+/// body { color: #333; }
+/// </style></head><body><table><tr><td>Hello</td></tr></table></body></html>"#);
+/// assert_eq!(
+///     inlined.expect("Inlining failed"),
+///     r#"<html><head></head><body style="color: #333;"><table style="color: #333;"><tbody><tr><td style="color: #333;">Hello</td></tr></tbody></table></body></html>"#
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PropertyPropagation {
+    pub(crate) properties: Vec<String>,
+    pub(crate) table_cells_only: bool,
+}
+
+impl PropertyPropagation {
+    /// Propagate the given properties to all descendant elements.
+    #[must_use]
+    pub fn new(properties: Vec<String>) -> PropertyPropagation {
+        PropertyPropagation {
+            properties: properties
+                .into_iter()
+                .map(|property| property.trim().to_ascii_lowercase())
+                .collect(),
+            table_cells_only: false,
+        }
+    }
+
+    /// Propagate properties only to `table`, `td` and `th` elements.
+    #[must_use]
+    pub fn table_cells_only(mut self, table_cells_only: bool) -> Self {
+        self.table_cells_only = table_cells_only;
+        self
+    }
+
+    /// Whether inherited values are written onto the element.
+    pub(super) fn applies_to(&self, element: &LocalName) -> bool {
+        if self.table_cells_only {
+            matches!(
+                *element,
+                local_name!("table") | local_name!("td") | local_name!("th")
+            )
+        } else {
+            // Skip elements that are not rendered
+            !matches!(
+                *element,
+                local_name!("head")
+                    | local_name!("title")
+                    | local_name!("meta")
+                    | local_name!("link")
+                    | local_name!("style")
+                    | local_name!("script")
+                    | local_name!("base")
+                    | local_name!("noscript")
+                    | local_name!("template")
+            )
+        }
+    }
+}
+
+/// Whether browsers give the element its own value of the property instead of inheriting it.
+pub(super) fn has_default_value(element: &LocalName, property: &str) -> bool {
+    match property {
+        "color" => *element == local_name!("a"),
+        "font-size" => matches!(
+            *element,
+            local_name!("h1")
+                | local_name!("h2")
+                | local_name!("h3")
+                | local_name!("h4")
+                | local_name!("h5")
+                | local_name!("h6")
+                | local_name!("small")
+                | local_name!("big")
+                | local_name!("sub")
+                | local_name!("sup")
+        ),
+        "font-family" => matches!(
+            *element,
+            local_name!("code")
+                | local_name!("pre")
+                | local_name!("kbd")
+                | local_name!("samp")
+                | local_name!("tt")
+        ),
+        "font-weight" => matches!(
+            *element,
+            local_name!("h1")
+                | local_name!("h2")
+                | local_name!("h3")
+                | local_name!("h4")
+                | local_name!("h5")
+                | local_name!("h6")
+                | local_name!("b")
+                | local_name!("strong")
+                | local_name!("th")
+        ),
+        "font-style" => matches!(
+            *element,
+            local_name!("i")
+                | local_name!("em")
+                | local_name!("cite")
+                | local_name!("var")
+                | local_name!("dfn")
+                | local_name!("address")
+        ),
+        _ => false,
+    }
+}
+
+impl Default for PropertyPropagation {
+    /// Propagate `font-family`, `color`, `font-size` and `line-height` to all descendants.
+    fn default() -> Self {
+        PropertyPropagation::new(DEFAULT_PROPERTIES.into_iter().map(str::to_string).collect())
+    }
+}
+
+/// The value that descendants of an element declaring `value` inherit.
+///
+/// Relative values would compound if they were written onto every descendant.
+pub(super) fn computed_value(
+    property: &str,
+    value: &str,
+    parent_value: Option<&str>,
+) -> Option<Box<str>> {
+    if property != "font-size" && property != "line-height" {
+        return Some(value.into());
+    }
+    let factor = match split_length(value) {
+        Some((number, "em")) => number,
+        Some((number, "%")) => number / 100.0,
+        _ if is_relative(value) => return None,
+        _ => return Some(value.into()),
+    };
+    if property == "line-height" {
+        // Resolved against the element's own font size, which is not always known
+        return None;
+    }
+    let (parent, _) = parent_value
+        .and_then(split_length)
+        .filter(|(_, unit)| *unit == "px")?;
+    let pixels = (parent * factor * 1000.0).round() / 1000.0;
+    Some(format!("{pixels}px").into())
+}
+
+/// Split a dimension like `1.5em` into its number and lowercase unit.
+fn split_length(value: &str) -> Option<(f64, &str)> {
+    let end = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let number = value[..end].parse().ok()?;
+    let unit = &value[end..];
+    unit.chars()
+        .all(|c| c.is_ascii_lowercase() || c == '%')
+        .then_some((number, unit))
+}
+
+/// Whether the value depends on the font size of the element, e.g. `1.2em` or `calc(100% + 2px)`.
+fn is_relative(value: &str) -> bool {
+    let value = value.to_ascii_lowercase();
+    value.contains('%')
+        || value.contains("smaller")
+        || value.contains("larger")
+        || value
+            .match_indices("em")
+            .any(|(idx, _)| !value[..idx].ends_with('r'))
+        || ["ex", "ch"].iter().any(|unit| {
+            value
+                .match_indices(unit)
+                .any(|(idx, _)| value[..idx].ends_with(|c: char| c.is_ascii_digit() || c == '.'))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::computed_value;
+    use test_case::test_case;
+
+    #[test_case("font-size", "1.5em", Some("16px"), Some("24px"))]
+    #[test_case("font-size", "87.5%", Some("16px"), Some("14px"))]
+    #[test_case("font-size", "1.1em", Some("13px"), Some("14.3px"))]
+    #[test_case("font-size", "1.5em", Some("12pt"), None)]
+    #[test_case("font-size", "1.5em", None, None)]
+    #[test_case("font-size", "smaller", Some("16px"), None)]
+    #[test_case("font-size", "calc(1em + 2px)", Some("16px"), None)]
+    #[test_case("font-size", "2rem", Some("16px"), Some("2rem"))]
+    #[test_case("font-size", "12px", Some("16px"), Some("12px"))]
+    #[test_case("line-height", "1.5", Some("20px"), Some("1.5"))]
+    #[test_case("line-height", "150%", Some("20px"), None)]
+    #[test_case("line-height", "1.2em", None, None)]
+    #[test_case("line-height", "20px", None, Some("20px"))]
+    #[test_case("color", "red", None, Some("red"))]
+    fn test_computed_value(
+        property: &str,
+        value: &str,
+        parent_value: Option<&str>,
+        expected: Option<&str>,
+    ) {
+        assert_eq!(
+            computed_value(property, value, parent_value).as_deref(),
+            expected
+        );
+    }
+}
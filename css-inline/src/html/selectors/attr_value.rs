@@ -1,10 +1,11 @@
 use cssparser::ToCss;
-use html5ever::tendril::StrTendril;
 use std::{fmt, fmt::Write};
 
-/// To use `StrTendril` in selectors, we need to implement `ToCss` on a wrapper.
+/// To use attribute values in selectors, we need to implement `ToCss` on a wrapper.
+///
+/// The value is owned, so that compiled selectors can be shared between threads.
 #[derive(Clone, Debug, Eq, PartialEq)]
-pub(crate) struct AttrValue(StrTendril);
+pub(crate) struct AttrValue(Box<str>);
 
 impl ToCss for AttrValue {
     fn to_css<W>(&self, dest: &mut W) -> fmt::Result
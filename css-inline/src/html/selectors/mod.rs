@@ -8,11 +8,16 @@ mod selector_impl;
 pub(super) use attr_value::AttrValue;
 pub(super) use local_name::LocalName;
 pub(super) use pseudo_classes::PseudoClass;
-pub(super) use pseudo_elements::PseudoElement;
+pub(crate) use pseudo_elements::PseudoElement;
 pub(super) use selector_impl::InlinerSelectors;
 
+use cssparser::Delimiter;
 use selectors::{
-    parser::{Component, ParseRelative, Selector as GenericSelector, SelectorParseErrorKind},
+    parser::{
+        Component, ParseRelative, RelativeSelector, Selector as GenericSelector,
+        SelectorParseErrorKind,
+    },
+    visitor::SelectorVisitor,
     SelectorList,
 };
 use smallvec::SmallVec;
@@ -45,6 +50,30 @@ fn parse(selectors: &str) -> Result<SelectorList<InlinerSelectors>, ParseError<'
     SelectorList::parse(&parser::SelectorParser, parser, ParseRelative::No)
 }
 
+/// Split a selector list into individual complex selectors.
+///
+/// Commas inside functional pseudo-classes like `:is(h1, h2)`, attribute selectors and strings
+/// do not separate selectors. Selectors are returned as written, including whitespace.
+pub(crate) fn split_list(selectors: &str) -> SmallVec<[&str; 2]> {
+    let mut input = cssparser::ParserInput::new(selectors);
+    let mut parser = cssparser::Parser::new(&mut input);
+    let mut list = SmallVec::new();
+    loop {
+        let start = parser.position();
+        let _ = parser.parse_until_before(Delimiter::Comma, |parser| {
+            // Nested blocks are skipped as a whole
+            while parser.next_including_whitespace_and_comments().is_ok() {}
+            Ok::<_, cssparser::ParseError<'_, ()>>(())
+        });
+        list.push(parser.slice_from(start));
+        // Either a comma or the end of the input
+        if parser.next().is_err() {
+            break;
+        }
+    }
+    list
+}
+
 /// The anchor type for indexed selector lookup.
 /// Determines which index to use for fast element lookup.
 #[derive(Debug)]
@@ -83,13 +112,52 @@ fn selector_anchor(selector: &Selector) -> SelectorAnchor<'_> {
     SelectorAnchor::None
 }
 
+/// Looks for pseudo-classes that can not be matched against a static document.
+struct DynamicPseudoClassVisitor;
+
+impl SelectorVisitor for DynamicPseudoClassVisitor {
+    type Impl = InlinerSelectors;
+
+    // Returning `false` stops the traversal
+    fn visit_simple_selector(&mut self, component: &Component<InlinerSelectors>) -> bool {
+        !matches!(component, Component::NonTSPseudoClass(pseudo) if pseudo.is_dynamic())
+    }
+
+    fn visit_relative_selector_list(
+        &mut self,
+        list: &[RelativeSelector<InlinerSelectors>],
+    ) -> bool {
+        list.iter().all(|relative| relative.selector.visit(self))
+    }
+}
+
 impl Selectors {
     /// Compile a list of selectors.
     #[inline]
-    pub(super) fn compile(selectors: &str) -> Result<Selectors, ParseError<'_>> {
+    pub(crate) fn compile(selectors: &str) -> Result<Selectors, ParseError<'_>> {
         parse(selectors).map(|list| Selectors(list.slice().into()))
     }
 
+    /// Specificity of the first selector in the list of selectors.
+    #[inline]
+    pub(crate) fn specificity(&self) -> Specificity {
+        Specificity::new(self.0[0].specificity())
+    }
+
+    /// Whether any selector contains a dynamic pseudo-class, like `:hover` or `:visited`.
+    #[inline]
+    pub(crate) fn is_dynamic(&self) -> bool {
+        self.0
+            .iter()
+            .any(|selector| !selector.visit(&mut DynamicPseudoClassVisitor))
+    }
+
+    /// The pseudo-element of the first selector, e.g. `::before` in `a::before`.
+    #[inline]
+    pub(crate) fn pseudo_element(&self) -> Option<PseudoElement> {
+        self.0[0].pseudo_element().copied()
+    }
+
     /// Iterator over selectors.
     #[inline]
     pub(super) fn iter(&self) -> impl Iterator<Item = &Selector> {
@@ -210,4 +278,24 @@ mod tests {
     fn test_none_anchor(selector: &str) {
         assert_anchor!(selector, None);
     }
+
+    #[test_case("a:hover", true; "hover")]
+    #[test_case("input:FOCUS-VISIBLE", true; "case_insensitive")]
+    #[test_case("div:not(:visited) span", true; "nested")]
+    #[test_case("div:not(.a, #b:target)", true; "nested_list")]
+    #[test_case("a:link", false; "static_link")]
+    #[test_case("input:checked", false; "checked")]
+    fn test_is_dynamic(selector: &str, expected: bool) {
+        let compiled = Selectors::compile(selector).expect("Invalid selector");
+        assert_eq!(compiled.is_dynamic(), expected);
+    }
+
+    #[test_case("h1, h2", &["h1", " h2"]; "simple")]
+    #[test_case(":is(h1, h2) > a", &[":is(h1, h2) > a"]; "is")]
+    #[test_case("a:not(.x, .y), p", &["a:not(.x, .y)", " p"]; "not")]
+    #[test_case(r#"[title="a,b"],[title='c,d']"#, &[r#"[title="a,b"]"#, "[title='c,d']"]; "strings")]
+    #[test_case("h1,", &["h1", ""]; "trailing_comma")]
+    fn test_split_list(selectors: &str, expected: &[&str]) {
+        assert_eq!(split_list(selectors).as_slice(), expected);
+    }
 }
@@ -1,4 +1,7 @@
-use super::{pseudo_classes::PseudoClass, selector_impl::InlinerSelectors, ParseError};
+use super::{
+    pseudo_classes::PseudoClass, pseudo_elements::PseudoElement, selector_impl::InlinerSelectors,
+    ParseError,
+};
 use cssparser::{CowRcStr, SourceLocation};
 use selectors::{parser::SelectorParseErrorKind, Parser};
 
@@ -9,6 +12,14 @@ impl<'i> Parser<'i> for SelectorParser {
     type Impl = InlinerSelectors;
     type Error = SelectorParseErrorKind<'i>;
 
+    fn parse_is_and_where(&self) -> bool {
+        true
+    }
+
+    fn parse_has(&self) -> bool {
+        true
+    }
+
     #[allow(clippy::enum_glob_use)]
     fn parse_non_ts_pseudo_class(
         &self,
@@ -26,8 +37,14 @@ impl<'i> Parser<'i> for SelectorParser {
             Ok(Active)
         } else if name.eq_ignore_ascii_case("focus") {
             Ok(Focus)
+        } else if name.eq_ignore_ascii_case("focus-visible") {
+            Ok(FocusVisible)
+        } else if name.eq_ignore_ascii_case("focus-within") {
+            Ok(FocusWithin)
         } else if name.eq_ignore_ascii_case("hover") {
             Ok(Hover)
+        } else if name.eq_ignore_ascii_case("target") {
+            Ok(Target)
         } else if name.eq_ignore_ascii_case("enabled") {
             Ok(Enabled)
         } else if name.eq_ignore_ascii_case("disabled") {
@@ -44,4 +61,22 @@ impl<'i> Parser<'i> for SelectorParser {
             )
         }
     }
+
+    fn parse_pseudo_element(
+        &self,
+        location: SourceLocation,
+        name: CowRcStr<'i>,
+    ) -> Result<PseudoElement, ParseError<'i>> {
+        if name.eq_ignore_ascii_case("before") {
+            Ok(PseudoElement::Before)
+        } else if name.eq_ignore_ascii_case("after") {
+            Ok(PseudoElement::After)
+        } else {
+            Err(
+                location.new_custom_error(SelectorParseErrorKind::UnsupportedPseudoClassOrElement(
+                    name,
+                )),
+            )
+        }
+    }
 }
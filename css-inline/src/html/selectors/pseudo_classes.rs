@@ -10,7 +10,10 @@ pub(crate) enum PseudoClass {
     Visited,
     Active,
     Focus,
+    FocusVisible,
+    FocusWithin,
     Hover,
+    Target,
     Enabled,
     Disabled,
     Checked,
@@ -27,11 +30,23 @@ impl NonTSPseudoClass for PseudoClass {
     fn is_user_action_state(&self) -> bool {
         matches!(
             *self,
-            PseudoClass::Active | PseudoClass::Hover | PseudoClass::Focus
+            PseudoClass::Active
+                | PseudoClass::Hover
+                | PseudoClass::Focus
+                | PseudoClass::FocusVisible
+                | PseudoClass::FocusWithin
         )
     }
 }
 
+impl PseudoClass {
+    /// Whether the pseudo-class depends on user interaction or browser state and can not be
+    /// matched against a static document.
+    pub(crate) fn is_dynamic(&self) -> bool {
+        self.is_user_action_state() || matches!(*self, PseudoClass::Visited | PseudoClass::Target)
+    }
+}
+
 impl ToCss for PseudoClass {
     fn to_css<W>(&self, dest: &mut W) -> fmt::Result
     where
@@ -43,7 +58,10 @@ impl ToCss for PseudoClass {
             PseudoClass::Visited => ":visited",
             PseudoClass::Active => ":active",
             PseudoClass::Focus => ":focus",
+            PseudoClass::FocusVisible => ":focus-visible",
+            PseudoClass::FocusWithin => ":focus-within",
             PseudoClass::Hover => ":hover",
+            PseudoClass::Target => ":target",
             PseudoClass::Enabled => ":enabled",
             PseudoClass::Disabled => ":disabled",
             PseudoClass::Checked => ":checked",
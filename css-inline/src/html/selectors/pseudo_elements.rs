@@ -2,18 +2,39 @@ use super::selector_impl::InlinerSelectors;
 use cssparser::ToCss;
 use std::fmt;
 
-#[derive(PartialEq, Eq, Clone, Debug)]
-pub(crate) enum PseudoElement {}
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub(crate) enum PseudoElement {
+    Before,
+    After,
+}
+
+impl PseudoElement {
+    /// Position in per-pseudo-element storage.
+    #[inline]
+    pub(crate) fn index(self) -> usize {
+        match self {
+            PseudoElement::Before => 0,
+            PseudoElement::After => 1,
+        }
+    }
+}
 
 impl ToCss for PseudoElement {
-    fn to_css<W>(&self, _dest: &mut W) -> fmt::Result
+    fn to_css<W>(&self, dest: &mut W) -> fmt::Result
     where
         W: fmt::Write,
     {
-        match *self {}
+        dest.write_str(match *self {
+            PseudoElement::Before => "::before",
+            PseudoElement::After => "::after",
+        })
     }
 }
 
 impl selectors::parser::PseudoElement for PseudoElement {
     type Impl = InlinerSelectors;
+
+    fn is_before_or_after(&self) -> bool {
+        true
+    }
 }
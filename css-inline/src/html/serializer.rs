@@ -1,117 +1,27 @@
 use super::{
-    attributes::Attributes,
+    attributes::{is_stylesheet_rel, Attributes},
     document::Document,
+    generated_content,
     node::{ElementData, NodeData, NodeId},
-    DocumentStyleMap, InliningMode,
+    presentational::AttributeMapping,
+    propagation::{self, PropertyPropagation},
+    selectors::{PseudoElement, Specificity},
+    variables::{self, CustomProperties},
+    DocumentStyleMap, InliningMode, PseudoElementStyleMap,
 };
-use crate::{html::ElementStyleMap, parser, InlineError};
-use html5ever::{local_name, ns, tendril::StrTendril, LocalName, QualName};
-use memchr::{memchr3_iter, memchr_iter};
+use crate::{parser, properties, urls, InlineError};
+use html5ever::{local_name, ns, LocalName, QualName};
+use memchr::{memchr, memchr2_iter, memchr3_iter, memchr_iter};
 use smallvec::{smallvec, SmallVec};
-use std::io::Write;
+use std::{borrow::Cow, io::Write, rc::Rc};
+use url::Url;
 
-/// Check if an element supports width/height HTML attributes.
-#[inline]
-fn supports_dimension_attrs(name: &LocalName) -> bool {
-    matches!(
-        *name,
-        local_name!("table") | local_name!("td") | local_name!("th") | local_name!("img")
-    )
-}
-
-/// Check if an element is a table element (supports percentage values).
-#[inline]
-fn is_table_element(name: &LocalName) -> bool {
-    matches!(
-        *name,
-        local_name!("table") | local_name!("td") | local_name!("th")
-    )
-}
-
-/// Extracted dimension value for HTML attribute.
-#[derive(Clone, Copy)]
-enum DimensionValue<'a> {
-    /// Numeric value without suffix (for px/unitless)
-    Numeric(&'a str),
-    /// Numeric value that needs % suffix when written
-    Percent(&'a str),
-    /// The "auto" keyword
-    Auto,
-}
-
-impl DimensionValue<'_> {
-    #[inline]
-    fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), InlineError> {
-        match self {
-            DimensionValue::Numeric(n) => writer.write_all(n.as_bytes())?,
-            DimensionValue::Percent(n) => {
-                writer.write_all(n.as_bytes())?;
-                writer.write_all(b"%")?;
-            }
-            DimensionValue::Auto => writer.write_all(b"auto")?,
-        }
-        Ok(())
-    }
-}
-
-/// Extract dimension value for HTML attribute from CSS value.
-#[inline]
-#[allow(clippy::arithmetic_side_effects)]
-fn extract_dimension_value(value: &str, allow_percent: bool) -> Option<DimensionValue<'_>> {
-    let value = value.trim();
-
-    if value.eq_ignore_ascii_case("auto") {
-        return Some(DimensionValue::Auto);
-    }
-
-    // Find where the numeric part ends
-    let bytes = value.as_bytes();
-    let mut end = 0;
-    let mut has_dot = false;
-
-    // Handle optional leading sign
-    if bytes.first() == Some(&b'-') || bytes.first() == Some(&b'+') {
-        end = 1;
-    }
-
-    // Parse digits and optional decimal point
-    while end < bytes.len() {
-        match bytes[end] {
-            b'0'..=b'9' => end += 1,
-            b'.' if !has_dot => {
-                has_dot = true;
-                end += 1;
-            }
-            _ => break,
-        }
-    }
-
-    // Must have at least one digit
-    if end == 0 || (end == 1 && (bytes[0] == b'-' || bytes[0] == b'+')) {
-        return None;
-    }
-
-    let numeric_part = &value[..end];
-    // Trim whitespace between number and unit (e.g., "100 px") for lenient parsing
-    let unit_part = value[end..].trim();
-    // Strip `!important` suffix if present
-    let unit_part = unit_part
-        .strip_suffix("!important")
-        .map_or(unit_part, str::trim);
-
-    match unit_part {
-        // Pixel values - strip the 'px' suffix
-        "" | "px" => Some(DimensionValue::Numeric(numeric_part)),
-        // Percentage - only allowed for table elements
-        "%" if allow_percent => Some(DimensionValue::Percent(numeric_part)),
-        // All other units are not supported
-        _ => None,
-    }
-}
+/// A declaration from stylesheets: (property name, specificity, value)
+type StyleDeclaration<'i> = (&'i str, Specificity, &'i str);
 
 /// Find a style property value from stylesheet rules (not pre-existing inline styles).
 #[inline]
-fn find_style_value<'a>(styles: &'a ElementStyleMap<'_>, property: &str) -> Option<&'a str> {
+fn find_style_value<'a>(styles: &[StyleDeclaration<'a>], property: &str) -> Option<&'a str> {
     styles
         .iter()
         .rev()
@@ -134,12 +44,19 @@ fn find_inline_style_value<'a>(style: &'a str, property: &str) -> Option<&'a str
     found
 }
 
+/// Whether an element's inline `style` attribute sets the property, directly or via a shorthand.
+fn inline_style_covers(style: &str, property: &str) -> bool {
+    let mut input = cssparser::ParserInput::new(style);
+    let mut css_parser = cssparser::Parser::new(&mut input);
+    let mut declaration_parser = parser::CSSDeclarationListParser;
+    cssparser::RuleBodyParser::new(&mut css_parser, &mut declaration_parser)
+        .flatten()
+        .any(|(name, _)| properties::covers(&name.to_ascii_lowercase(), property))
+}
+
 /// Pick the cascade-effective value between an inline `style` declaration and a stylesheet rule.
 /// Precedence (high to low): inline `!important`, stylesheet `!important`, inline, stylesheet.
-fn effective_dimension<'a>(
-    inline: Option<&'a str>,
-    stylesheet: Option<&'a str>,
-) -> Option<&'a str> {
+fn effective_value<'a>(inline: Option<&'a str>, stylesheet: Option<&'a str>) -> Option<&'a str> {
     let important = |v: &str| v.trim_end().ends_with("!important");
     if inline.is_some_and(important) {
         inline
@@ -150,18 +67,71 @@ fn effective_dimension<'a>(
     }
 }
 
+/// Resolve a relative URL in attributes that refer to other resources.
+fn absolutize_attribute(name: &LocalName, value: &str, base: &Url) -> Option<String> {
+    match *name {
+        local_name!("href")
+        | local_name!("src")
+        | local_name!("background")
+        | local_name!("poster") => urls::absolutize(value, base),
+        local_name!("srcset") => urls::absolutize_srcset(value, base),
+        _ => None,
+    }
+}
+
+/// Attributes whose presence alone represents `true` in HTML.
+fn is_boolean_attribute(name: &LocalName) -> bool {
+    matches!(
+        &**name,
+        "allowfullscreen"
+            | "async"
+            | "autofocus"
+            | "autoplay"
+            | "checked"
+            | "compact"
+            | "controls"
+            | "declare"
+            | "default"
+            | "defer"
+            | "disabled"
+            | "formnovalidate"
+            | "hidden"
+            | "inert"
+            | "ismap"
+            | "itemscope"
+            | "loop"
+            | "multiple"
+            | "muted"
+            | "nohref"
+            | "noresize"
+            | "noshade"
+            | "novalidate"
+            | "nowrap"
+            | "open"
+            | "playsinline"
+            | "readonly"
+            | "required"
+            | "reversed"
+            | "selected"
+    )
+}
+
 #[allow(clippy::too_many_arguments, clippy::fn_params_excessive_bools)]
-pub(crate) fn serialize_to<W: Write>(
+pub(crate) fn serialize_to<'i, W: Write>(
     document: &Document,
     writer: &mut W,
-    styles: DocumentStyleMap<'_>,
+    styles: DocumentStyleMap<'i>,
+    pseudo_styles: PseudoElementStyleMap<'i>,
     keep_style_tags: bool,
     keep_link_tags: bool,
     minify_css: bool,
     at_rules: Option<&String>,
     mode: InliningMode,
-    apply_width_attributes: bool,
-    apply_height_attributes: bool,
+    attribute_mappings: &[AttributeMapping],
+    resolve_css_variables: bool,
+    url_base: Option<&Url>,
+    xhtml: bool,
+    propagation: Option<&PropertyPropagation>,
 ) -> Result<(), InlineError> {
     let sink = Sink::new(
         document,
@@ -171,10 +141,17 @@ pub(crate) fn serialize_to<W: Write>(
         minify_css,
         at_rules,
         mode,
-        apply_width_attributes,
-        apply_height_attributes,
+        attribute_mappings,
+    );
+    let mut ser = HtmlSerializer::new(
+        writer,
+        styles,
+        pseudo_styles,
+        resolve_css_variables,
+        url_base,
+        xhtml,
+        propagation,
     );
-    let mut ser = HtmlSerializer::new(writer, styles);
     sink.serialize(&mut ser)
 }
 
@@ -188,8 +165,7 @@ struct Sink<'a> {
     minify_css: bool,
     at_rules: Option<&'a String>,
     inlining_mode: InliningMode,
-    apply_width_attributes: bool,
-    apply_height_attributes: bool,
+    attribute_mappings: &'a [AttributeMapping],
 }
 
 impl<'a> Sink<'a> {
@@ -202,8 +178,7 @@ impl<'a> Sink<'a> {
         minify_css: bool,
         at_rules: Option<&'a String>,
         inlining_mode: InliningMode,
-        apply_width_attributes: bool,
-        apply_height_attributes: bool,
+        attribute_mappings: &'a [AttributeMapping],
     ) -> Sink<'a> {
         Sink {
             document,
@@ -213,8 +188,7 @@ impl<'a> Sink<'a> {
             minify_css,
             at_rules,
             inlining_mode,
-            apply_width_attributes,
-            apply_height_attributes,
+            attribute_mappings,
         }
     }
     #[inline]
@@ -227,8 +201,7 @@ impl<'a> Sink<'a> {
             self.minify_css,
             self.at_rules,
             self.inlining_mode,
-            self.apply_width_attributes,
-            self.apply_height_attributes,
+            self.attribute_mappings,
         )
     }
     #[inline]
@@ -237,10 +210,15 @@ impl<'a> Sink<'a> {
     }
     #[inline]
     fn should_skip_element(&self, element: &ElementData) -> bool {
-        if element.name.local == local_name!("style") {
+        if self.document.is_inapplicable_stylesheet(self.node) {
+            false
+        } else if element.name.local == local_name!("style") {
             !self.keep_style_tags && element.attributes.get_css_inline() != Some("keep")
         } else if element.name.local == local_name!("link")
-            && element.attributes.get(local_name!("rel")) == Some("stylesheet")
+            && element
+                .attributes
+                .get(local_name!("rel"))
+                .is_some_and(is_stylesheet_rel)
         {
             !self.keep_link_tags
         } else if element.name.local == local_name!("html") {
@@ -252,7 +230,7 @@ impl<'a> Sink<'a> {
 
     fn serialize_children<W: Write>(
         &self,
-        serializer: &mut HtmlSerializer<'_, W>,
+        serializer: &mut HtmlSerializer<'_, '_, W>,
     ) -> Result<(), InlineError> {
         for child in self.document.children(self.node) {
             self.for_node(child).serialize(serializer)?;
@@ -262,7 +240,7 @@ impl<'a> Sink<'a> {
 
     fn serialize<W: Write>(
         &self,
-        serializer: &mut HtmlSerializer<'_, W>,
+        serializer: &mut HtmlSerializer<'_, '_, W>,
     ) -> Result<(), InlineError> {
         match self.data() {
             NodeData::Element {
@@ -279,13 +257,19 @@ impl<'a> Sink<'a> {
                     Some(self.node)
                 };
 
+                // `cellpadding` applies to the cells of a table
+                let cell_padding = (element.name.local == local_name!("table")
+                    && self.attribute_mappings.contains(&AttributeMapping::Padding))
+                .then(|| serializer.cell_padding(self.document, self.node))
+                .flatten();
+
                 serializer.start_elem(
                     &element.name,
                     &element.attributes,
                     style_node_id,
                     self.minify_css,
-                    self.apply_width_attributes,
-                    self.apply_height_attributes,
+                    self.attribute_mappings,
+                    cell_padding.as_deref(),
                 )?;
 
                 if element.name.local == local_name!("head") {
@@ -296,13 +280,29 @@ impl<'a> Sink<'a> {
                     }
                 }
 
+                serializer.write_pseudo_element(
+                    style_node_id,
+                    PseudoElement::Before,
+                    &element.attributes,
+                    self.minify_css,
+                )?;
                 self.serialize_children(serializer)?;
+                serializer.write_pseudo_element(
+                    style_node_id,
+                    PseudoElement::After,
+                    &element.attributes,
+                    self.minify_css,
+                )?;
 
                 serializer.end_elem(&element.name)?;
                 Ok(())
             }
             NodeData::Document => self.serialize_children(serializer),
-            NodeData::Doctype { name } => serializer.write_doctype(name),
+            NodeData::Doctype {
+                name,
+                public_id,
+                system_id,
+            } => serializer.write_doctype(name, public_id, system_id),
             NodeData::Text { text } => serializer.write_text(text),
             NodeData::Comment { text } => serializer.write_comment(text),
             NodeData::ProcessingInstruction { target, data } => {
@@ -315,29 +315,57 @@ impl<'a> Sink<'a> {
 struct ElemInfo {
     html_name: Option<LocalName>,
     ignore_children: bool,
+    /// Computed custom properties, if they are resolved.
+    custom_properties: Option<Rc<CustomProperties>>,
+    /// Computed values of propagated properties, in the order of `PropertyPropagation`.
+    inherited: Option<Rc<[Option<Box<str>>]>>,
 }
 
 /// Inspired by HTML serializer from `html5ever`
 /// Source: <https://github.com/servo/html5ever/blob/98d3c0cd01471af997cd60849a38da45a9414dfd/html5ever/src/serialize/mod.rs#L77>
-struct HtmlSerializer<'a, Wr: Write> {
+struct HtmlSerializer<'a, 'p, Wr: Write> {
     writer: Wr,
     styles: DocumentStyleMap<'a>,
+    /// Styles of `::before` and `::after`, materialized as `span` elements.
+    pseudo_styles: PseudoElementStyleMap<'a>,
     stack: Vec<ElemInfo>,
     style_buffer: SmallVec<[Vec<u8>; 8]>,
+    resolve_css_variables: bool,
+    /// Relative URLs in attributes are resolved against it.
+    url_base: Option<Url>,
+    /// Whether to emit XHTML syntax.
+    xhtml: bool,
+    /// Inherited properties to write explicitly onto descendants.
+    propagation: Option<&'p PropertyPropagation>,
 }
 
-impl<'a, W: Write> HtmlSerializer<'a, W> {
-    fn new(writer: W, styles: DocumentStyleMap<'a>) -> Self {
+impl<'a, 'p, W: Write> HtmlSerializer<'a, 'p, W> {
+    fn new(
+        writer: W,
+        styles: DocumentStyleMap<'a>,
+        pseudo_styles: PseudoElementStyleMap<'a>,
+        resolve_css_variables: bool,
+        url_base: Option<&Url>,
+        xhtml: bool,
+        propagation: Option<&'p PropertyPropagation>,
+    ) -> Self {
         let mut stack = Vec::with_capacity(8);
         stack.push(ElemInfo {
             html_name: None,
             ignore_children: false,
+            custom_properties: None,
+            inherited: None,
         });
         HtmlSerializer {
             writer,
             styles,
+            pseudo_styles,
             stack,
             style_buffer: smallvec![],
+            resolve_css_variables,
+            url_base: url_base.cloned(),
+            xhtml,
+            propagation,
         }
     }
 
@@ -356,7 +384,7 @@ impl<'a, W: Write> HtmlSerializer<'a, W> {
             // This is slightly faster than matching on `char`
             // Notably, this approach does not work in `write_attributes` below
             match (part.as_bytes()[0] & 0b0000_1110) >> 1 {
-                1 => self.writer.write_all(b"&nbsp;")?,
+                1 => self.write_nbsp()?,
                 3 => self.writer.write_all(b"&amp;")?,
                 6 => self.writer.write_all(b"&lt;")?,
                 7 => self.writer.write_all(b"&gt;")?,
@@ -372,8 +400,21 @@ impl<'a, W: Write> HtmlSerializer<'a, W> {
         Ok(())
     }
 
+    /// `&nbsp;` is not defined in XML, so XHTML uses a character reference instead.
+    fn write_nbsp(&mut self) -> Result<(), InlineError> {
+        if self.xhtml {
+            self.writer.write_all(b"&#160;")?;
+        } else {
+            self.writer.write_all(b"&nbsp;")?;
+        }
+        Ok(())
+    }
+
     #[allow(clippy::arithmetic_side_effects)]
     fn write_attributes(&mut self, text: &str) -> Result<(), InlineError> {
+        if self.xhtml {
+            return self.write_xml_attribute(text);
+        }
         let bytes = text.as_bytes();
         let mut last_end = 0;
 
@@ -392,7 +433,7 @@ impl<'a, W: Write> HtmlSerializer<'a, W> {
                 }
                 0xC2 if bytes.get(idx + 1) == Some(&0xA0) => {
                     self.writer.write_all(&bytes[last_end..idx])?;
-                    self.writer.write_all(b"&nbsp;")?;
+                    self.write_nbsp()?;
                     last_end = idx + 2; // Skip both bytes of \u{00A0}
                 }
                 _ => {} // False positive for 0xC2 not followed by 0xA0
@@ -402,6 +443,51 @@ impl<'a, W: Write> HtmlSerializer<'a, W> {
         Ok(())
     }
 
+    /// XML additionally requires escaping `<` in attribute values.
+    fn write_xml_attribute(&mut self, text: &str) -> Result<(), InlineError> {
+        let mut last_end = 0;
+        for (start, part) in text.match_indices(['&', '"', '<', '\u{00A0}']) {
+            self.writer.write_all(
+                text.get(last_end..start)
+                    .expect("Invalid substring")
+                    .as_bytes(),
+            )?;
+            match part {
+                "&" => self.writer.write_all(b"&amp;")?,
+                "\"" => self.writer.write_all(b"&quot;")?,
+                "<" => self.writer.write_all(b"&lt;")?,
+                _ => self.write_nbsp()?,
+            }
+            last_end = start.checked_add(part.len()).expect("Size overflow");
+        }
+        self.writer.write_all(
+            text.get(last_end..text.len())
+                .expect("Invalid substring")
+                .as_bytes(),
+        )?;
+        Ok(())
+    }
+
+    /// Write the content of `script` and `style` elements. In XHTML, it is wrapped in a CDATA
+    /// section if it contains markup characters. The section is commented out for HTML parsers.
+    fn write_raw_text(&mut self, text: &str, is_script: bool) -> Result<(), InlineError> {
+        if !self.xhtml || !(text.contains(['<', '&']) || text.contains("]]>")) {
+            self.writer.write_all(text.as_bytes())?;
+            return Ok(());
+        }
+        let (start, end): (&[u8], &[u8]) = if is_script {
+            (b"//<![CDATA[\n", b"\n//]]>")
+        } else {
+            (b"/*<![CDATA[*/", b"/*]]>*/")
+        };
+        self.writer.write_all(start)?;
+        // `]]>` ends the section, so it is split between two sections
+        self.writer
+            .write_all(text.replace("]]>", "]]]]><![CDATA[>").as_bytes())?;
+        self.writer.write_all(end)?;
+        Ok(())
+    }
+
     #[allow(clippy::too_many_lines)]
     fn start_elem(
         &mut self,
@@ -409,8 +495,8 @@ impl<'a, W: Write> HtmlSerializer<'a, W> {
         attrs: &Attributes,
         style_node_id: Option<NodeId>,
         minify_css: bool,
-        apply_width_attributes: bool,
-        apply_height_attributes: bool,
+        attribute_mappings: &[AttributeMapping],
+        cell_padding: Option<&str>,
     ) -> Result<(), InlineError> {
         let html_name = match name.ns {
             ns!(html) => Some(name.local.clone()),
@@ -421,15 +507,18 @@ impl<'a, W: Write> HtmlSerializer<'a, W> {
             self.stack.push(ElemInfo {
                 html_name,
                 ignore_children: true,
+                custom_properties: None,
+                inherited: None,
             });
             return Ok(());
         }
 
-        let mut styles = if let Some(node_id) = style_node_id {
+        let styles = if let Some(node_id) = style_node_id {
             self.styles.get_mut(node_id.get()).and_then(|slot| {
                 slot.take().map(|mut styles| {
-                    // Sort by specificity for consistent output order
-                    styles.sort_unstable_by_key(|a| a.1);
+                    // Sort by specificity for consistent output order. The sort is stable, so
+                    // a longhand stays after a shorthand with the same specificity
+                    styles.sort_by_key(|a| a.1);
                     styles
                 })
             })
@@ -437,48 +526,165 @@ impl<'a, W: Write> HtmlSerializer<'a, W> {
             None
         };
 
+        let mut style_attr = attrs.get(local_name!("style")).map(Cow::Borrowed);
+        let mut custom_properties = None;
+        let mut values = Vec::new();
+        if self.resolve_css_variables {
+            custom_properties = variables::compute(
+                self.parent().custom_properties.as_ref(),
+                styles.as_ref(),
+                style_attr.as_deref(),
+            );
+            if let Some(styles) = &styles {
+                values = variables::substitute_styles(styles, custom_properties.as_deref());
+            }
+            style_attr = style_attr.map(|style| match style {
+                Cow::Borrowed(style) => {
+                    variables::substitute_style_attribute(style, custom_properties.as_deref())
+                }
+                Cow::Owned(style) => Cow::Owned(style),
+            });
+        }
+        let parent_inherited = self.parent().inherited.clone();
+        let mut styles: Option<Cow<'_, [StyleDeclaration<'_>]>> = if self.resolve_css_variables {
+            styles
+                .as_ref()
+                .map(|styles| {
+                    styles
+                        .iter()
+                        .zip(&values)
+                        .filter_map(|((name, specificity, _), value)| {
+                            value.as_deref().map(|value| (*name, *specificity, value))
+                        })
+                        .collect::<Vec<_>>()
+                })
+                // Do not write an empty "style" attribute if all declarations were custom properties
+                .filter(|styles| !styles.is_empty())
+                .map(Cow::Owned)
+        } else {
+            styles.as_deref().map(Cow::Borrowed)
+        };
+
+        let mut inherited = None;
+        if let Some(propagation) = self.propagation {
+            let mut values = Vec::with_capacity(propagation.properties.len());
+            for (idx, property) in propagation.properties.iter().enumerate() {
+                let parent_value = parent_inherited
+                    .as_ref()
+                    .and_then(|values| values.get(idx))
+                    .and_then(Option::as_deref);
+                let declared = styles.as_ref().is_some_and(|styles| {
+                    styles
+                        .iter()
+                        .any(|(name, _, _)| properties::covers(name, property))
+                }) || style_attr
+                    .as_deref()
+                    .is_some_and(|style| inline_style_covers(style, property));
+                if declared {
+                    let value = effective_value(
+                        style_attr
+                            .as_deref()
+                            .and_then(|s| find_inline_style_value(s, property)),
+                        styles.as_ref().and_then(|s| find_style_value(s, property)),
+                    )
+                    .map(|value| {
+                        let value = value.trim();
+                        value
+                            .strip_suffix("!important")
+                            .map_or(value, str::trim_end)
+                    });
+                    // A value set only via a shorthand is not known
+                    values.push(match value {
+                        Some(value) if value.eq_ignore_ascii_case("inherit") => {
+                            parent_value.map(Box::from)
+                        }
+                        Some(value) => propagation::computed_value(property, value, parent_value),
+                        None => None,
+                    });
+                } else if html_name
+                    .as_ref()
+                    .is_some_and(|html_name| propagation::has_default_value(html_name, property))
+                {
+                    // Keep the browser's value, which descendants inherit
+                    values.push(None);
+                } else {
+                    if let (Some(value), Some(html_name)) = (parent_value, &html_name) {
+                        if propagation.applies_to(html_name) {
+                            styles
+                                .get_or_insert_with(|| Cow::Owned(Vec::new()))
+                                .to_mut()
+                                .push((property.as_str(), Specificity::new(0), value));
+                        }
+                    }
+                    values.push(parent_value.map(Box::from));
+                }
+            }
+            if values.iter().any(Option::is_some) {
+                inherited = Some(Rc::from(values));
+            }
+        }
+
         self.writer.write_all(b"<")?;
         self.writer.write_all(name.local.as_bytes())?;
+        if self.xhtml
+            && html_name == Some(local_name!("html"))
+            && !attrs.contains(local_name!("xmlns"))
+        {
+            self.writer
+                .write_all(b" xmlns=\"http://www.w3.org/1999/xhtml\"")?;
+        }
         if let Some(class) = &attrs.class {
             self.writer.write_all(b" class=\"")?;
-            self.writer.write_all(class.value.as_bytes())?;
+            if self.xhtml {
+                self.write_xml_attribute(&class.value)?;
+            } else {
+                self.writer.write_all(class.value.as_bytes())?;
+            }
             self.writer.write_all(b"\"")?;
         }
 
-        // Extract and write width/height HTML attributes before styles is consumed
+        // Extract and write presentational HTML attributes before styles is consumed
         if let Some(ref html_name) = html_name {
-            if supports_dimension_attrs(html_name) {
-                let allow_percent = is_table_element(html_name);
-                // Resolve the cascade-effective value across the inline `style` and stylesheet.
-                let style_attr = attrs.get(local_name!("style"));
-                if apply_width_attributes && !attrs.contains(local_name!("width")) {
-                    if let Some(dim) = effective_dimension(
-                        style_attr.and_then(|s| find_inline_style_value(s, "width")),
-                        styles.as_ref().and_then(|s| find_style_value(s, "width")),
-                    )
-                    .and_then(|v| extract_dimension_value(v, allow_percent))
-                    {
-                        self.writer.write_all(b" width=\"")?;
-                        dim.write_to(&mut self.writer)?;
-                        self.writer.write_all(b"\"")?;
-                    }
+            for mapping in attribute_mappings {
+                let attribute = mapping.attribute();
+                if !mapping.applies_to(html_name) || attrs.contains(attribute.clone()) {
+                    continue;
                 }
-                if apply_height_attributes && !attrs.contains(local_name!("height")) {
-                    if let Some(dim) = effective_dimension(
-                        style_attr.and_then(|s| find_inline_style_value(s, "height")),
-                        styles.as_ref().and_then(|s| find_style_value(s, "height")),
+                let property = mapping.property();
+                let value = if *mapping == AttributeMapping::Padding {
+                    cell_padding
+                } else {
+                    // Resolve the cascade-effective value across the inline `style` and stylesheet.
+                    effective_value(
+                        style_attr
+                            .as_deref()
+                            .and_then(|s| find_inline_style_value(s, property)),
+                        styles.as_ref().and_then(|s| find_style_value(s, property)),
                     )
-                    .and_then(|v| extract_dimension_value(v, allow_percent))
-                    {
-                        self.writer.write_all(b" height=\"")?;
-                        dim.write_to(&mut self.writer)?;
-                        self.writer.write_all(b"\"")?;
-                    }
+                };
+                if let Some(value) = value.and_then(|v| mapping.convert(v, html_name)) {
+                    self.writer.write_all(b" ")?;
+                    self.writer.write_all(attribute.as_bytes())?;
+                    self.writer.write_all(b"=\"")?;
+                    value.write_to(&mut self.writer)?;
+                    self.writer.write_all(b"\"")?;
                 }
             }
         }
 
         for attr in &attrs.attributes {
+            let is_style = attr.name.local == local_name!("style");
+            // All declarations were custom properties
+            if is_style
+                && self.resolve_css_variables
+                && styles.is_none()
+                && style_attr
+                    .as_deref()
+                    .is_some_and(|style| style.trim().is_empty())
+                && !attr.value.trim().is_empty()
+            {
+                continue;
+            }
             self.writer.write_all(b" ")?;
 
             match attr.name.ns {
@@ -497,19 +703,44 @@ impl<'a, W: Write> HtmlSerializer<'a, W> {
 
             self.writer.write_all(attr.name.local.as_bytes())?;
             self.writer.write_all(b"=\"")?;
-            if attr.name.local == local_name!("style") {
+            if is_style {
+                let style_attr = style_attr.as_deref().unwrap_or_default();
                 if let Some(new_styles) = &styles {
-                    merge_styles(
-                        &mut self.writer,
-                        &attr.value,
-                        new_styles,
-                        &mut self.style_buffer,
-                        minify_css,
-                    )?;
+                    if self.xhtml {
+                        merge_styles(
+                            &mut XmlAttributeWriter(&mut self.writer),
+                            style_attr,
+                            new_styles,
+                            &mut self.style_buffer,
+                            minify_css,
+                        )?;
+                    } else {
+                        merge_styles(
+                            &mut self.writer,
+                            style_attr,
+                            new_styles,
+                            &mut self.style_buffer,
+                            minify_css,
+                        )?;
+                    }
                     styles = None;
                 } else {
-                    self.write_attributes(&attr.value)?;
+                    self.write_attributes(style_attr)?;
                 }
+            } else if let Some(url) = self
+                .url_base
+                .as_ref()
+                .filter(|_| html_name.is_some() && attr.name.ns == ns!())
+                .and_then(|base| absolutize_attribute(&attr.name.local, &attr.value, base))
+            {
+                self.write_attributes(&url)?;
+            } else if self.xhtml
+                && attr.value.is_empty()
+                && html_name.is_some()
+                && is_boolean_attribute(&attr.name.local)
+            {
+                // XHTML has no attribute minimization, e.g. `checked` becomes `checked="checked"`
+                self.writer.write_all(attr.name.local.as_bytes())?;
             } else {
                 self.write_attributes(&attr.value)?;
             }
@@ -517,24 +748,12 @@ impl<'a, W: Write> HtmlSerializer<'a, W> {
         }
         if let Some(styles) = styles {
             self.writer.write_all(b" style=\"")?;
-            if minify_css {
-                let mut it = styles.iter().peekable();
-                while let Some((property, _, value)) = it.next() {
-                    write_declaration(&mut self.writer, property, value, minify_css)?;
-                    if !minify_css || it.peek().is_some() {
-                        self.writer.write_all(b";")?;
-                    }
-                }
-            } else {
-                for (property, _, value) in styles {
-                    write_declaration(&mut self.writer, property, value, minify_css)?;
-                    self.writer.write_all(b";")?;
-                }
-            }
+            self.write_style_attribute(
+                styles.iter().map(|(name, _, value)| (*name, *value)),
+                minify_css,
+            )?;
             self.writer.write_all(b"\"")?;
         }
-        self.writer.write_all(b">")?;
-
         let ignore_children = name.ns == ns!(html)
             && matches!(
                 name.local,
@@ -558,9 +777,17 @@ impl<'a, W: Write> HtmlSerializer<'a, W> {
                     | local_name!("wbr")
             );
 
+        if ignore_children && self.xhtml {
+            self.writer.write_all(b" />")?;
+        } else {
+            self.writer.write_all(b">")?;
+        }
+
         self.stack.push(ElemInfo {
             html_name,
             ignore_children,
+            custom_properties,
+            inherited,
         });
 
         Ok(())
@@ -581,8 +808,9 @@ impl<'a, W: Write> HtmlSerializer<'a, W> {
     }
 
     fn write_text(&mut self, text: &str) -> Result<(), InlineError> {
+        let parent = self.parent().html_name.clone();
         let escape = !matches!(
-            self.parent().html_name,
+            parent,
             Some(
                 local_name!("style")
                     | local_name!("script")
@@ -595,17 +823,120 @@ impl<'a, W: Write> HtmlSerializer<'a, W> {
             ),
         );
 
-        if escape {
-            self.write_escaped(text)?;
-        } else {
-            self.writer.write_all(text.as_bytes())?;
+        match parent {
+            Some(local_name!("script")) => self.write_raw_text(text, true)?,
+            Some(local_name!("style")) => self.write_raw_text(text, false)?,
+            // XML has no other raw text elements
+            _ if escape || self.xhtml => self.write_escaped(text)?,
+            _ => self.writer.write_all(text.as_bytes())?,
+        }
+        Ok(())
+    }
+
+    /// Write a pseudo-element of the current element as a `span` with its styles inlined.
+    fn write_pseudo_element(
+        &mut self,
+        node_id: Option<NodeId>,
+        pseudo: PseudoElement,
+        attributes: &Attributes,
+        minify_css: bool,
+    ) -> Result<(), InlineError> {
+        // Void elements have no pseudo-elements
+        if self.parent().ignore_children {
+            return Ok(());
         }
+        let Some(mut styles) = node_id
+            .and_then(|node_id| self.pseudo_styles[pseudo.index()].get_mut(node_id.get()))
+            .and_then(Option::take)
+        else {
+            return Ok(());
+        };
+        let Some(text) = styles
+            .iter()
+            .find(|(name, _, _)| *name == "content")
+            .and_then(|(_, _, value)| generated_content::text(value, attributes))
+        else {
+            return Ok(());
+        };
+        styles.sort_by_key(|a| a.1);
+        self.writer.write_all(b"<span")?;
+        if styles.iter().any(|(name, _, _)| *name != "content") {
+            self.writer.write_all(b" style=\"")?;
+            self.write_style_attribute(
+                styles
+                    .iter()
+                    .filter(|(name, _, _)| *name != "content")
+                    .map(|(property, _, value)| (*property, *value)),
+                minify_css,
+            )?;
+            self.writer.write_all(b"\"")?;
+        }
+        self.writer.write_all(b">")?;
+        self.write_escaped(&text)?;
+        self.writer.write_all(b"</span>")?;
         Ok(())
     }
 
+    /// The `padding` shared by all cells of a table, if they have the same one on every side.
+    fn cell_padding(&self, document: &Document, table: NodeId) -> Option<String> {
+        let mut padding: Option<String> = None;
+        let mut stack: Vec<_> = document.children(table).collect();
+        while let Some(node) = stack.pop() {
+            let Some(element) = document[node].as_element() else {
+                continue;
+            };
+            match element.name.local {
+                local_name!("td") | local_name!("th") => {
+                    let style = element.attributes.get(local_name!("style"));
+                    let styles = self.styles.get(node.get()).and_then(Option::as_ref);
+                    // Longhands change the padding of individual sides
+                    if style.is_some_and(|style| style.to_ascii_lowercase().contains("padding-"))
+                        || styles.is_some_and(|styles| {
+                            styles
+                                .iter()
+                                .any(|(name, _, _)| name.starts_with("padding-"))
+                        })
+                    {
+                        return None;
+                    }
+                    let value = effective_value(
+                        style.and_then(|s| find_inline_style_value(s, "padding")),
+                        styles.and_then(|s| find_style_value(s, "padding")),
+                    )?;
+                    match &padding {
+                        Some(padding) if padding != value => return None,
+                        Some(_) => {}
+                        None => padding = Some(value.to_string()),
+                    }
+                }
+                // Cells of nested tables do not belong to this one
+                local_name!("table") => {}
+                _ => stack.extend(document.children(node)),
+            }
+        }
+        padding
+    }
+
+    /// Write declarations as the value of a `style` attribute.
+    fn write_style_attribute<'d>(
+        &mut self,
+        declarations: impl Iterator<Item = (&'d str, &'d str)>,
+        minify_css: bool,
+    ) -> Result<(), InlineError> {
+        if self.xhtml {
+            write_declarations(
+                &mut XmlAttributeWriter(&mut self.writer),
+                declarations,
+                minify_css,
+            )
+        } else {
+            write_declarations(&mut self.writer, declarations, minify_css)
+        }
+    }
+
     fn write_at_rules_style(&mut self, at_rules: &str) -> Result<(), InlineError> {
         self.writer.write_all(b"<style>")?;
-        self.writer.write_all(at_rules.as_bytes())?;
+        self.write_raw_text(at_rules, false)?;
         self.writer.write_all(b"</style>")?;
         Ok(())
     }
@@ -617,13 +948,41 @@ impl<'a, W: Write> HtmlSerializer<'a, W> {
         Ok(())
     }
 
-    fn write_doctype(&mut self, name: &str) -> Result<(), InlineError> {
+    fn write_doctype(
+        &mut self,
+        name: &str,
+        public_id: &str,
+        system_id: &str,
+    ) -> Result<(), InlineError> {
         self.writer.write_all(b"<!DOCTYPE ")?;
         self.writer.write_all(name.as_bytes())?;
+        if !public_id.is_empty() {
+            self.writer.write_all(b" PUBLIC ")?;
+            self.write_doctype_identifier(public_id)?;
+        } else if !system_id.is_empty() {
+            self.writer.write_all(b" SYSTEM")?;
+        }
+        if !system_id.is_empty() {
+            self.writer.write_all(b" ")?;
+            self.write_doctype_identifier(system_id)?;
+        }
         self.writer.write_all(b">")?;
         Ok(())
     }
 
+    fn write_doctype_identifier(&mut self, identifier: &str) -> Result<(), InlineError> {
+        // Identifiers can not contain the quote they are wrapped in
+        let quote: &[u8] = if identifier.contains('"') {
+            b"'"
+        } else {
+            b"\""
+        };
+        self.writer.write_all(quote)?;
+        self.writer.write_all(identifier.as_bytes())?;
+        self.writer.write_all(quote)?;
+        Ok(())
+    }
+
     fn write_processing_instruction(
         &mut self,
         target: &str,
@@ -657,6 +1016,44 @@ fn write_declaration<Wr: Write>(
     write_declaration_value(writer, value)
 }
 
+fn write_declarations<'d, Wr: Write>(
+    writer: &mut Wr,
+    declarations: impl Iterator<Item = (&'d str, &'d str)>,
+    minify_css: bool,
+) -> Result<(), InlineError> {
+    let mut declarations = declarations.peekable();
+    while let Some((property, value)) = declarations.next() {
+        write_declaration(writer, property, value, minify_css)?;
+        if !minify_css || declarations.peek().is_some() {
+            writer.write_all(b";")?;
+        }
+    }
+    Ok(())
+}
+
+/// Escapes characters that are not allowed in XML attribute values. Double quotes are already
+/// replaced in declaration values.
+struct XmlAttributeWriter<W>(W);
+
+impl<W: Write> Write for XmlAttributeWriter<W> {
+    #[allow(clippy::arithmetic_side_effects)]
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut last_end = 0;
+        for idx in memchr2_iter(b'&', b'<', buf) {
+            self.0.write_all(&buf[last_end..idx])?;
+            self.0
+                .write_all(if buf[idx] == b'&' { b"&amp;" } else { b"&lt;" })?;
+            last_end = idx + 1;
+        }
+        self.0.write_all(&buf[last_end..])?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
 #[inline]
 #[allow(clippy::arithmetic_side_effects)]
 fn write_declaration_value<Wr: Write>(writer: &mut Wr, value: &str) -> Result<(), InlineError> {
@@ -698,8 +1095,8 @@ macro_rules! push_or_update {
 /// The merge process maintains the order of specificity and respects the `!important` rule in CSS.
 fn merge_styles<Wr: Write>(
     writer: &mut Wr,
-    current_style: &StrTendril,
-    new_styles: &ElementStyleMap<'_>,
+    current_style: &str,
+    new_styles: &[StyleDeclaration<'_>],
     declarations_buffer: &mut SmallVec<[Vec<u8>; 8]>,
     minify_css: bool,
 ) -> Result<(), InlineError> {
@@ -741,8 +1138,23 @@ fn merge_styles<Wr: Write>(
         STYLE_SEPARATOR
     };
     for (property, _, value) in new_styles {
+        let important_value = value.trim_end().strip_suffix("!important");
+        // A shorthand in the "style" attribute, e.g. `margin`, resets the property, e.g.
+        // `margin-top`. Only a stylesheet `!important` value survives a regular inline shorthand
+        let is_reset = declarations_buffer[..current_declarations_count]
+            .iter()
+            .any(|style| {
+                declaration_name(style).is_some_and(|name| {
+                    name != *property
+                        && properties::covers(name, property)
+                        && (important_value.is_none() || style.ends_with(b"!important"))
+                })
+            });
+        if is_reset {
+            continue;
+        }
         match (
-            value.trim_end().strip_suffix("!important"),
+            important_value,
             declarations_buffer
                 .iter_mut()
                 .take(parsed_declarations_count)
@@ -813,11 +1225,18 @@ fn merge_styles<Wr: Write>(
     Ok(())
 }
 
+/// Property name of a serialized declaration.
+fn declaration_name(declaration: &[u8]) -> Option<&str> {
+    let end = memchr(b':', declaration)?;
+    std::str::from_utf8(&declaration[..end]).ok()
+}
+
 #[cfg(test)]
 mod tests {
     use crate::html::InliningMode;
 
     use super::Document;
+    use test_case::test_case;
 
     #[test]
     fn test_serialize() {
@@ -830,13 +1249,17 @@ mod tests {
         doc.serialize(
             &mut buffer,
             vec![None; doc.nodes.len()],
+            [Vec::new(), Vec::new()],
             true,
             false,
             false,
             None,
             InliningMode::Document,
+            &[],
             false,
+            None,
             false,
+            None,
         )
         .expect("Should not fail");
         assert_eq!(buffer, b"<html><head><style>h1 { color:blue; }</style><style>h1 { color:red }</style></head><body></body></html>");
@@ -853,13 +1276,17 @@ mod tests {
         doc.serialize(
             &mut buffer,
             vec![None; doc.nodes.len()],
+            [Vec::new(), Vec::new()],
             false,
             false,
             false,
             None,
             InliningMode::Document,
+            &[],
             false,
+            None,
             false,
+            None,
         )
         .expect("Should not fail");
         assert_eq!(buffer, b"<html><head></head><body></body></html>");
@@ -876,18 +1303,56 @@ mod tests {
         doc.serialize(
             &mut buffer,
             vec![None; doc.nodes.len()],
+            [Vec::new(), Vec::new()],
             false,
             false,
             false,
             None,
             InliningMode::Document,
+            &[],
             false,
+            None,
             false,
+            None,
         )
         .expect("Should not fail");
         assert_eq!(buffer, b"<!DOCTYPE html><html><head><title>&amp; &lt; &gt; &nbsp;</title></head><body></body></html>");
     }
 
+    #[test_case("<!DOCTYPE html>"; "html5")]
+    #[test_case(
+        r#"<!DOCTYPE html PUBLIC "-//W3C//DTD XHTML 1.0 Transitional//EN" "http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd">"#;
+        "xhtml transitional"
+    )]
+    #[test_case(
+        r#"<!DOCTYPE html PUBLIC "-//W3C//DTD HTML 4.01//EN">"#;
+        "public only"
+    )]
+    #[test_case(r#"<!DOCTYPE html SYSTEM "about:legacy-compat">"#; "system only")]
+    #[test_case(r#"<!DOCTYPE html SYSTEM 'a"b'>"#; "quotes")]
+    fn test_doctype(doctype: &str) {
+        let html = format!("{doctype}<html><head></head><body></body></html>");
+        let doc = Document::parse_with_options(html.as_bytes(), 0, InliningMode::Document);
+        let mut buffer = Vec::new();
+        doc.serialize(
+            &mut buffer,
+            vec![None; doc.nodes.len()],
+            [Vec::new(), Vec::new()],
+            false,
+            false,
+            false,
+            None,
+            InliningMode::Document,
+            &[],
+            false,
+            None,
+            false,
+            None,
+        )
+        .expect("Should not fail");
+        assert_eq!(String::from_utf8(buffer).expect("Invalid UTF-8"), html);
+    }
+
     #[test]
     fn test_untouched_style() {
         let doc = Document::parse_with_options(
@@ -899,13 +1364,17 @@ mod tests {
         doc.serialize(
             &mut buffer,
             vec![None; doc.nodes.len()],
+            [Vec::new(), Vec::new()],
             false,
             false,
             false,
             None,
             InliningMode::Document,
+            &[],
             false,
+            None,
             false,
+            None,
         )
         .expect("Should not fail");
         assert_eq!(
@@ -925,13 +1394,17 @@ mod tests {
         doc.serialize(
             &mut buffer,
             vec![None; doc.nodes.len()],
+            [Vec::new(), Vec::new()],
             false,
             false,
             false,
             None,
             InliningMode::Document,
+            &[],
             false,
+            None,
             false,
+            None,
         )
         .expect("Should not fail");
         assert_eq!(buffer, b"<!DOCTYPE html><html><head></head><body data-foo=\"&amp; &nbsp; &quot;\"></body></html>");
@@ -948,6 +1421,7 @@ mod tests {
         doc.serialize(
             &mut buffer,
             vec![None; doc.nodes.len()],
+            [Vec::new(), Vec::new()],
             false,
             false,
             false,
@@ -955,8 +1429,11 @@ mod tests {
                 "@media (max-width: 600px) { h1 { font-size: 18px; } }",
             )),
             InliningMode::Document,
+            &[],
             false,
+            None,
             false,
+            None,
         )
         .expect("Should not fail");
         assert_eq!(buffer, b"<html><head><style>@media (max-width: 600px) { h1 { font-size: 18px; } }</style></head><body></body></html>");
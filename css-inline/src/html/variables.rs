@@ -0,0 +1,308 @@
+//! Resolution of CSS custom properties (`--name: value`) and their `var()` references.
+use super::ElementStyleMap;
+use crate::parser;
+use cssparser::{Parser, ParserInput, Token};
+use rustc_hash::FxHashMap;
+use std::{borrow::Cow, rc::Rc};
+
+/// Computed values of custom properties for an element, including inherited ones.
+pub(super) type CustomProperties = FxHashMap<Box<str>, Box<str>>;
+
+#[inline]
+fn is_custom_property(name: &str) -> bool {
+    name.starts_with("--")
+}
+
+#[inline]
+fn has_var_function(value: &str) -> bool {
+    value
+        .as_bytes()
+        .windows(4)
+        .any(|window| window.eq_ignore_ascii_case(b"var("))
+}
+
+/// Custom property value without surrounding whitespace and the `!important` flag.
+fn strip_important(value: &str) -> (&str, bool) {
+    let value = value.trim();
+    match value.strip_suffix("!important") {
+        Some(value) => (value.trim_end(), true),
+        None => (value, false),
+    }
+}
+
+/// Compute custom properties for an element.
+///
+/// Declarations from the "style" attribute take precedence over stylesheet ones, unless only
+/// the stylesheet one is `!important`. Elements without own custom properties share the
+/// computed values of their parent.
+pub(super) fn compute(
+    parent: Option<&Rc<CustomProperties>>,
+    styles: Option<&ElementStyleMap<'_>>,
+    style_attribute: Option<&str>,
+) -> Option<Rc<CustomProperties>> {
+    let mut declared: Vec<(Cow<'_, str>, &str, bool)> = styles
+        .into_iter()
+        .flatten()
+        .filter(|(name, _, _)| is_custom_property(name))
+        .map(|(name, _, value)| {
+            let (value, important) = strip_important(value);
+            (Cow::Borrowed(*name), value, important)
+        })
+        .collect();
+    let mut input = ParserInput::new(style_attribute.unwrap_or_default());
+    let mut css_parser = Parser::new(&mut input);
+    let mut declaration_parser = parser::CSSDeclarationListParser;
+    for (name, value) in
+        cssparser::RuleBodyParser::new(&mut css_parser, &mut declaration_parser).flatten()
+    {
+        if !is_custom_property(&name) {
+            continue;
+        }
+        let (value, important) = strip_important(value);
+        if let Some(existing) = declared.iter_mut().find(|(n, _, _)| *n == *name) {
+            if important || !existing.2 {
+                existing.1 = value;
+                existing.2 = important;
+            }
+        } else {
+            declared.push((Cow::Owned(name.to_string()), value, important));
+        }
+    }
+    if declared.is_empty() {
+        return parent.cloned();
+    }
+    let declared: Vec<(&str, &str)> = declared
+        .iter()
+        .map(|(name, value, _)| (name.as_ref(), *value))
+        .collect();
+    let mut resolved = FxHashMap::default();
+    let mut in_progress = Vec::new();
+    for (name, _) in &declared {
+        resolve_declared(name, &declared, parent, &mut resolved, &mut in_progress);
+    }
+    let mut properties = parent.map(|parent| (**parent).clone()).unwrap_or_default();
+    for (name, value) in resolved {
+        match value {
+            Some(value) => {
+                properties.insert(name.into(), value.into());
+            }
+            // Invalid at computed-value time, e.g. a reference cycle
+            None => {
+                properties.remove(name);
+            }
+        }
+    }
+    Some(Rc::new(properties))
+}
+
+/// Resolve a custom property declared on the current element.
+/// It may refer to other custom properties on the same element.
+fn resolve_declared<'d>(
+    name: &'d str,
+    declared: &[(&'d str, &'d str)],
+    parent: Option<&Rc<CustomProperties>>,
+    resolved: &mut FxHashMap<&'d str, Option<String>>,
+    in_progress: &mut Vec<&'d str>,
+) -> Option<String> {
+    if let Some(value) = resolved.get(name) {
+        return value.clone();
+    }
+    if in_progress.contains(&name) {
+        return None;
+    }
+    let (_, raw) = declared.iter().find(|(n, _)| *n == name)?;
+    in_progress.push(name);
+    let value = substitute(raw, &mut |reference| {
+        if let Some((reference, _)) = declared.iter().find(|(n, _)| *n == reference) {
+            resolve_declared(reference, declared, parent, resolved, in_progress)
+        } else {
+            parent
+                .and_then(|parent| parent.get(reference))
+                .map(ToString::to_string)
+        }
+    })
+    .map(Cow::into_owned);
+    in_progress.pop();
+    resolved.insert(name, value.clone());
+    value
+}
+
+/// Substitute `var()` references in a declaration value.
+///
+/// Returns `None` if a reference can not be resolved and has no fallback.
+pub(super) fn substitute_value<'v>(
+    value: &'v str,
+    properties: Option<&CustomProperties>,
+) -> Option<Cow<'v, str>> {
+    substitute(value, &mut |name| {
+        properties
+            .and_then(|properties| properties.get(name))
+            .map(ToString::to_string)
+    })
+}
+
+fn substitute<'v>(
+    value: &'v str,
+    lookup: &mut dyn FnMut(&str) -> Option<String>,
+) -> Option<Cow<'v, str>> {
+    if !has_var_function(value) {
+        return Some(Cow::Borrowed(value));
+    }
+    let mut input = ParserInput::new(value);
+    let mut parser = Parser::new(&mut input);
+    let mut output = String::with_capacity(value.len());
+    if substitute_into(&mut parser, &mut output, lookup) {
+        Some(Cow::Owned(output))
+    } else {
+        None
+    }
+}
+
+/// Copy tokens to the output, replacing `var()` functions with their values.
+fn substitute_into(
+    parser: &mut Parser<'_, '_>,
+    output: &mut String,
+    lookup: &mut dyn FnMut(&str) -> Option<String>,
+) -> bool {
+    let mut start = parser.position();
+    loop {
+        let before = parser.position();
+        let closing = match parser.next_including_whitespace_and_comments() {
+            Ok(Token::Function(name)) if name.eq_ignore_ascii_case("var") => {
+                output.push_str(parser.slice(start..before));
+                let value = parser.parse_nested_block(|parser| {
+                    Ok::<_, cssparser::ParseError<'_, ()>>(resolve_var(parser, lookup))
+                });
+                match value {
+                    Ok(Some(value)) => output.push_str(&value),
+                    _ => return false,
+                }
+                start = parser.position();
+                continue;
+            }
+            Ok(Token::Function(_) | Token::ParenthesisBlock) => ')',
+            Ok(Token::SquareBracketBlock) => ']',
+            Ok(Token::CurlyBracketBlock) => '}',
+            Ok(_) => continue,
+            Err(_) => break,
+        };
+        output.push_str(parser.slice_from(start));
+        let is_valid = parser
+            .parse_nested_block(|parser| {
+                Ok::<_, cssparser::ParseError<'_, ()>>(substitute_into(parser, output, lookup))
+            })
+            .unwrap_or(false);
+        if !is_valid {
+            return false;
+        }
+        output.push(closing);
+        start = parser.position();
+    }
+    output.push_str(parser.slice_from(start));
+    true
+}
+
+/// Resolve the arguments of a `var()` function: a custom property name and an optional fallback.
+fn resolve_var(
+    parser: &mut Parser<'_, '_>,
+    lookup: &mut dyn FnMut(&str) -> Option<String>,
+) -> Option<String> {
+    let name = parser.expect_ident_cloned().ok()?;
+    if !is_custom_property(&name) {
+        return None;
+    }
+    if let Some(value) = lookup(&name) {
+        // Skip the fallback
+        while parser.next().is_ok() {}
+        return Some(value);
+    }
+    parser.expect_comma().ok()?;
+    let mut fallback = String::new();
+    if substitute_into(parser, &mut fallback, lookup) {
+        Some(fallback.trim().to_string())
+    } else {
+        None
+    }
+}
+
+/// Substitute `var()` references in stylesheet declarations.
+///
+/// Returns the new value for each declaration, `None` for ones that should be dropped: custom
+/// properties themselves and declarations with unresolvable references.
+pub(super) fn substitute_styles<'s>(
+    styles: &ElementStyleMap<'s>,
+    properties: Option<&CustomProperties>,
+) -> Vec<Option<Cow<'s, str>>> {
+    styles
+        .iter()
+        .map(|(name, _, value)| {
+            if is_custom_property(name) {
+                None
+            } else {
+                substitute_value(value, properties)
+            }
+        })
+        .collect()
+}
+
+/// Substitute `var()` references in a "style" attribute and remove custom properties from it.
+pub(super) fn substitute_style_attribute<'a>(
+    style: &'a str,
+    properties: Option<&CustomProperties>,
+) -> Cow<'a, str> {
+    if !style.contains("--") {
+        return Cow::Borrowed(style);
+    }
+    let mut input = ParserInput::new(style);
+    let mut css_parser = Parser::new(&mut input);
+    let mut declaration_parser = parser::CSSDeclarationListParser;
+    let mut output = String::with_capacity(style.len());
+    for (name, value) in
+        cssparser::RuleBodyParser::new(&mut css_parser, &mut declaration_parser).flatten()
+    {
+        if is_custom_property(&name) {
+            continue;
+        }
+        if let Some(value) = substitute_value(value, properties) {
+            if !output.is_empty() {
+                output.push(';');
+            }
+            output.push_str(&name);
+            output.push_str(": ");
+            output.push_str(&value);
+        }
+    }
+    Cow::Owned(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{substitute_value, CustomProperties};
+    use test_case::test_case;
+
+    fn properties() -> CustomProperties {
+        let mut properties = CustomProperties::default();
+        properties.insert("--color".into(), "red".into());
+        properties.insert("--size".into(), "10px".into());
+        properties
+    }
+
+    #[test_case("blue", Some("blue"); "no references")]
+    #[test_case("var(--color)", Some("red"); "reference")]
+    #[test_case("VAR(--color)", Some("red"); "uppercase")]
+    #[test_case("1px solid var(--color)", Some("1px solid red"); "inside value")]
+    #[test_case("var(--unknown, blue)", Some("blue"); "fallback")]
+    #[test_case("var(--unknown, var(--color))", Some("red"); "nested fallback")]
+    #[test_case("var(--color, var(--unknown))", Some("red"); "unused fallback")]
+    #[test_case("calc(var(--size) * 2)", Some("calc(10px * 2)"); "inside function")]
+    #[test_case("var(--unknown)", None; "unresolved")]
+    #[test_case("var(--unknown, var(--missing))", None; "unresolved fallback")]
+    #[test_case("var(color)", None; "not a custom property")]
+    fn test_substitute(value: &str, expected: Option<&str>) {
+        let properties = properties();
+        assert_eq!(
+            substitute_value(value, Some(&properties)).as_deref(),
+            expected
+        );
+    }
+}
@@ -29,19 +29,41 @@
 #![allow(clippy::module_name_repetitions)]
 pub mod error;
 mod html;
+mod media;
 mod parser;
+mod properties;
+mod report;
 mod resolver;
+mod stylesheet;
+mod templates;
+mod urls;
 
+use cssparser::{ParseErrorKind, ToCss};
 pub use error::InlineError;
 #[cfg(feature = "stylesheet-cache")]
 use lru::{DefaultHasher, LruCache};
-use selectors::context::SelectorCaches;
+use selectors::{context::SelectorCaches, parser::SelectorParseErrorKind};
 use smallvec::SmallVec;
 use std::{borrow::Cow, fmt::Formatter, io::Write, ops::Range, sync::Arc};
 
-use html::{Document, InliningMode, NodeData, NodeId, Specificity};
-pub use resolver::{DefaultStylesheetResolver, StylesheetResolver};
+use html::{
+    have_same_tags, split_downlevel_hidden, split_selector_list, Document, DocumentStyleMap,
+    InliningMode, NodeData, NodeId, PseudoElement, PseudoElementStyleMap, Selectors, Specificity,
+};
+pub use html::{AttributeMapping, PropertyPropagation};
+pub use media::{ColorScheme, MediaEnvironment, MediaType};
+use parser::{InvalidCss, InvalidCssKind};
+pub use report::InlineReport;
+#[cfg(feature = "async")]
+pub use resolver::{AsyncStylesheetResolver, StylesheetFuture};
+pub use resolver::{
+    ChainResolver, DefaultStylesheetResolver, MemoryStylesheetResolver, PrefixResolver,
+    ResolverPolicy, StylesheetResolver,
+};
+#[cfg(feature = "http")]
+pub use resolver::{HttpStylesheetResolver, HttpStylesheetResolverBuilder};
 use rustc_hash::FxHashMap;
+pub use stylesheet::CompiledStylesheet;
 pub use url::{ParseError, Url};
 
 /// An LRU Cache for external stylesheets.
@@ -65,6 +87,7 @@ pub struct InlineOptions<'a> {
     /// Remove trailing semicolons and spaces between properties and values.
     pub minify_css: bool,
     /// Used for loading external stylesheets via relative URLs.
+    /// If it is not set, the `href` of the document's `base` element is used.
     pub base_url: Option<Url>,
     /// Whether remote stylesheets should be loaded or not.
     pub load_remote_stylesheets: bool,
@@ -81,6 +104,14 @@ pub struct InlineOptions<'a> {
     pub preallocate_node_capacity: usize,
     /// A way to resolve stylesheets from various sources.
     pub resolver: Arc<dyn StylesheetResolver>,
+    /// Restrictions on locations of linked and imported stylesheets, e.g. allowed hosts.
+    ///
+    /// Locations are checked before they are passed to the resolver.
+    pub resolver_policy: Option<ResolverPolicy>,
+    /// A way to resolve stylesheets without blocking, used by
+    /// [`CSSInliner::inline_async`] and [`CSSInliner::inline_fragment_async`].
+    #[cfg(feature = "async")]
+    pub async_resolver: Arc<dyn AsyncStylesheetResolver>,
     /// Remove selectors that were successfully inlined from inline `<style>` blocks.
     pub remove_inlined_selectors: bool,
     /// Apply `width` HTML attributes from CSS `width` properties on supported elements.
@@ -93,6 +124,97 @@ pub struct InlineOptions<'a> {
     /// This is useful for email compatibility with clients like Outlook that ignore CSS height.
     /// Supported elements: `table`, `td`, `th`, `img`.
     pub apply_height_attributes: bool,
+    /// Generate presentational HTML attributes from CSS properties on supported elements,
+    /// e.g. `bgcolor` from `background-color` on table cells.
+    ///
+    /// This is useful for email compatibility with clients like Outlook that ignore CSS.
+    /// `apply_width_attributes` and `apply_height_attributes` are equivalent to including
+    /// [`AttributeMapping::Width`] and [`AttributeMapping::Height`].
+    pub attribute_mappings: Vec<AttributeMapping>,
+    /// Fail on CSS that would otherwise be silently skipped.
+    ///
+    /// Unparsable or unsupported selectors, malformed declarations and invalid rules produce
+    /// [`InlineError::ParseError`] with the offending text and its position in the stylesheet.
+    pub strict: bool,
+    /// A stylesheet parsed ahead of time and applied to every document.
+    ///
+    /// Its rules are applied after all other CSS.
+    pub compiled_stylesheet: Option<Arc<CompiledStylesheet>>,
+    /// Replace `var()` references with values of CSS custom properties.
+    ///
+    /// Custom properties are computed along the element tree, respecting inheritance and the
+    /// cascade. Their declarations (`--name: value`) are removed from the output.
+    pub resolve_css_variables: bool,
+    /// Evaluate `@media` rules against this environment.
+    ///
+    /// Rules inside matching `@media` blocks are inlined like any other rules. Non-matching
+    /// blocks are kept if `keep_at_rules` is enabled and dropped otherwise.
+    /// It does not apply to [`CompiledStylesheet`], as it is parsed before inlining.
+    ///
+    /// The `media` attributes of "style" and "link" tags are always evaluated, against a
+    /// default screen environment if it is not set.
+    pub media_environment: Option<MediaEnvironment>,
+    /// Load stylesheets referenced by `@import` rules in "style" tags and linked stylesheets.
+    ///
+    /// Imports are resolved relative to the importing stylesheet, or `base_url` for "style"
+    /// tags, and their rules are applied before the rules of the importing stylesheet.
+    /// Circular imports and imports nested deeper than 16 levels are skipped.
+    pub follow_imports: bool,
+    /// Rewrite relative URLs in `href`, `src`, `srcset`, `background` and `poster` attributes
+    /// and in CSS `url()` references.
+    ///
+    /// They are resolved against `base_url` or, if it is not set, the `href` of the document's
+    /// `base` element. References in linked and imported stylesheets are resolved against the
+    /// stylesheet URL instead.
+    pub absolutize_urls: bool,
+    /// Prefer the `href` of the document's `base` element over `base_url`.
+    ///
+    /// By default, the `base` element is used only if `base_url` is not set. A relative `href`
+    /// is resolved against `base_url`.
+    pub prefer_document_base_url: bool,
+    /// Remove "style" and "link" tags that are not inlined because they are disabled or their
+    /// `media` attribute does not match. By default, they are kept untouched.
+    pub remove_inapplicable_stylesheets: bool,
+    /// Serialize the output as XHTML instead of HTML5.
+    ///
+    /// Void elements are self-closed (`<br />`), boolean attributes get values
+    /// (`checked="checked"`), `&nbsp;` is written as `&#160;` and the `html` element gets the
+    /// XHTML namespace if it has none.
+    pub xhtml: bool,
+    /// Delimiter pairs of template syntax to preserve, e.g. `("{{", "}}")` and `("{%", "%}")`.
+    ///
+    /// Enclosed segments are protected from HTML parsing and written to the output verbatim,
+    /// so templates can be inlined before rendering. Pairs are tried in order, so longer
+    /// delimiters like `("{{{", "}}}")` should come first.
+    /// Segments inside tags should be separated from attributes by whitespace.
+    pub template_delimiters: Vec<(String, String)>,
+    /// Remove conditional comments, e.g. `<!--[if mso]>...<![endif]-->`, for clients other
+    /// than Outlook.
+    ///
+    /// Otherwise, CSS is inlined into the HTML inside them. Their contents are matched on their
+    /// own, so selectors that depend on elements outside of a comment, e.g. `.wrapper td`, do not
+    /// apply there. Stylesheets inside conditional comments are not loaded.
+    pub remove_conditional_comments: bool,
+    /// Write computed values of inherited properties explicitly onto descendant elements.
+    ///
+    /// Outlook's Word rendering engine and some webmail clients do not inherit properties
+    /// like `font-family` or `color` into tables. Elements that declare a property themselves,
+    /// directly or via a shorthand, keep their own value. Relative font sizes are resolved
+    /// against the parent's font size in pixels.
+    pub propagate_properties: Option<PropertyPropagation>,
+    /// Keep rules with dynamic pseudo-classes, like `:hover`, `:focus` or `:visited`, in a
+    /// "style" tag in the document's `head`.
+    ///
+    /// These rules can not be inlined. Other rules are inlined and removed as usual.
+    /// It has no effect if `keep_style_tags` is enabled, as the rules are kept anyway.
+    pub keep_dynamic_rules: bool,
+    /// Materialize `::before` and `::after` pseudo-elements as `span` elements with inlined
+    /// styles at the start and the end of their originating elements.
+    ///
+    /// The text of a `span` comes from the `content` property, which may contain strings and
+    /// `attr()` references. Pseudo-elements with other `content` values are not generated.
+    /// Otherwise, selectors with pseudo-elements are skipped as unsupported.
+    pub materialize_pseudo_elements: bool,
 }
 
 impl std::fmt::Debug for InlineOptions<'_> {
@@ -118,12 +240,14 @@ impl std::fmt::Debug for InlineOptions<'_> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct CssChunk {
     range: Range<usize>,
     /// The style node this chunk came from, if any.
     /// `None` for linked stylesheets, extra CSS, or fragment CSS.
     style_node: Option<NodeId>,
+    /// Rewritten `url()` functions with their original text, relative to the chunk start.
+    rewritten_urls: Vec<(Range<usize>, Box<str>)>,
 }
 
 type SelectorList<'i> = SmallVec<[&'i str; 2]>;
@@ -166,6 +290,152 @@ fn find_chunk_index(chunks: &[CssChunk], offset: usize) -> Option<usize> {
         .position(|chunk| chunk.range.contains(&offset))
 }
 
+/// Line and column (both 1-based) of `text` within the chunk of `source` it was sliced from.
+#[allow(clippy::arithmetic_side_effects)]
+fn source_location(source: &str, chunks: &[CssChunk], text: &str) -> (usize, usize) {
+    let offset = (text.as_ptr() as usize)
+        .saturating_sub(source.as_ptr() as usize)
+        .min(source.len());
+    let chunk = find_chunk_index(chunks, offset).map(|idx| &chunks[idx]);
+    let chunk_start = chunk.map_or(0, |chunk| chunk.range.start);
+    let prefix = original_prefix(
+        &source[chunk_start..offset],
+        chunk.map_or(&[], |chunk| chunk.rewritten_urls.as_slice()),
+    );
+    let line_start = prefix.rfind('\n').map_or(0, |idx| idx + 1);
+    (
+        prefix.matches('\n').count() + 1,
+        prefix[line_start..].chars().count() + 1,
+    )
+}
+
+/// Restore rewritten `url()` references in the beginning of a chunk.
+fn original_prefix<'p>(
+    prefix: &'p str,
+    rewritten_urls: &[(Range<usize>, Box<str>)],
+) -> Cow<'p, str> {
+    if rewritten_urls.is_empty() {
+        return Cow::Borrowed(prefix);
+    }
+    let mut original = String::with_capacity(prefix.len());
+    let mut last = 0;
+    for (range, url) in rewritten_urls {
+        if range.end > prefix.len() {
+            break;
+        }
+        original.push_str(&prefix[last..range.start]);
+        original.push_str(url);
+        last = range.end;
+    }
+    // Positions inside a rewritten reference point to its start
+    let end = rewritten_urls
+        .iter()
+        .find(|(range, _)| range.start < prefix.len() && range.end > prefix.len())
+        .map_or(prefix.len(), |(range, _)| range.start);
+    original.push_str(&prefix[last..end]);
+    Cow::Owned(original)
+}
+
+fn strict_error(source: &str, chunks: &[CssChunk], reason: &str, text: &str) -> InlineError {
+    let (line, column) = source_location(source, chunks, text);
+    parse_error(reason, line, column, text)
+}
+
+fn parse_error(reason: &str, line: usize, column: usize, text: &str) -> InlineError {
+    InlineError::ParseError(Cow::Owned(format!(
+        "{reason} at line {line}, column {column}: {text}"
+    )))
+}
+
+fn selector_error_reason(error: &html::ParseError<'_>) -> String {
+    match &error.kind {
+        ParseErrorKind::Custom(SelectorParseErrorKind::UnsupportedPseudoClassOrElement(name)) => {
+            format!("Unsupported pseudo-class or pseudo-element `{name}`")
+        }
+        _ => String::from("Invalid selector"),
+    }
+}
+
+/// The reason to skip a selector with a pseudo-element if pseudo-elements are not materialized.
+fn pseudo_element_reason(pseudo: PseudoElement) -> String {
+    format!(
+        "Unsupported pseudo-class or pseudo-element `{}`",
+        pseudo.to_css_string().trim_start_matches(':')
+    )
+}
+
+/// Styles of elements, or of their pseudo-elements if a selector has one.
+///
+/// Returns the pseudo-element as an error if pseudo-elements are not materialized.
+fn target_styles<'s, 'i>(
+    selectors: &Selectors,
+    styles: &'s mut DocumentStyleMap<'i>,
+    pseudo_styles: &'s mut PseudoElementStyleMap<'i>,
+    materialize_pseudo_elements: bool,
+) -> std::result::Result<&'s mut DocumentStyleMap<'i>, PseudoElement> {
+    match selectors.pseudo_element() {
+        None => Ok(styles),
+        Some(pseudo) if materialize_pseudo_elements => Ok(&mut pseudo_styles[pseudo.index()]),
+        Some(pseudo) => Err(pseudo),
+    }
+}
+
+/// Assign declarations to all elements matching `selectors`.
+/// Returns whether any element was matched.
+fn cascade<'i>(
+    document: &Document,
+    selectors: &Selectors,
+    specificity: Specificity,
+    caches: &mut SelectorCaches,
+    styles: &mut DocumentStyleMap<'i>,
+    declarations: &(impl Iterator<Item = (&'i str, &'i str)> + Clone),
+) -> bool {
+    let mut matched_any = false;
+    for matching_element in document.select(selectors, caches) {
+        matched_any = true;
+        let element_styles =
+            styles[matching_element.node_id.get()].get_or_insert_with(SmallVec::new);
+        // Iterate over pairs of property name & value
+        // Example: `padding`, `0`
+        for (prop_name, value) in declarations.clone() {
+            let new_important = value.trim_end().ends_with("!important");
+            let mut overridden = false;
+            // Linear search for the same property, its shorthands and longhands
+            let mut idx = 0;
+            while let Some(&(name, old_specificity, old_value)) = element_styles.get(idx) {
+                if properties::overlap(name, prop_name) {
+                    let old_important = old_value.trim_end().ends_with("!important");
+                    let wins = match (new_important, old_important) {
+                        // Equal importance; the higher specificity wins.
+                        (false, false) | (true, true) => old_specificity <= specificity,
+                        // Only the new value is important; it wins.
+                        (true, false) => true,
+                        // The old value is important and the new one is not; keep
+                        // the old value.
+                        (false, true) => false,
+                    };
+                    if wins {
+                        // The new value fully replaces the old one, e.g. `margin` replaces
+                        // `margin-top`
+                        if properties::covers(prop_name, name) {
+                            element_styles.remove(idx);
+                            continue;
+                        }
+                    } else if properties::covers(name, prop_name) {
+                        overridden = true;
+                        break;
+                    }
+                }
+                idx = idx.saturating_add(1);
+            }
+            if !overridden {
+                element_styles.push((prop_name, specificity, value));
+            }
+        }
+    }
+    matched_any
+}
+
 /// Compute chunk indices for all rules based on where their selectors point in the source.
 #[allow(clippy::arithmetic_side_effects)]
 fn compute_rule_chunk_indices(
@@ -218,10 +488,35 @@ impl CssBuffer {
             chunks.push(CssChunk {
                 range: start..end,
                 style_node,
+                rewritten_urls: Vec::new(),
             });
         }
     }
 
+    /// Push CSS with relative `url()` references resolved against `base`.
+    ///
+    /// Original references are kept, so positions in the source CSS can be reported.
+    fn push_absolutized(
+        &mut self,
+        style_node: Option<NodeId>,
+        content: &str,
+        base: Option<&Url>,
+        append_newline: bool,
+    ) {
+        let Some(base) = base.filter(|_| !content.is_empty()) else {
+            self.push(style_node, content, append_newline);
+            return;
+        };
+        let (content, rewritten_urls) = urls::absolutize_css_urls(content, base);
+        self.push(style_node, &content, append_newline);
+        if let Some(chunk) = self.chunks.as_mut().and_then(|chunks| chunks.last_mut()) {
+            chunk.rewritten_urls = rewritten_urls
+                .into_iter()
+                .map(|(range, original)| (range, Box::from(original)))
+                .collect();
+        }
+    }
+
     fn into_parts(self) -> (String, Option<Vec<CssChunk>>) {
         (self.raw, self.chunks)
     }
@@ -429,6 +724,21 @@ impl<'a> InlineOptions<'a> {
         self
     }
 
+    /// Set restrictions on locations of linked and imported stylesheets.
+    #[must_use]
+    pub fn resolver_policy(mut self, policy: Option<ResolverPolicy>) -> Self {
+        self.resolver_policy = policy;
+        self
+    }
+
+    /// Set the way to resolve stylesheets without blocking.
+    #[cfg(feature = "async")]
+    #[must_use]
+    pub fn async_resolver(mut self, resolver: Arc<dyn AsyncStylesheetResolver>) -> Self {
+        self.async_resolver = resolver;
+        self
+    }
+
     /// Remove selectors that were successfully inlined from inline `<style>` blocks.
     #[must_use]
     pub fn remove_inlined_selectors(mut self, enabled: bool) -> Self {
@@ -456,6 +766,114 @@ impl<'a> InlineOptions<'a> {
         self
     }
 
+    /// Generate presentational HTML attributes from CSS properties on supported elements.
+    #[must_use]
+    pub fn attribute_mappings(mut self, mappings: Vec<AttributeMapping>) -> Self {
+        self.attribute_mappings = mappings;
+        self
+    }
+
+    /// Fail on CSS that would otherwise be silently skipped.
+    ///
+    /// Unparsable or unsupported selectors, malformed declarations and invalid rules produce
+    /// [`InlineError::ParseError`] with the offending text and its position in the stylesheet.
+    #[must_use]
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Set a stylesheet that is parsed ahead of time and applied to every document.
+    #[must_use]
+    pub fn compiled_stylesheet(mut self, stylesheet: Option<Arc<CompiledStylesheet>>) -> Self {
+        self.compiled_stylesheet = stylesheet;
+        self
+    }
+
+    /// Replace `var()` references with values of CSS custom properties.
+    #[must_use]
+    pub fn resolve_css_variables(mut self, resolve: bool) -> Self {
+        self.resolve_css_variables = resolve;
+        self
+    }
+
+    /// Evaluate `@media` rules against the given environment and inline the matching ones.
+    #[must_use]
+    pub fn media_environment(mut self, environment: Option<MediaEnvironment>) -> Self {
+        self.media_environment = environment;
+        self
+    }
+
+    /// Load stylesheets referenced by `@import` rules.
+    #[must_use]
+    pub fn follow_imports(mut self, follow_imports: bool) -> Self {
+        self.follow_imports = follow_imports;
+        self
+    }
+
+    /// Rewrite relative URLs in attributes and CSS referring to other resources.
+    #[must_use]
+    pub fn absolutize_urls(mut self, absolutize_urls: bool) -> Self {
+        self.absolutize_urls = absolutize_urls;
+        self
+    }
+
+    /// Prefer the document's `base` element over `base_url`.
+    #[must_use]
+    pub fn prefer_document_base_url(mut self, prefer_document_base_url: bool) -> Self {
+        self.prefer_document_base_url = prefer_document_base_url;
+        self
+    }
+
+    /// Remove disabled "style" and "link" tags and ones with non-matching `media` attributes.
+    #[must_use]
+    pub fn remove_inapplicable_stylesheets(mut self, remove: bool) -> Self {
+        self.remove_inapplicable_stylesheets = remove;
+        self
+    }
+
+    /// Serialize the output as XHTML.
+    #[must_use]
+    pub fn xhtml(mut self, xhtml: bool) -> Self {
+        self.xhtml = xhtml;
+        self
+    }
+
+    /// Preserve template segments enclosed in the given delimiter pairs.
+    #[must_use]
+    pub fn template_delimiters(mut self, delimiters: Vec<(String, String)>) -> Self {
+        self.template_delimiters = delimiters;
+        self
+    }
+
+    /// Remove conditional comments.
+    #[must_use]
+    pub fn remove_conditional_comments(mut self, remove: bool) -> Self {
+        self.remove_conditional_comments = remove;
+        self
+    }
+
+    /// Write computed values of inherited properties onto descendant elements.
+    #[must_use]
+    pub fn propagate_properties(mut self, propagation: Option<PropertyPropagation>) -> Self {
+        self.propagate_properties = propagation;
+        self
+    }
+
+    /// Keep rules with dynamic pseudo-classes in a "style" tag.
+    #[must_use]
+    pub fn keep_dynamic_rules(mut self, keep_dynamic_rules: bool) -> Self {
+        self.keep_dynamic_rules = keep_dynamic_rules;
+        self
+    }
+
+    /// Materialize `::before` and `::after` pseudo-elements as `span` elements.
+    #[must_use]
+    pub fn materialize_pseudo_elements(mut self, materialize: bool) -> Self {
+        self.materialize_pseudo_elements = materialize;
+        self
+    }
+
     /// Create a new `CSSInliner` instance from this options.
     #[must_use]
     pub const fn build(self) -> CSSInliner<'a> {
@@ -479,9 +897,27 @@ impl Default for InlineOptions<'_> {
             extra_css: None,
             preallocate_node_capacity: 32,
             resolver: Arc::new(DefaultStylesheetResolver),
+            resolver_policy: None,
+            #[cfg(feature = "async")]
+            async_resolver: Arc::new(DefaultStylesheetResolver),
             remove_inlined_selectors: false,
             apply_width_attributes: false,
             apply_height_attributes: false,
+            attribute_mappings: Vec::new(),
+            strict: false,
+            compiled_stylesheet: None,
+            resolve_css_variables: false,
+            media_environment: None,
+            follow_imports: false,
+            absolutize_urls: false,
+            prefer_document_base_url: false,
+            remove_inapplicable_stylesheets: false,
+            xhtml: false,
+            template_delimiters: Vec::new(),
+            remove_conditional_comments: false,
+            propagate_properties: None,
+            keep_dynamic_rules: false,
+            materialize_pseudo_elements: false,
         }
     }
 }
@@ -498,6 +934,11 @@ pub struct CSSInliner<'a> {
 const GROWTH_COEFFICIENT: f64 = 1.5;
 // A rough coefficient to calculate the number of individual declarations based on the total CSS size.
 const DECLARATION_SIZE_COEFFICIENT: f64 = 30.0;
+// How deep `@import` rules are followed.
+const MAX_IMPORT_DEPTH: usize = 16;
+
+/// Stylesheets loaded ahead of inlining, by their locations.
+type Prefetched = FxHashMap<String, String>;
 
 fn allocate_output_buffer(html: &str) -> Vec<u8> {
     // Allocating more memory than the input HTML, as the inlined version is usually bigger
@@ -584,7 +1025,43 @@ impl<'a> CSSInliner<'a> {
     /// using the same inliner panicked while resolving external stylesheets.
     #[inline]
     pub fn inline_to<W: Write>(&self, html: &str, target: &mut W) -> Result<()> {
-        self.inline_to_impl(html, None, target, InliningMode::Document)
+        self.inline_to_impl(html, None, target, InliningMode::Document, None, None)
+    }
+
+    /// Inline CSS styles and return the resulting HTML together with a report on CSS that could
+    /// not be inlined.
+    ///
+    /// ```rust
+    /// let html = "<html><head><style>h1 { color: blue; } h2 { color: red; }</style></head><body><h1>Hello</h1></body></html>";
+    /// let (inlined, report) = css_inline::CSSInliner::default().inline_with_report(html)?;
+    /// assert_eq!(report.unmatched_selectors, vec!["h2"]);
+    /// # Ok::<(), css_inline::InlineError>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Inlining might fail for the following reasons:
+    ///   - Missing stylesheet file;
+    ///   - Remote stylesheet is not available;
+    ///   - IO errors;
+    ///   - Internal CSS selector parsing error;
+    ///
+    /// # Panics
+    ///
+    /// This function may panic if external stylesheet cache lock is poisoned, i.e. another thread
+    /// using the same inliner panicked while resolving external stylesheets.
+    pub fn inline_with_report(&self, html: &str) -> Result<(String, InlineReport)> {
+        let mut out = allocate_output_buffer(html);
+        let mut report = InlineReport::default();
+        self.inline_to_impl(
+            html,
+            None,
+            &mut out,
+            InliningMode::Document,
+            Some(&mut report),
+            None,
+        )?;
+        Ok((String::from_utf8_lossy(&out).to_string(), report))
     }
 
     /// Inline CSS into an HTML fragment.
@@ -637,7 +1114,227 @@ impl<'a> CSSInliner<'a> {
         css: &str,
         target: &mut W,
     ) -> Result<()> {
-        self.inline_to_impl(html, Some(css), target, InliningMode::Fragment)
+        self.inline_to_impl(html, Some(css), target, InliningMode::Fragment, None, None)
+    }
+
+    /// Inline CSS into an HTML fragment and return the result together with a report on CSS that
+    /// could not be inlined.
+    ///
+    /// See [`inline_fragment`](CSSInliner::inline_fragment) for details on fragment handling and
+    /// [`inline_with_report`](CSSInliner::inline_with_report) for details on the report.
+    ///
+    /// # Errors
+    ///
+    /// Inlining might fail for the following reasons:
+    ///   - Missing stylesheet file;
+    ///   - Remote stylesheet is not available;
+    ///   - IO errors;
+    ///   - Internal CSS selector parsing error;
+    ///
+    /// # Panics
+    ///
+    /// This function may panic if external stylesheet cache lock is poisoned, i.e. another thread
+    /// using the same inliner panicked while resolving external stylesheets.
+    pub fn inline_fragment_with_report(
+        &self,
+        html: &str,
+        css: &str,
+    ) -> Result<(String, InlineReport)> {
+        let mut out = allocate_output_buffer(html);
+        let mut report = InlineReport::default();
+        self.inline_to_impl(
+            html,
+            Some(css),
+            &mut out,
+            InliningMode::Fragment,
+            Some(&mut report),
+            None,
+        )?;
+        Ok((String::from_utf8_lossy(&out).to_string(), report))
+    }
+
+    /// Inline CSS styles like [`inline`](CSSInliner::inline), loading linked stylesheets via
+    /// the async resolver.
+    ///
+    /// All linked stylesheets are loaded concurrently and applied in document order. With
+    /// `follow_imports`, imported stylesheets are loaded concurrently as well, level by level.
+    ///
+    /// ```rust
+    /// # async fn run() -> Result<(), css_inline::InlineError> {
+    /// let html = r#"<html><head><link href="https://example.com/style.css" rel="stylesheet"></head><body><h1>Hello</h1></body></html>"#;
+    /// let inlined = css_inline::CSSInliner::default().inline_async(html).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Inlining might fail for the following reasons:
+    ///   - Missing stylesheet file;
+    ///   - Remote stylesheet is not available;
+    ///   - IO errors;
+    ///   - Internal CSS selector parsing error;
+    ///
+    /// # Panics
+    ///
+    /// This function may panic if external stylesheet cache lock is poisoned, i.e. another thread
+    /// using the same inliner panicked while resolving external stylesheets.
+    #[cfg(feature = "async")]
+    pub async fn inline_async(&self, html: &str) -> Result<String> {
+        let prefetched = self
+            .prefetch_stylesheets(html, InliningMode::Document)
+            .await?;
+        let mut out = allocate_output_buffer(html);
+        self.inline_to_impl(
+            html,
+            None,
+            &mut out,
+            InliningMode::Document,
+            None,
+            Some(&prefetched),
+        )?;
+        Ok(String::from_utf8_lossy(&out).to_string())
+    }
+
+    /// Inline CSS into an HTML fragment, loading linked stylesheets via the async resolver.
+    ///
+    /// See [`inline_fragment`](CSSInliner::inline_fragment) for details on fragment handling and
+    /// [`inline_async`](CSSInliner::inline_async) for details on loading stylesheets.
+    ///
+    /// # Errors
+    ///
+    /// Inlining might fail for the following reasons:
+    ///   - Missing stylesheet file;
+    ///   - Remote stylesheet is not available;
+    ///   - IO errors;
+    ///   - Internal CSS selector parsing error;
+    ///
+    /// # Panics
+    ///
+    /// This function may panic if external stylesheet cache lock is poisoned, i.e. another thread
+    /// using the same inliner panicked while resolving external stylesheets.
+    #[cfg(feature = "async")]
+    pub async fn inline_fragment_async(&self, html: &str, css: &str) -> Result<String> {
+        let prefetched = self
+            .prefetch_stylesheets(html, InliningMode::Fragment)
+            .await?;
+        let mut out = allocate_output_buffer(html);
+        self.inline_to_impl(
+            html,
+            Some(css),
+            &mut out,
+            InliningMode::Fragment,
+            None,
+            Some(&prefetched),
+        )?;
+        Ok(String::from_utf8_lossy(&out).to_string())
+    }
+
+    /// Load all stylesheets that inlining `html` requires via the async resolver.
+    #[cfg(feature = "async")]
+    async fn prefetch_stylesheets(&self, html: &str, mode: InliningMode) -> Result<Prefetched> {
+        // The document is not `Send`, so only stylesheet locations are kept across await points
+        // and the document is parsed again for inlining
+        let (mut pending, base_url) = self.stylesheet_locations(html, mode);
+        let mut prefetched = Prefetched::default();
+        // Imported stylesheets are known only after their importers are loaded
+        for _ in 0..MAX_IMPORT_DEPTH {
+            pending.retain(|url| !prefetched.contains_key(url));
+            pending.sort_unstable();
+            pending.dedup();
+            if pending.is_empty() {
+                break;
+            }
+            let stylesheets = futures_util::future::try_join_all(
+                pending.iter().map(|url| self.fetch_stylesheet(url)),
+            )
+            .await?;
+            let mut imports = Vec::new();
+            for (url, css) in pending.into_iter().zip(stylesheets) {
+                if self.options.follow_imports {
+                    imports.extend(
+                        parser::parse_imports(&css).iter().map(|import| {
+                            get_import_url(&import.href, Some(&url), base_url.as_ref())
+                        }),
+                    );
+                }
+                prefetched.insert(url, css);
+            }
+            pending = imports;
+        }
+        Ok(prefetched)
+    }
+
+    /// Locations of linked stylesheets and stylesheets imported by "style" tags, together with
+    /// the document's base URL.
+    #[cfg(feature = "async")]
+    fn stylesheet_locations(&self, html: &str, mode: InliningMode) -> (Vec<String>, Option<Url>) {
+        let protected = if self.options.template_delimiters.is_empty() {
+            None
+        } else {
+            templates::protect(html, &self.options.template_delimiters)
+        };
+        let html = protected.as_ref().map_or(html, |(html, _)| html.as_str());
+        let document = self.parse_document(html, mode);
+        let base_url = self.document_base_url(&document).map(Cow::into_owned);
+        let mut locations = Vec::new();
+        if self.options.follow_imports
+            && (self.options.inline_style_tags || self.options.keep_at_rules)
+        {
+            for (_, style) in document.styles() {
+                locations.extend(
+                    parser::parse_imports(style)
+                        .iter()
+                        .map(|import| get_import_url(&import.href, None, base_url.as_ref())),
+                );
+            }
+        }
+        if self.options.load_remote_stylesheets {
+            locations.extend(
+                linked_stylesheets(&document, base_url.as_ref())
+                    .into_iter()
+                    .map(Cow::into_owned),
+            );
+        }
+        (locations, base_url)
+    }
+
+    /// Retrieve a stylesheet via the async resolver, or from the cache if it was loaded before.
+    #[cfg(feature = "async")]
+    async fn fetch_stylesheet(&self, url: &str) -> Result<String> {
+        // Resolving host names here would block the executor. The resolver checks addresses
+        // when connecting instead
+        if let Some(policy) = &self.options.resolver_policy {
+            policy.check_without_lookup(url)?;
+        }
+        if let Some(css) = self.cached_stylesheet(url) {
+            return Ok(css);
+        }
+        let css = match &self.options.resolver_policy {
+            Some(policy) => {
+                self.options
+                    .async_resolver
+                    .retrieve_with_policy(url, policy)
+                    .await?
+            }
+            None => self.options.async_resolver.retrieve(url).await?,
+        };
+        self.cache_stylesheet(url, &css);
+        Ok(css)
+    }
+
+    /// Parse the document and exclude stylesheets that do not apply to the media environment.
+    fn parse_document(&self, html: &str, mode: InliningMode) -> Document {
+        let mut document = Document::parse_with_options(
+            html.as_bytes(),
+            self.options.preallocate_node_capacity,
+            mode,
+        );
+        document.exclude_inapplicable_stylesheets(
+            &self.options.media_environment.unwrap_or_default(),
+            self.options.remove_inapplicable_stylesheets,
+        );
+        document
     }
 
     #[allow(clippy::too_many_lines)]
@@ -647,12 +1344,15 @@ impl<'a> CSSInliner<'a> {
         css: Option<&str>,
         target: &mut W,
         mode: InliningMode,
+        mut report: Option<&mut InlineReport>,
+        prefetched: Option<&Prefetched>,
     ) -> Result<()> {
-        let mut document = Document::parse_with_options(
-            html.as_bytes(),
-            self.options.preallocate_node_capacity,
-            mode,
-        );
+        let protected = if self.options.template_delimiters.is_empty() {
+            None
+        } else {
+            templates::protect(html, &self.options.template_delimiters)
+        };
+        let html = protected.as_ref().map_or(html, |(html, _)| html.as_str());
         // CSS rules may overlap, and the final set of rules applied to an element depend on
         // selectors' specificity - selectors with higher specificity have more priority.
         // Inlining happens in two major steps:
@@ -660,7 +1360,14 @@ impl<'a> CSSInliner<'a> {
         //      selector's specificity. When two rules overlap on the same declaration, then
         //      the one with higher specificity replaces another.
         //   2. Resulting styles are merged into existing "style" tags.
+        let mut document = self.parse_document(html, mode);
+        let base_url = self.document_base_url(&document);
+        let base_url = base_url.as_deref();
+        // Relative `url()` references in CSS from the document are resolved against it
+        let css_base_url = base_url.filter(|_| self.options.absolutize_urls);
         let track_selector_cleanup = self.options.remove_inlined_selectors;
+        // Strict mode reports positions relative to the stylesheet the CSS came from
+        let track_chunks = track_selector_cleanup || self.options.strict;
         let mut size_estimate: usize = if self.options.inline_style_tags {
             document
                 .styles()
@@ -678,44 +1385,62 @@ impl<'a> CSSInliner<'a> {
         if let Some(css) = css {
             size_estimate = size_estimate.saturating_add(css.len());
         }
-        let mut css_buffer = CssBuffer::new(track_selector_cleanup);
+        let mut css_buffer = CssBuffer::new(track_chunks);
         css_buffer.raw.reserve(size_estimate);
         if self.options.inline_style_tags || self.options.keep_at_rules {
             for (node_id, style) in document.styles() {
                 let style_node = track_selector_cleanup.then_some(node_id);
-                css_buffer.push(style_node, style, true);
+                if self.options.follow_imports {
+                    let (imported, style) = self.resolve_imports(
+                        style,
+                        None,
+                        base_url,
+                        &mut Vec::new(),
+                        report.as_deref_mut(),
+                        prefetched,
+                    )?;
+                    css_buffer.push(None, &imported, true);
+                    css_buffer.push_absolutized(style_node, &style, css_base_url, true);
+                } else {
+                    css_buffer.push_absolutized(style_node, style, css_base_url, true);
+                }
             }
         }
         if self.options.load_remote_stylesheets {
-            let mut links = document.stylesheets().collect::<Vec<&str>>();
-            links.sort_unstable();
-            links.dedup();
-            for href in &links {
-                let url = self.get_full_url(href);
-                #[cfg(feature = "stylesheet-cache")]
-                if let Some(lock) = self.options.cache.as_ref() {
-                    let mut cache = lock.lock().expect("Cache lock is poisoned");
-                    if let Some(cached) = cache.get(url.as_ref()) {
-                        css_buffer.push(None, cached, true);
-                        continue;
-                    }
-                }
-
-                let css = self.options.resolver.retrieve(url.as_ref())?;
-                css_buffer.push(None, &css, true);
-
-                #[cfg(feature = "stylesheet-cache")]
-                if let Some(lock) = self.options.cache.as_ref() {
-                    let mut cache = lock.lock().expect("Cache lock is poisoned");
-                    cache.put(url.into_owned(), css);
+            for url in linked_stylesheets(&document, base_url) {
+                let css = self.load_stylesheet(&url, report.as_deref_mut(), prefetched)?;
+                if self.options.follow_imports {
+                    let mut importers = vec![url.to_string()];
+                    let (imported, css) = self.resolve_imports(
+                        &css,
+                        Some(&url),
+                        base_url,
+                        &mut importers,
+                        report.as_deref_mut(),
+                        prefetched,
+                    )?;
+                    css_buffer.push(None, &imported, true);
+                    css_buffer.push_absolutized(
+                        None,
+                        &css,
+                        self.css_base_url(Some(&url), base_url).as_deref(),
+                        true,
+                    );
+                } else {
+                    css_buffer.push_absolutized(
+                        None,
+                        &css,
+                        self.css_base_url(Some(&url), base_url).as_deref(),
+                        true,
+                    );
                 }
             }
         }
         if let Some(extra_css) = &self.options.extra_css {
-            css_buffer.push(None, extra_css, false);
+            css_buffer.push_absolutized(None, extra_css, css_base_url, false);
         }
         if let Some(css) = css {
-            css_buffer.push(None, css, false);
+            css_buffer.push_absolutized(None, css, css_base_url, false);
         }
         let (raw_styles, css_chunks) = css_buffer.into_parts();
         let mut selector_cleanup_state = if track_selector_cleanup {
@@ -723,8 +1448,9 @@ impl<'a> CSSInliner<'a> {
         } else {
             None
         };
-        if let (Some(state), Some(chunks)) = (&mut selector_cleanup_state, css_chunks) {
-            state.chunks = chunks;
+        let css_chunks = css_chunks.unwrap_or_default();
+        if let Some(state) = &mut selector_cleanup_state {
+            state.chunks.clone_from(&css_chunks);
         }
         let mut parse_input = cssparser::ParserInput::new(&raw_styles);
         let mut parser = cssparser::Parser::new(&mut parse_input);
@@ -741,50 +1467,129 @@ impl<'a> CSSInliner<'a> {
                 .max(16),
         );
         let mut rule_list = Vec::with_capacity(declarations.capacity() / 3);
+        // CSS skipped inside rule blocks and on the top level
+        let mut invalid_nested = Vec::new();
+        let mut invalid = Vec::new();
         let at_rules = if self.options.keep_at_rules {
             let mut at_rules = String::new();
             for rule in cssparser::StyleSheetParser::new(
                 &mut parser,
-                &mut parser::AtRuleFilteringParser::new(&mut declarations, &mut at_rules),
-            )
-            .flatten()
-            {
-                if self.options.inline_style_tags {
-                    rule_list.push(rule);
+                &mut parser::AtRuleFilteringParser::new(
+                    &mut declarations,
+                    &mut invalid_nested,
+                    &mut at_rules,
+                    self.options.media_environment.as_ref(),
+                ),
+            ) {
+                match rule {
+                    Ok(rules) => {
+                        if self.options.inline_style_tags {
+                            rule_list.extend(rules);
+                        }
+                    }
+                    Err((error, text)) => {
+                        invalid.push(InvalidCss::new(&error, text, InvalidCssKind::Rule));
+                    }
                 }
             }
+            if let Some(stylesheet) = &self.options.compiled_stylesheet {
+                at_rules.push_str(&stylesheet.at_rules);
+            }
             Some(at_rules)
         } else if !raw_styles.is_empty() {
             // At this point, we collected some styles from at least one source, hence we need to process it.
             for rule in cssparser::StyleSheetParser::new(
                 &mut parser,
-                &mut parser::CSSRuleListParser::new(&mut declarations),
-            )
-            .flatten()
-            {
-                rule_list.push(rule);
+                &mut parser::CSSRuleListParser::new(
+                    &mut declarations,
+                    &mut invalid_nested,
+                    self.options.media_environment.as_ref(),
+                ),
+            ) {
+                match rule {
+                    Ok(rules) => rule_list.extend(rules),
+                    Err((error, text)) => {
+                        invalid.push(InvalidCss::new(&error, text, InvalidCssKind::Rule));
+                    }
+                }
             }
             None
         } else {
             None
         };
+        if let Some(report) = report.as_deref_mut() {
+            for item in invalid.iter().chain(&invalid_nested) {
+                let target = match item.kind {
+                    InvalidCssKind::AtRule => &mut report.dropped_at_rules,
+                    InvalidCssKind::Rule => &mut report.invalid_rules,
+                    InvalidCssKind::Declaration => &mut report.invalid_declarations,
+                };
+                target.push(item.text.to_string());
+            }
+        }
+        if self.options.strict {
+            // Fail on the first problem in source order. At-rules are valid CSS, they are just
+            // not applicable to inlining.
+            if let Some(item) = invalid
+                .iter()
+                .chain(&invalid_nested)
+                .filter(|item| item.kind != InvalidCssKind::AtRule)
+                .min_by_key(|item| item.text.as_ptr())
+            {
+                let reason = match item.kind {
+                    InvalidCssKind::Declaration => "Invalid declaration",
+                    _ => "Invalid rule",
+                };
+                return Err(strict_error(&raw_styles, &css_chunks, reason, item.text));
+            }
+        }
+        if let Some(stylesheet) = &self.options.compiled_stylesheet {
+            for skipped in &stylesheet.skipped {
+                // With `keep_at_rules` at-rules are moved to the output as is
+                if skipped.kind == InvalidCssKind::AtRule && self.options.keep_at_rules {
+                    continue;
+                }
+                if self.options.strict && skipped.kind != InvalidCssKind::AtRule {
+                    return Err(skipped.to_error());
+                }
+                if let Some(report) = report.as_deref_mut() {
+                    let target = match skipped.kind {
+                        InvalidCssKind::AtRule => &mut report.dropped_at_rules,
+                        InvalidCssKind::Rule => &mut report.invalid_rules,
+                        InvalidCssKind::Declaration => &mut report.invalid_declarations,
+                    };
+                    target.push(skipped.text.to_string());
+                }
+            }
+        }
         // Compute chunk indices for all rules once, before processing
         let rule_chunk_indices = selector_cleanup_state
             .as_ref()
             .map(|state| compute_rule_chunk_indices(&rule_list, &raw_styles, &state.chunks))
             .unwrap_or_default();
+        // Rules that can not be inlined and are written to a separate "style" tag
+        let mut dynamic_rules =
+            (self.options.keep_dynamic_rules && !self.options.keep_style_tags).then(String::new);
         // Vec indexed by NodeId for O(1) access instead of hash lookups
         let mut styles: Vec<Option<SmallVec<[_; 4]>>> = vec![None; document.nodes.len()];
+        let mut pseudo_styles: PseudoElementStyleMap<'_> =
+            if self.options.materialize_pseudo_elements {
+                [
+                    vec![None; document.nodes.len()],
+                    vec![None; document.nodes.len()],
+                ]
+            } else {
+                [Vec::new(), Vec::new()]
+            };
         // This cache is unused but required in the `selectors` API
         let mut caches = SelectorCaches::default();
         for (rule_id, (selectors, (start, end))) in rule_list.iter().enumerate() {
-            // Only CSS Syntax Level 3 is supported, therefore it is OK to split by `,`
-            // With `is` or `where` selectors (Level 4) this split should be done on the parser level
-            for selector in selectors.split(',') {
-                let mut matched_any = false;
+            for selector in split_selector_list(selectors) {
                 // Quick check: skip selectors whose anchor doesn't exist in the document
-                // This avoids parsing selectors that can't possibly match anything
-                if !document.anchor_exists(selector) {
+                // This avoids parsing selectors that can't possibly match anything.
+                // The report and strict mode need to tell unsupported selectors apart, so they
+                // always parse them.
+                if report.is_none() && !self.options.strict && !document.anchor_exists(selector) {
                     if let Some(state) = selector_cleanup_state.as_mut() {
                         if let Some(chunk_index) =
                             rule_chunk_indices.get(rule_id).copied().flatten()
@@ -800,46 +1605,82 @@ impl<'a> CSSInliner<'a> {
                     }
                     continue;
                 }
-                if let Ok(matching_elements) = document.select(selector, &mut caches) {
-                    let specificity = matching_elements.specificity();
-                    for matching_element in matching_elements {
+                let mut matched_any = false;
+                match Selectors::compile(selector) {
+                    Ok(selectors) if dynamic_rules.is_some() && selectors.is_dynamic() => {
+                        if let Some(dynamic_rules) = dynamic_rules.as_mut() {
+                            push_rule(
+                                dynamic_rules,
+                                selector.trim(),
+                                declarations[*start..*end]
+                                    .iter()
+                                    .map(|(name, value)| (name.as_ref(), *value)),
+                                self.options.minify_css,
+                            );
+                        }
+                        // Moved to the output, so it is removed from its original block
                         matched_any = true;
-                        let element_styles = styles[matching_element.node_id.get()]
-                            .get_or_insert_with(SmallVec::new);
-                        // Iterate over pairs of property name & value
-                        // Example: `padding`, `0`
-                        for (name, value) in &declarations[*start..*end] {
-                            let prop_name = name.as_ref();
-                            // Linear search for existing property
-                            if let Some(idx) =
-                                element_styles.iter().position(|(n, _, _)| *n == prop_name)
-                            {
-                                let entry: &mut (&str, Specificity, &str) =
-                                    &mut element_styles[idx];
-                                let new_important = value.trim_end().ends_with("!important");
-                                let old_important = entry.2.trim_end().ends_with("!important");
-                                match (new_important, old_important) {
-                                    // Equal importance; the higher specificity wins.
-                                    (false, false) | (true, true) => {
-                                        if entry.1 <= specificity {
-                                            entry.1 = specificity;
-                                            entry.2 = *value;
-                                        }
-                                    }
-                                    // Only the new value is important; it wins.
-                                    (true, false) => {
-                                        entry.1 = specificity;
-                                        entry.2 = *value;
+                    }
+                    Ok(selectors) => {
+                        match target_styles(
+                            &selectors,
+                            &mut styles,
+                            &mut pseudo_styles,
+                            self.options.materialize_pseudo_elements,
+                        ) {
+                            Ok(target) => {
+                                matched_any = cascade(
+                                    &document,
+                                    &selectors,
+                                    selectors.specificity(),
+                                    &mut caches,
+                                    target,
+                                    &declarations[*start..*end]
+                                        .iter()
+                                        .map(|(name, value)| (name.as_ref(), *value)),
+                                );
+                                if !matched_any {
+                                    if let Some(report) = report.as_deref_mut() {
+                                        report
+                                            .unmatched_selectors
+                                            .push(selector.trim().to_string());
                                     }
-                                    // The old value is important and the new one is not; keep
-                                    // the old value.
-                                    (false, true) => {}
                                 }
-                            } else {
-                                element_styles.push((prop_name, specificity, *value));
+                            }
+                            Err(pseudo) => {
+                                if self.options.strict {
+                                    return Err(strict_error(
+                                        &raw_styles,
+                                        &css_chunks,
+                                        &pseudo_element_reason(pseudo),
+                                        selector.trim(),
+                                    ));
+                                }
+                                if let Some(report) = report.as_deref_mut() {
+                                    report
+                                        .unsupported_selectors
+                                        .push(selector.trim().to_string());
+                                }
                             }
                         }
                     }
+                    // Ignore not parsable selectors. E.g. there is no parser for @media queries
+                    // Which means that they will fall into this category and will be ignored
+                    Err(error) => {
+                        if self.options.strict {
+                            return Err(strict_error(
+                                &raw_styles,
+                                &css_chunks,
+                                &selector_error_reason(&error),
+                                selector.trim(),
+                            ));
+                        }
+                        if let Some(report) = report.as_deref_mut() {
+                            report
+                                .unsupported_selectors
+                                .push(selector.trim().to_string());
+                        }
+                    }
                 }
                 if let Some(state) = selector_cleanup_state.as_mut() {
                     if let Some(chunk_index) = rule_chunk_indices.get(rule_id).copied().flatten() {
@@ -852,8 +1693,68 @@ impl<'a> CSSInliner<'a> {
                         });
                     }
                 }
-                // Ignore not parsable selectors. E.g. there is no parser for @media queries
-                // Which means that they will fall into this category and will be ignored
+            }
+        }
+        if let Some(stylesheet) = &self.options.compiled_stylesheet {
+            for rule in &stylesheet.rules {
+                for selector in &rule.selectors {
+                    match &selector.compiled {
+                        Ok((selectors, _)) if dynamic_rules.is_some() && selectors.is_dynamic() => {
+                            if let Some(dynamic_rules) = dynamic_rules.as_mut() {
+                                push_rule(
+                                    dynamic_rules,
+                                    &selector.text,
+                                    rule.declarations
+                                        .iter()
+                                        .map(|(name, value)| (name.as_ref(), value.as_ref())),
+                                    self.options.minify_css,
+                                );
+                            }
+                        }
+                        Ok((selectors, specificity)) => {
+                            if let Ok(target) = target_styles(
+                                selectors,
+                                &mut styles,
+                                &mut pseudo_styles,
+                                self.options.materialize_pseudo_elements,
+                            ) {
+                                let matched_any = cascade(
+                                    &document,
+                                    selectors,
+                                    *specificity,
+                                    &mut caches,
+                                    target,
+                                    &rule
+                                        .declarations
+                                        .iter()
+                                        .map(|(name, value)| (name.as_ref(), value.as_ref())),
+                                );
+                                if !matched_any {
+                                    if let Some(report) = report.as_deref_mut() {
+                                        report.unmatched_selectors.push(selector.text.to_string());
+                                    }
+                                }
+                            } else {
+                                if let Some(skipped) = &selector.pseudo_element {
+                                    if self.options.strict {
+                                        return Err(skipped.to_error());
+                                    }
+                                }
+                                if let Some(report) = report.as_deref_mut() {
+                                    report.unsupported_selectors.push(selector.text.to_string());
+                                }
+                            }
+                        }
+                        Err(skipped) => {
+                            if self.options.strict {
+                                return Err(skipped.to_error());
+                            }
+                            if let Some(report) = report.as_deref_mut() {
+                                report.unsupported_selectors.push(selector.text.to_string());
+                            }
+                        }
+                    }
+                }
             }
         }
         let cleanup_requires_css = selector_cleanup_state
@@ -868,40 +1769,472 @@ impl<'a> CSSInliner<'a> {
                 &declarations,
             );
         }
+        if self.options.remove_conditional_comments {
+            for node in document.conditional_comments() {
+                document.detach_node(node);
+            }
+        } else {
+            self.inline_conditional_comments(&mut document, &rule_list, &declarations, base_url)?;
+        }
+        let at_rules = match (dynamic_rules, at_rules) {
+            (Some(mut dynamic_rules), at_rules) if !dynamic_rules.is_empty() => {
+                if let Some(at_rules) = at_rules.filter(|at_rules| !at_rules.is_empty()) {
+                    dynamic_rules.push(' ');
+                    dynamic_rules.push_str(&at_rules);
+                }
+                Some(dynamic_rules)
+            }
+            (_, at_rules) => at_rules,
+        };
+        let url_base = base_url.filter(|_| self.options.absolutize_urls);
+        if let Some((_, templates)) = &protected {
+            let mut buffer = Vec::with_capacity(html.len());
+            self.serialize(
+                &document,
+                &mut buffer,
+                styles,
+                pseudo_styles,
+                keep_style_tags,
+                self.options.keep_link_tags,
+                at_rules.as_ref(),
+                mode,
+                url_base,
+            )?;
+            templates.restore(&buffer, target)
+        } else {
+            self.serialize(
+                &document,
+                target,
+                styles,
+                pseudo_styles,
+                keep_style_tags,
+                self.options.keep_link_tags,
+                at_rules.as_ref(),
+                mode,
+                url_base,
+            )
+        }
+    }
+
+    /// Inline CSS into the HTML inside downlevel-hidden conditional comments, e.g.
+    /// `<!--[if mso]><table><![endif]-->`.
+    ///
+    /// Their contents are parsed as fragments and matched against the rules already parsed for
+    /// the document. Stylesheets inside the comments are kept as is and not loaded. Each fragment
+    /// is matched on its own, so selectors that depend on elements outside of the comment, e.g.
+    /// `.wrapper td`, do not apply. Contents that can not be parsed without changing their tags,
+    /// like unclosed elements, are kept as is.
+    fn inline_conditional_comments(
+        &self,
+        document: &mut Document,
+        rule_list: &[parser::QualifiedRule<'_>],
+        declarations: &[parser::Declaration<'_>],
+        base_url: Option<&Url>,
+    ) -> Result<()> {
+        let materialize = self.options.materialize_pseudo_elements;
+        // Dynamic rules are already moved to the "style" tag
+        let skip_dynamic = self.options.keep_dynamic_rules && !self.options.keep_style_tags;
+        let mut caches = SelectorCaches::default();
+        for node in document.conditional_comments() {
+            let NodeData::Comment { text } = &document[node].data else {
+                continue;
+            };
+            let Some((condition, content)) = split_downlevel_hidden(text) else {
+                continue;
+            };
+            let fragment = self.parse_document(content, InliningMode::Fragment);
+            let mut styles: DocumentStyleMap<'_> = vec![None; fragment.nodes.len()];
+            let mut pseudo_styles: PseudoElementStyleMap<'_> = if materialize {
+                [
+                    vec![None; fragment.nodes.len()],
+                    vec![None; fragment.nodes.len()],
+                ]
+            } else {
+                [Vec::new(), Vec::new()]
+            };
+            for (selectors, (start, end)) in rule_list {
+                let declarations = declarations[*start..*end]
+                    .iter()
+                    .map(|(name, value)| (name.as_ref(), *value));
+                for selector in split_selector_list(selectors) {
+                    if !fragment.anchor_exists(selector) {
+                        continue;
+                    }
+                    // Unsupported selectors are already reported for the document
+                    let Ok(selectors) = Selectors::compile(selector) else {
+                        continue;
+                    };
+                    if skip_dynamic && selectors.is_dynamic() {
+                        continue;
+                    }
+                    if let Ok(target) =
+                        target_styles(&selectors, &mut styles, &mut pseudo_styles, materialize)
+                    {
+                        cascade(
+                            &fragment,
+                            &selectors,
+                            selectors.specificity(),
+                            &mut caches,
+                            target,
+                            &declarations,
+                        );
+                    }
+                }
+            }
+            if let Some(stylesheet) = &self.options.compiled_stylesheet {
+                for rule in &stylesheet.rules {
+                    let declarations = rule
+                        .declarations
+                        .iter()
+                        .map(|(name, value)| (name.as_ref(), value.as_ref()));
+                    for selector in &rule.selectors {
+                        let Ok((selectors, specificity)) = &selector.compiled else {
+                            continue;
+                        };
+                        if skip_dynamic && selectors.is_dynamic() {
+                            continue;
+                        }
+                        if let Ok(target) =
+                            target_styles(selectors, &mut styles, &mut pseudo_styles, materialize)
+                        {
+                            cascade(
+                                &fragment,
+                                selectors,
+                                *specificity,
+                                &mut caches,
+                                target,
+                                &declarations,
+                            );
+                        }
+                    }
+                }
+            }
+            let mut inlined = Vec::with_capacity(content.len());
+            self.serialize(
+                &fragment,
+                &mut inlined,
+                styles,
+                pseudo_styles,
+                true,
+                true,
+                None,
+                InliningMode::Fragment,
+                base_url.filter(|_| self.options.absolutize_urls),
+            )?;
+            let Ok(inlined) = String::from_utf8(inlined) else {
+                continue;
+            };
+            if have_same_tags(content, &inlined) {
+                let text = format!("{condition}{inlined}<![endif]");
+                document.set_comment_text(node, &text);
+            }
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn serialize<'i, W: Write>(
+        &self,
+        document: &Document,
+        target: &mut W,
+        styles: DocumentStyleMap<'i>,
+        pseudo_styles: PseudoElementStyleMap<'i>,
+        keep_style_tags: bool,
+        keep_link_tags: bool,
+        at_rules: Option<&String>,
+        mode: InliningMode,
+        url_base: Option<&Url>,
+    ) -> Result<()> {
+        let mut attribute_mappings = SmallVec::<[AttributeMapping; 8]>::new();
+        if self.options.apply_width_attributes {
+            attribute_mappings.push(AttributeMapping::Width);
+        }
+        if self.options.apply_height_attributes {
+            attribute_mappings.push(AttributeMapping::Height);
+        }
+        for mapping in &self.options.attribute_mappings {
+            if !attribute_mappings.contains(mapping) {
+                attribute_mappings.push(*mapping);
+            }
+        }
         document.serialize(
             target,
             styles,
+            pseudo_styles,
             keep_style_tags,
-            self.options.keep_link_tags,
+            keep_link_tags,
             self.options.minify_css,
-            at_rules.as_ref(),
+            at_rules,
             mode,
-            self.options.apply_width_attributes,
-            self.options.apply_height_attributes,
-        )?;
-        Ok(())
+            &attribute_mappings,
+            self.options.resolve_css_variables,
+            url_base,
+            self.options.xhtml,
+            self.options.propagate_properties.as_ref(),
+        )
+    }
+
+    /// Retrieve a stylesheet via the resolver, or from the cache if it was loaded before.
+    ///
+    /// Stylesheets that are already fetched by the async resolver are taken from `prefetched`.
+    /// The blocking resolver is never used on the async path, as it may not run inside an async
+    /// runtime.
+    fn load_stylesheet(
+        &self,
+        url: &str,
+        report: Option<&mut InlineReport>,
+        prefetched: Option<&Prefetched>,
+    ) -> Result<String> {
+        let css = if let Some(prefetched) = prefetched {
+            prefetched
+                .get(url)
+                .cloned()
+                .ok_or_else(|| InlineError::MissingStyleSheet {
+                    path: url.to_string(),
+                })?
+        } else {
+            self.check_policy(url)?;
+            if let Some(css) = self.cached_stylesheet(url) {
+                css
+            } else {
+                let css = match &self.options.resolver_policy {
+                    Some(policy) => self.options.resolver.retrieve_with_policy(url, policy)?,
+                    None => self.options.resolver.retrieve(url)?,
+                };
+                self.cache_stylesheet(url, &css);
+                css
+            }
+        };
+        if let Some(report) = report {
+            if css.trim().is_empty() {
+                report.empty_stylesheets.push(url.to_string());
+            }
+            report.resolved_stylesheets.push(url.to_string());
+        }
+        Ok(css)
+    }
+
+    /// Check whether the resolver policy allows loading a stylesheet from the location.
+    fn check_policy(&self, location: &str) -> Result<()> {
+        match &self.options.resolver_policy {
+            Some(policy) => policy.check(location),
+            None => Ok(()),
+        }
+    }
+
+    /// A stylesheet from the cache, if it was loaded before.
+    #[cfg_attr(not(feature = "stylesheet-cache"), allow(clippy::unused_self))]
+    fn cached_stylesheet(&self, url: &str) -> Option<String> {
+        #[cfg(feature = "stylesheet-cache")]
+        {
+            self.options.cache.as_ref().and_then(|lock| {
+                let mut cache = lock.lock().expect("Cache lock is poisoned");
+                cache.get(url).cloned()
+            })
+        }
+        #[cfg(not(feature = "stylesheet-cache"))]
+        {
+            let _ = url;
+            None
+        }
+    }
+
+    #[cfg_attr(not(feature = "stylesheet-cache"), allow(clippy::unused_self))]
+    fn cache_stylesheet(&self, url: &str, css: &str) {
+        #[cfg(feature = "stylesheet-cache")]
+        if let Some(lock) = self.options.cache.as_ref() {
+            let mut cache = lock.lock().expect("Cache lock is poisoned");
+            cache.put(url.to_string(), css.to_string());
+        }
+        #[cfg(not(feature = "stylesheet-cache"))]
+        let _ = (url, css);
     }
 
-    fn get_full_url<'u>(&self, href: &'u str) -> Cow<'u, str> {
-        // Valid absolute URL
-        if Url::parse(href).is_ok() {
-            return Cow::Borrowed(href);
+    /// The URL that relative `url()` references in CSS loaded from `location` are resolved
+    /// against, if they are absolutized.
+    fn css_base_url<'u>(
+        &self,
+        location: Option<&str>,
+        base_url: Option<&'u Url>,
+    ) -> Option<Cow<'u, Url>> {
+        if !self.options.absolutize_urls {
+            return None;
+        }
+        // URLs like `data:` can not be a base for relative references
+        location
+            .and_then(|location| Url::parse(location).ok())
+            .filter(|location| !location.cannot_be_a_base())
+            .map(Cow::Owned)
+            .or_else(|| base_url.map(Cow::Borrowed))
+    }
+
+    /// Load stylesheets referenced by `@import` rules at the start of `css`.
+    ///
+    /// Returns the imported CSS, with nested imports resolved, and `css` without the resolved
+    /// rules. They are replaced with whitespace to keep positions of the remaining CSS.
+    /// `importers` are the locations of the stylesheets being resolved, for detecting cycles.
+    fn resolve_imports<'c>(
+        &self,
+        css: &'c str,
+        location: Option<&str>,
+        base_url: Option<&Url>,
+        importers: &mut Vec<String>,
+        mut report: Option<&mut InlineReport>,
+        prefetched: Option<&Prefetched>,
+    ) -> Result<(String, Cow<'c, str>)> {
+        let imports = parser::parse_imports(css);
+        let mut imported = String::new();
+        if imports.is_empty() {
+            return Ok((imported, Cow::Borrowed(css)));
         }
-        if let Some(base_url) = &self.options.base_url {
-            // Use the same scheme as the base URL
-            if href.starts_with("//") {
-                return Cow::Owned(format!("{}:{}", base_url.scheme(), href));
+        let mut remainder = String::with_capacity(css.len());
+        let mut last = 0;
+        for import in imports {
+            let url = get_import_url(&import.href, location, base_url);
+            if importers.len() >= MAX_IMPORT_DEPTH || importers.contains(&url) {
+                continue;
             }
-            // Not a URL, then it is a relative URL
-            if let Ok(new_url) = base_url.join(href) {
-                return Cow::Owned(new_url.into());
+            let stylesheet = self.load_stylesheet(&url, report.as_deref_mut(), prefetched)?;
+            importers.push(url.clone());
+            let (nested, stylesheet) = self.resolve_imports(
+                &stylesheet,
+                Some(&url),
+                base_url,
+                importers,
+                report.as_deref_mut(),
+                prefetched,
+            )?;
+            importers.pop();
+            let stylesheet = match self.css_base_url(Some(&url), base_url) {
+                Some(base) => urls::absolutize_css_urls(&stylesheet, &base).0,
+                None => Cow::Borrowed(stylesheet.as_ref()),
+            };
+            if import.media.is_empty() {
+                imported.push_str(&nested);
+                imported.push_str(&stylesheet);
+            } else {
+                // Keep the condition, so it is evaluated like other `@media` rules
+                imported.push_str("@media ");
+                imported.push_str(import.media);
+                imported.push_str(" {\n");
+                imported.push_str(&nested);
+                imported.push_str(&stylesheet);
+                imported.push_str("\n}");
             }
+            imported.push('\n');
+            remainder.push_str(&css[last..import.range.start]);
+            remainder.extend(css[import.range.clone()].chars().map(|c| {
+                if c == '\n' {
+                    '\n'
+                } else {
+                    ' '
+                }
+            }));
+            last = import.range.end;
+        }
+        remainder.push_str(&css[last..]);
+        Ok((imported, Cow::Owned(remainder)))
+    }
+
+    /// The base URL of the document: `base_url` or the `href` of its `base` element.
+    fn document_base_url(&self, document: &Document) -> Option<Cow<'_, Url>> {
+        let base_url = self.options.base_url.as_ref();
+        if base_url.is_some() && !self.options.prefer_document_base_url {
+            return base_url.map(Cow::Borrowed);
+        }
+        // A relative `href` is resolved against the document URL
+        document
+            .base_href()
+            .and_then(|href| match base_url {
+                Some(base_url) => base_url.join(href).ok(),
+                None => Url::parse(href).ok(),
+            })
+            .map(Cow::Owned)
+            .or_else(|| base_url.map(Cow::Borrowed))
+    }
+}
+
+/// Append a style rule to CSS that is written to the output as is.
+fn push_rule<'d>(
+    css: &mut String,
+    selector: &str,
+    declarations: impl Iterator<Item = (&'d str, &'d str)>,
+    minify_css: bool,
+) {
+    if !css.is_empty() && !minify_css {
+        css.push(' ');
+    }
+    css.push_str(selector);
+    css.push_str(if minify_css { "{" } else { " { " });
+    for (idx, (name, value)) in declarations.enumerate() {
+        if minify_css && idx > 0 {
+            css.push(';');
+        }
+        css.push_str(name);
+        css.push_str(if minify_css { ":" } else { ": " });
+        css.push_str(value.trim());
+        if !minify_css {
+            css.push_str("; ");
+        }
+    }
+    css.push('}');
+}
+
+/// Full URLs of the document's linked stylesheets, without duplicates, in document order.
+fn linked_stylesheets<'d>(document: &'d Document, base_url: Option<&Url>) -> Vec<Cow<'d, str>> {
+    let mut urls: Vec<Cow<'d, str>> = Vec::new();
+    for href in document.stylesheets() {
+        let url = get_full_url(href, base_url);
+        if !urls.contains(&url) {
+            urls.push(url);
+        }
+    }
+    urls
+}
+
+/// Resolve an `@import` URL relative to the importing stylesheet.
+fn get_import_url(href: &str, location: Option<&str>, base_url: Option<&Url>) -> String {
+    let Some(location) = location else {
+        return get_full_url(href, base_url).into_owned();
+    };
+    if Url::parse(href).is_ok() {
+        return href.to_string();
+    }
+    if let Ok(location) = Url::parse(location) {
+        // E.g. stylesheets from `data:` URLs import relative to the document
+        if location.cannot_be_a_base() {
+            return get_full_url(href, base_url).into_owned();
         }
-        // If it is not a valid URL and there is no base URL specified, we assume a local path
-        Cow::Borrowed(href)
+        if let Ok(url) = location.join(href) {
+            return url.into();
+        }
+    }
+    // A local path
+    match std::path::Path::new(location).parent() {
+        Some(directory) => directory.join(href).to_string_lossy().into_owned(),
+        None => href.to_string(),
     }
 }
 
+fn get_full_url<'u>(href: &'u str, base_url: Option<&Url>) -> Cow<'u, str> {
+    // Valid absolute URL
+    if Url::parse(href).is_ok() {
+        return Cow::Borrowed(href);
+    }
+    if let Some(base_url) = base_url {
+        // Use the same scheme as the base URL
+        if href.starts_with("//") {
+            return Cow::Owned(format!("{}:{}", base_url.scheme(), href));
+        }
+        // Not a URL, then it is a relative URL
+        if let Ok(new_url) = base_url.join(href) {
+            return Cow::Owned(new_url.into());
+        }
+    }
+    // If it is not a valid URL and there is no base URL specified, we assume a local path
+    Cow::Borrowed(href)
+}
+
 impl Default for CSSInliner<'_> {
     #[inline]
     fn default() -> Self {
@@ -997,4 +2330,13 @@ mod tests {
         assert_send::<CSSInliner<'_>>();
         assert_send::<InlineOptions<'_>>();
     }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_inline_async_send() {
+        fn assert_send<T: Send>(_: &T) {}
+        let inliner = CSSInliner::default();
+        assert_send(&inliner.inline_async(""));
+        assert_send(&inliner.inline_fragment_async("", ""));
+    }
 }
@@ -7,7 +7,7 @@ fn main() {
 #[cfg(feature = "cli")]
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     use core::fmt;
-    use css_inline::{CSSInliner, DefaultStylesheetResolver, InlineOptions};
+    use css_inline::{AttributeMapping, CSSInliner, InlineOptions};
     use rayon::prelude::*;
     use std::{
         borrow::Cow,
@@ -61,10 +61,21 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         load_remote_stylesheets: bool,
         #[cfg(feature = "stylesheet-cache")]
         cache_size: Option<usize>,
+        #[cfg(feature = "http")]
+        http: HttpArgs,
         minify_css: bool,
         remove_inlined_selectors: bool,
         apply_width_attributes: bool,
         apply_height_attributes: bool,
+        attribute_mappings: Vec<AttributeMapping>,
+        strict: bool,
+        resolve_css_variables: bool,
+        follow_imports: bool,
+        absolutize_urls: bool,
+        xhtml: bool,
+        remove_conditional_comments: bool,
+        keep_dynamic_rules: bool,
+        materialize_pseudo_elements: bool,
     }
 
     impl Default for ParsedArgs {
@@ -84,14 +95,102 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 load_remote_stylesheets: false,
                 #[cfg(feature = "stylesheet-cache")]
                 cache_size: None,
+                #[cfg(feature = "http")]
+                http: HttpArgs::default(),
                 minify_css: false,
                 remove_inlined_selectors: false,
                 apply_width_attributes: false,
                 apply_height_attributes: false,
+                attribute_mappings: Vec::new(),
+                strict: false,
+                resolve_css_variables: false,
+                follow_imports: false,
+                absolutize_urls: false,
+                xhtml: false,
+                remove_conditional_comments: false,
+                keep_dynamic_rules: false,
+                materialize_pseudo_elements: false,
             }
         }
     }
 
+    /// Limits of HTTP requests for remote stylesheets.
+    #[cfg(feature = "http")]
+    #[derive(Default)]
+    struct HttpArgs {
+        connect_timeout: Option<u64>,
+        read_timeout: Option<u64>,
+        max_body_size: Option<u64>,
+        headers: Vec<(String, String)>,
+        user_agent: Option<String>,
+        max_redirects: Option<usize>,
+        retries: Option<u32>,
+        retry_backoff: Option<u64>,
+    }
+
+    #[cfg(feature = "http")]
+    impl HttpArgs {
+        /// Whether any of the `--http-*` flags is given.
+        fn is_set(&self) -> bool {
+            self.connect_timeout.is_some()
+                || self.read_timeout.is_some()
+                || self.max_body_size.is_some()
+                || !self.headers.is_empty()
+                || self.user_agent.is_some()
+                || self.max_redirects.is_some()
+                || self.retries.is_some()
+                || self.retry_backoff.is_some()
+        }
+    }
+
+    #[cfg(feature = "http")]
+    fn build_http_resolver(
+        args: HttpArgs,
+    ) -> Result<css_inline::HttpStylesheetResolver, css_inline::InlineError> {
+        use std::time::Duration;
+
+        let mut builder = css_inline::HttpStylesheetResolver::builder()
+            .connect_timeout(args.connect_timeout.map(Duration::from_secs))
+            .max_body_size(args.max_body_size)
+            .user_agent(args.user_agent);
+        if let Some(timeout) = args.read_timeout {
+            builder = builder.read_timeout(Some(Duration::from_secs(timeout)));
+        }
+        for (name, value) in args.headers {
+            builder = builder.header(name, value);
+        }
+        if let Some(max_redirects) = args.max_redirects {
+            builder = builder.max_redirects(max_redirects);
+        }
+        if let Some(retries) = args.retries {
+            builder = builder.retries(retries);
+        }
+        if let Some(backoff) = args.retry_backoff {
+            builder = builder.retry_backoff(Duration::from_millis(backoff));
+        }
+        builder.build()
+    }
+
+    #[cfg(feature = "http")]
+    fn is_http_flag(flag: &str) -> bool {
+        matches!(
+            flag,
+            "http-connect-timeout"
+                | "http-read-timeout"
+                | "http-max-body-size"
+                | "http-header"
+                | "http-user-agent"
+                | "http-max-redirects"
+                | "http-retries"
+                | "http-retry-backoff"
+        )
+    }
+
+    #[cfg(not(feature = "http"))]
+    fn is_http_flag(_: &str) -> bool {
+        false
+    }
+
     #[cfg(feature = "stylesheet-cache")]
     macro_rules! if_cfg_feature_stylesheet_cache {
         ($val:expr) => {
@@ -115,8 +214,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 | "extra-css"
                 | "extra-css-file"
                 | "output-filename-prefix"
+                | "attribute-mappings"
                 | if_cfg_feature_stylesheet_cache!("cache-size")
-        )
+        ) || is_http_flag(flag)
     }
 
     fn parse_value<T>(value: &str, flag: &str) -> Result<T, ParseError>
@@ -129,6 +229,34 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         })
     }
 
+    fn parse_attribute_mappings(
+        value: &str,
+        flag: &str,
+    ) -> Result<Vec<AttributeMapping>, ParseError> {
+        let mut mappings = Vec::new();
+        for attribute in value.split(',').map(str::trim) {
+            match attribute {
+                "all" => mappings.extend(AttributeMapping::ALL),
+                "width" => mappings.push(AttributeMapping::Width),
+                "height" => mappings.push(AttributeMapping::Height),
+                "bgcolor" => mappings.push(AttributeMapping::BackgroundColor),
+                "align" => mappings.push(AttributeMapping::TextAlign),
+                "valign" => mappings.push(AttributeMapping::VerticalAlign),
+                "cellspacing" => mappings.push(AttributeMapping::BorderSpacing),
+                "cellpadding" => mappings.push(AttributeMapping::Padding),
+                "border" => mappings.push(AttributeMapping::Border),
+                _ => {
+                    return Err(ParseError {
+                        message: format!(
+                            "Failed to parse value '{value}' for flag '{flag}': unknown attribute '{attribute}'"
+                        ),
+                    })
+                }
+            }
+        }
+        Ok(mappings)
+    }
+
     fn handle_flag_with_value(
         parsed: &mut ParsedArgs,
         flag: &str,
@@ -140,11 +268,44 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             "base-url" => parsed.base_url = Some(value.to_string()),
             "extra-css" => parsed.extra_css = Some(value.to_string()),
             "extra-css-file" => parsed.extra_css_files.push(value.to_string()),
+            "attribute-mappings" => parsed
+                .attribute_mappings
+                .extend(parse_attribute_mappings(value, flag)?),
             "output-filename-prefix" => {
                 parsed.output_filename_prefix = Some(value.to_string().into());
             }
             #[cfg(feature = "stylesheet-cache")]
             "cache-size" => parsed.cache_size = Some(parse_value(value, flag)?),
+            #[cfg(feature = "http")]
+            "http-connect-timeout" => {
+                parsed.http.connect_timeout = Some(parse_value(value, flag)?);
+            }
+            #[cfg(feature = "http")]
+            "http-read-timeout" => parsed.http.read_timeout = Some(parse_value(value, flag)?),
+            #[cfg(feature = "http")]
+            "http-max-body-size" => parsed.http.max_body_size = Some(parse_value(value, flag)?),
+            #[cfg(feature = "http")]
+            "http-header" => {
+                let Some((name, header_value)) = value.split_once(':') else {
+                    return Err(ParseError {
+                        message: format!(
+                            "Failed to parse value '{value}' for flag '{flag}': expected 'Name: value'"
+                        ),
+                    });
+                };
+                parsed
+                    .http
+                    .headers
+                    .push((name.trim().to_string(), header_value.trim().to_string()));
+            }
+            #[cfg(feature = "http")]
+            "http-user-agent" => parsed.http.user_agent = Some(value.to_string()),
+            #[cfg(feature = "http")]
+            "http-max-redirects" => parsed.http.max_redirects = Some(parse_value(value, flag)?),
+            #[cfg(feature = "http")]
+            "http-retries" => parsed.http.retries = Some(parse_value(value, flag)?),
+            #[cfg(feature = "http")]
+            "http-retry-backoff" => parsed.http.retry_backoff = Some(parse_value(value, flag)?),
             _ => {
                 return Err(ParseError {
                     message: format!("Unknown flag: --{flag}"),
@@ -165,6 +326,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             "remove-inlined-selectors" => parsed.remove_inlined_selectors = true,
             "apply-width-attributes" => parsed.apply_width_attributes = true,
             "apply-height-attributes" => parsed.apply_height_attributes = true,
+            "strict" => parsed.strict = true,
+            "resolve-css-variables" => parsed.resolve_css_variables = true,
+            "follow-imports" => parsed.follow_imports = true,
+            "absolutize-urls" => parsed.absolutize_urls = true,
+            "xhtml" => parsed.xhtml = true,
+            "remove-conditional-comments" => parsed.remove_conditional_comments = true,
+            "keep-dynamic-rules" => parsed.keep_dynamic_rules = true,
+            "materialize-pseudo-elements" => parsed.materialize_pseudo_elements = true,
             _ => {
                 return Err(ParseError {
                     message: format!("Unknown flag: {flag}"),
@@ -262,6 +431,40 @@ OPTIONS:
         Apply height HTML attributes from CSS height properties on supported elements
         (table, td, th, img). Useful for email compatibility with clients like Outlook.
 
+    --attribute-mappings <ATTRIBUTES>
+        Comma-separated presentational HTML attributes to generate from CSS on supported
+        elements: bgcolor, align, valign, cellspacing, cellpadding, border, width, height,
+        or all. cellpadding is taken from the padding shared by all cells of a table.
+
+    --strict
+        Fail on unparsable or unsupported selectors, malformed declarations and invalid rules
+        instead of skipping them.
+
+    --resolve-css-variables
+        Replace var() references with values of CSS custom properties and remove custom
+        property declarations.
+
+    --follow-imports
+        Load stylesheets referenced by @import rules in style tags and linked stylesheets.
+
+    --absolutize-urls
+        Rewrite relative URLs in href, src, srcset, background and poster attributes and in
+        CSS url() references using the base URL or the document's <base> element.
+
+    --xhtml
+        Serialize the output as XHTML: self-close void elements and give boolean attributes
+        values.
+
+    --remove-conditional-comments
+        Remove conditional comments like <!--[if mso]>. Otherwise, CSS is inlined into
+        the HTML inside them.
+
+    --keep-dynamic-rules
+        Keep rules with dynamic pseudo-classes, like :hover, in a "style" tag in "head".
+
+    --materialize-pseudo-elements
+        Materialize ::before and ::after pseudo-elements as "span" elements.
+
     --base-url
         Used for loading external stylesheets via relative URLs.
 
@@ -271,6 +474,33 @@ OPTIONS:
     --cache-size
         Set the cache size for remote stylesheets.
 
+    --http-connect-timeout <SECONDS>
+        Timeout for connecting to servers of remote stylesheets. Not limited by default.
+
+    --http-read-timeout <SECONDS>
+        Timeout for receiving response headers and for each read of the response body.
+        Defaults to 30 seconds.
+
+    --http-max-body-size <BYTES>
+        Fail on remote stylesheets larger than this size. Not limited by default.
+
+    --http-header <NAME: VALUE>
+        Send a header with requests for remote stylesheets, e.g. "Authorization: Bearer token".
+        Can be used multiple times.
+
+    --http-user-agent <VALUE>
+        Value of the User-Agent header for requests for remote stylesheets.
+
+    --http-max-redirects <NUMBER>
+        Maximum number of redirects to follow. Zero rejects any redirect. Defaults to 10.
+
+    --http-retries <NUMBER>
+        Retry requests that failed with a timeout, a connection error, a 429 or a 5xx status
+        code. No retries by default.
+
+    --http-retry-backoff <MILLISECONDS>
+        Delay before the first retry, doubled for each next one. Defaults to 100.
+
     --extra-css
         Additional CSS to inline.
 
@@ -358,6 +588,21 @@ OPTIONS:
         } else {
             None
         };
+        // The default resolver keeps its behavior unless HTTP requests are configured
+        #[cfg(feature = "http")]
+        let resolver: Arc<dyn css_inline::StylesheetResolver> = if args.http.is_set() {
+            match build_http_resolver(args.http) {
+                Ok(resolver) => Arc::new(resolver),
+                Err(error) => {
+                    format_error(None, error);
+                    std::process::exit(1);
+                }
+            }
+        } else {
+            Arc::new(css_inline::DefaultStylesheetResolver)
+        };
+        #[cfg(not(feature = "http"))]
+        let resolver = Arc::new(css_inline::DefaultStylesheetResolver);
         let extra_css = match combine_extra_css(args.extra_css, args.extra_css_files) {
             Ok(css) => css,
             Err(error) => {
@@ -377,10 +622,28 @@ OPTIONS:
             cache,
             extra_css: extra_css.as_deref().map(Cow::Borrowed),
             preallocate_node_capacity: 32,
-            resolver: Arc::new(DefaultStylesheetResolver),
+            resolver,
+            resolver_policy: None,
+            #[cfg(feature = "async")]
+            async_resolver: Arc::new(css_inline::DefaultStylesheetResolver),
             remove_inlined_selectors: args.remove_inlined_selectors,
             apply_width_attributes: args.apply_width_attributes,
             apply_height_attributes: args.apply_height_attributes,
+            attribute_mappings: args.attribute_mappings,
+            strict: args.strict,
+            compiled_stylesheet: None,
+            resolve_css_variables: args.resolve_css_variables,
+            media_environment: None,
+            follow_imports: args.follow_imports,
+            absolutize_urls: args.absolutize_urls,
+            prefer_document_base_url: false,
+            remove_inapplicable_stylesheets: false,
+            xhtml: args.xhtml,
+            template_delimiters: Vec::new(),
+            remove_conditional_comments: args.remove_conditional_comments,
+            propagate_properties: None,
+            keep_dynamic_rules: args.keep_dynamic_rules,
+            materialize_pseudo_elements: args.materialize_pseudo_elements,
         };
         let inliner = CSSInliner::new(options);
         if args.files.is_empty() {
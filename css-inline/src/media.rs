@@ -0,0 +1,439 @@
+//! Evaluation of media queries against a configured environment.
+use cssparser::{match_ignore_ascii_case, Delimiter, ParseError, Parser, ParserInput, Token};
+
+/// A media type from a media query, e.g. `screen` in `@media screen and (max-width: 600px)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MediaType {
+    /// Devices with screens.
+    #[default]
+    Screen,
+    /// Printed output & print previews.
+    Print,
+}
+
+/// A value of the `prefers-color-scheme` media feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorScheme {
+    /// Light color scheme.
+    #[default]
+    Light,
+    /// Dark color scheme.
+    Dark,
+}
+
+/// The target environment for evaluating `@media` rules.
+///
+/// ```rust
+/// use css_inline::{CSSInliner, MediaEnvironment};
+///
+/// // A mobile-first variant for clients that ignore `<style>` tags
+/// let inliner = CSSInliner::options()
+///     .media_environment(Some(MediaEnvironment::new(375, 667)))
+///     .build();
+/// let inlined = inliner.inline(r#"<html><head><style>
+/// h1 { color: blue; }
+/// @media (max-width: 600px) { h1 { color: red; } }
+/// </style></head><body><h1>Hello</h1></body></html>"#);
+/// assert_eq!(
+///     inlined.expect("Inlining failed"),
+///     r#"<html><head></head><body><h1 style="color: red;">Hello</h1></body></html>"#
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MediaEnvironment {
+    /// Viewport width in CSS pixels.
+    pub width: u32,
+    /// Viewport height in CSS pixels.
+    pub height: u32,
+    /// Media type.
+    pub media_type: MediaType,
+    /// Value of the `prefers-color-scheme` media feature.
+    pub prefers_color_scheme: ColorScheme,
+}
+
+impl MediaEnvironment {
+    /// A screen with the given viewport size and the light color scheme.
+    #[must_use]
+    pub fn new(width: u32, height: u32) -> MediaEnvironment {
+        MediaEnvironment {
+            width,
+            height,
+            media_type: MediaType::Screen,
+            prefers_color_scheme: ColorScheme::Light,
+        }
+    }
+
+    /// Set the media type.
+    #[must_use]
+    pub fn media_type(mut self, media_type: MediaType) -> Self {
+        self.media_type = media_type;
+        self
+    }
+
+    /// Set the value of the `prefers-color-scheme` media feature.
+    #[must_use]
+    pub fn prefers_color_scheme(mut self, prefers_color_scheme: ColorScheme) -> Self {
+        self.prefers_color_scheme = prefers_color_scheme;
+        self
+    }
+
+    /// Whether a comma-separated list of media queries matches this environment.
+    ///
+    /// An empty list matches any environment, while invalid queries never match.
+    pub(crate) fn matches(&self, media_query_list: &str) -> bool {
+        let mut input = ParserInput::new(media_query_list);
+        let mut parser = Parser::new(&mut input);
+        if parser.is_exhausted() {
+            return true;
+        }
+        let mut matches = false;
+        loop {
+            matches |= parser
+                .parse_until_before(Delimiter::Comma, |input| self.media_query(input))
+                .unwrap_or(false);
+            if parser.next().is_err() {
+                return matches;
+            }
+        }
+    }
+
+    fn media_query<'i>(&self, input: &mut Parser<'i, '_>) -> Result<bool, ParseError<'i, ()>> {
+        if let Ok(matches) = input.try_parse(|input| self.condition(input, true)) {
+            return Ok(matches);
+        }
+        let negated = input
+            .try_parse(|input| input.expect_ident_matching("not"))
+            .is_ok();
+        if !negated {
+            let _ = input.try_parse(|input| input.expect_ident_matching("only"));
+        }
+        let location = input.current_source_location();
+        let media_type = input.expect_ident()?.clone();
+        let mut matches = match_ignore_ascii_case! { &media_type,
+            "all" => true,
+            "screen" => self.media_type == MediaType::Screen,
+            "print" => self.media_type == MediaType::Print,
+            "not" | "only" | "and" | "or" | "layer" => {
+                return Err(location.new_custom_error(()));
+            },
+            // Other media types are deprecated and never match
+            _ => false,
+        };
+        if input
+            .try_parse(|input| input.expect_ident_matching("and"))
+            .is_ok()
+        {
+            // Evaluate the condition anyway, so invalid ones make the whole query fail
+            matches &= self.condition(input, false)?;
+        }
+        Ok(matches != negated)
+    }
+
+    /// `<media-condition>` or `<media-condition-without-or>`.
+    fn condition<'i>(
+        &self,
+        input: &mut Parser<'i, '_>,
+        allow_or: bool,
+    ) -> Result<bool, ParseError<'i, ()>> {
+        if input
+            .try_parse(|input| input.expect_ident_matching("not"))
+            .is_ok()
+        {
+            return Ok(!self.in_parens(input)?);
+        }
+        let mut matches = self.in_parens(input)?;
+        let mut combinator = None;
+        while let Ok(is_and) = input.try_parse(|input| {
+            let location = input.current_source_location();
+            let ident = input.expect_ident()?.clone();
+            match_ignore_ascii_case! { &ident,
+                "and" => Ok(true),
+                "or" if allow_or => Ok(false),
+                _ => Err(location.new_custom_error::<(), ()>(())),
+            }
+        }) {
+            // `and` & `or` can not be mixed without parentheses
+            if combinator.is_some_and(|combinator| combinator != is_and) {
+                return Err(input.new_custom_error(()));
+            }
+            combinator = Some(is_and);
+            let next = self.in_parens(input)?;
+            matches = if is_and {
+                matches && next
+            } else {
+                matches || next
+            };
+        }
+        Ok(matches)
+    }
+
+    /// `<media-in-parens>`
+    fn in_parens<'i>(&self, input: &mut Parser<'i, '_>) -> Result<bool, ParseError<'i, ()>> {
+        input.expect_parenthesis_block()?;
+        input.parse_nested_block(|input| {
+            if let Ok(matches) = input.try_parse(|input| self.condition(input, true)) {
+                return Ok(matches);
+            }
+            if let Ok(matches) = input.try_parse(|input| self.feature(input)) {
+                return Ok(matches);
+            }
+            // `<general-enclosed>`, e.g. an unknown feature, never matches
+            while input.next().is_ok() {}
+            Ok(false)
+        })
+    }
+
+    /// `<media-feature>` in the plain, boolean or range form.
+    fn feature<'i>(&self, input: &mut Parser<'i, '_>) -> Result<bool, ParseError<'i, ()>> {
+        let matches = match parse_value(input)? {
+            Value::Ident(name) if input.is_exhausted() => self.boolean_feature(&name),
+            Value::Ident(name) if input.try_parse(Parser::expect_colon).is_ok() => {
+                let value = parse_value(input)?;
+                let (name, comparison) = if let Some(name) = strip_prefix(&name, "min-") {
+                    (name, Comparison::GreaterOrEqual)
+                } else if let Some(name) = strip_prefix(&name, "max-") {
+                    (name, Comparison::LessOrEqual)
+                } else {
+                    (&*name, Comparison::Equal)
+                };
+                self.compare(name, comparison, &value)
+            }
+            Value::Ident(name) => {
+                let comparison = parse_comparison(input)?;
+                let value = parse_value(input)?;
+                self.compare(&name, comparison, &value)
+            }
+            value @ Value::Number(_) => {
+                let comparison = parse_comparison(input)?;
+                let Value::Ident(name) = parse_value(input)? else {
+                    return Err(input.new_custom_error(()));
+                };
+                let mut matches = self.compare(&name, comparison.reverse(), &value);
+                if let Ok(comparison) = input.try_parse(parse_comparison) {
+                    let value = parse_value(input)?;
+                    matches &= self.compare(&name, comparison, &value);
+                }
+                matches
+            }
+        };
+        input.expect_exhausted()?;
+        Ok(matches)
+    }
+
+    fn feature_value(&self, name: &str) -> Option<FeatureValue> {
+        let width = f64::from(self.width);
+        let height = f64::from(self.height);
+        Some(match_ignore_ascii_case! { name,
+            "width" | "device-width" => FeatureValue::Number(width),
+            "height" | "device-height" => FeatureValue::Number(height),
+            "aspect-ratio" | "device-aspect-ratio" => FeatureValue::Number(width / height),
+            "orientation" => FeatureValue::Ident(if self.height >= self.width {
+                "portrait"
+            } else {
+                "landscape"
+            }),
+            "prefers-color-scheme" => FeatureValue::Ident(match self.prefers_color_scheme {
+                ColorScheme::Light => "light",
+                ColorScheme::Dark => "dark",
+            }),
+            _ => return None,
+        })
+    }
+
+    fn boolean_feature(&self, name: &str) -> bool {
+        match self.feature_value(name) {
+            Some(FeatureValue::Number(value)) => value != 0.0,
+            Some(FeatureValue::Ident(_)) => true,
+            None => false,
+        }
+    }
+
+    fn compare(&self, name: &str, comparison: Comparison, expected: &Value<'_>) -> bool {
+        match (self.feature_value(name), expected) {
+            (Some(FeatureValue::Number(actual)), Value::Number(expected)) => {
+                comparison.evaluate(actual, *expected)
+            }
+            (Some(FeatureValue::Ident(actual)), Value::Ident(expected)) => {
+                comparison == Comparison::Equal && actual.eq_ignore_ascii_case(expected)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Default for MediaEnvironment {
+    /// A 1024x768 screen with the light color scheme.
+    fn default() -> Self {
+        MediaEnvironment::new(1024, 768)
+    }
+}
+
+fn strip_prefix<'a>(name: &'a str, prefix: &str) -> Option<&'a str> {
+    if name.len() > prefix.len()
+        && name.is_char_boundary(prefix.len())
+        && name[..prefix.len()].eq_ignore_ascii_case(prefix)
+    {
+        Some(&name[prefix.len()..])
+    } else {
+        None
+    }
+}
+
+enum FeatureValue {
+    Number(f64),
+    Ident(&'static str),
+}
+
+/// A value in a media feature. Lengths are in CSS pixels & ratios are divided.
+enum Value<'i> {
+    Number(f64),
+    Ident(cssparser::CowRcStr<'i>),
+}
+
+fn parse_value<'i>(input: &mut Parser<'i, '_>) -> Result<Value<'i>, ParseError<'i, ()>> {
+    let location = input.current_source_location();
+    match input.next()?.clone() {
+        Token::Number { value, .. } => {
+            let value = f64::from(value);
+            if input.try_parse(|input| input.expect_delim('/')).is_ok() {
+                let denominator = f64::from(input.expect_number()?);
+                Ok(Value::Number(value / denominator))
+            } else {
+                Ok(Value::Number(value))
+            }
+        }
+        Token::Dimension { value, unit, .. } => {
+            let pixels = match_ignore_ascii_case! { &unit,
+                "px" => 1.0,
+                // Relative to the initial font size
+                "em" | "rem" => 16.0,
+                "pt" => 96.0 / 72.0,
+                "pc" => 16.0,
+                "in" => 96.0,
+                "cm" => 96.0 / 2.54,
+                "mm" => 96.0 / 25.4,
+                "q" => 96.0 / 101.6,
+                _ => return Err(location.new_custom_error(())),
+            };
+            Ok(Value::Number(f64::from(value) * pixels))
+        }
+        Token::Ident(ident) => Ok(Value::Ident(ident)),
+        token => Err(location.new_unexpected_token_error(token)),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    GreaterOrEqual,
+    Greater,
+}
+
+impl Comparison {
+    /// The same comparison with swapped operands.
+    fn reverse(self) -> Comparison {
+        match self {
+            Comparison::Less => Comparison::Greater,
+            Comparison::LessOrEqual => Comparison::GreaterOrEqual,
+            Comparison::Equal => Comparison::Equal,
+            Comparison::GreaterOrEqual => Comparison::LessOrEqual,
+            Comparison::Greater => Comparison::Less,
+        }
+    }
+
+    fn evaluate(self, left: f64, right: f64) -> bool {
+        match self {
+            Comparison::Less => left < right,
+            Comparison::LessOrEqual => left <= right,
+            Comparison::Equal => (left - right).abs() < f64::EPSILON,
+            Comparison::GreaterOrEqual => left >= right,
+            Comparison::Greater => left > right,
+        }
+    }
+}
+
+fn parse_comparison<'i>(input: &mut Parser<'i, '_>) -> Result<Comparison, ParseError<'i, ()>> {
+    let location = input.current_source_location();
+    let comparison = match input.next()? {
+        Token::Delim('=') => return Ok(Comparison::Equal),
+        Token::Delim('<') => Comparison::Less,
+        Token::Delim('>') => Comparison::Greater,
+        token => return Err(location.new_unexpected_token_error(token.clone())),
+    };
+    // No whitespace is allowed between `<` or `>` and `=`
+    if input
+        .try_parse(|input| match input.next_including_whitespace() {
+            Ok(Token::Delim('=')) => Ok(()),
+            _ => Err(()),
+        })
+        .is_ok()
+    {
+        Ok(match comparison {
+            Comparison::Less => Comparison::LessOrEqual,
+            _ => Comparison::GreaterOrEqual,
+        })
+    } else {
+        Ok(comparison)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ColorScheme, MediaEnvironment, MediaType};
+    use test_case::test_case;
+
+    #[test_case("", true)]
+    #[test_case("all", true)]
+    #[test_case("screen", true)]
+    #[test_case("SCREEN", true)]
+    #[test_case("only screen", true)]
+    #[test_case("print", false)]
+    #[test_case("not print", true)]
+    #[test_case("tv", false)]
+    #[test_case("print, screen", true)]
+    #[test_case("(max-width: 600px)", true)]
+    #[test_case("(max-width: 320px)", false)]
+    #[test_case("(min-width: 375px)", true)]
+    #[test_case("(min-width: 24em)", false)]
+    #[test_case("(width: 375px)", true)]
+    #[test_case("screen and (max-width: 600px)", true)]
+    #[test_case("only screen and (max-width: 600px) and (orientation: portrait)", true)]
+    #[test_case("screen and (orientation: landscape)", false)]
+    #[test_case("not screen and (max-width: 600px)", false)]
+    #[test_case("print and (max-width: 600px)", false)]
+    #[test_case("(min-width: 700px) or (max-height: 700px)", true)]
+    #[test_case("(min-width: 700px) and (max-height: 700px)", false)]
+    #[test_case("not (min-width: 700px)", true)]
+    #[test_case("(not (min-width: 700px)) and (color)", false)]
+    #[test_case("(width)", true)]
+    #[test_case("(width <= 600px)", true)]
+    #[test_case("(width > 375px)", false)]
+    #[test_case("(600px >= width)", true)]
+    #[test_case("(320px < width < 400px)", true)]
+    #[test_case("(400px < width < 800px)", false)]
+    #[test_case("(max-aspect-ratio: 1/1)", true)]
+    #[test_case("(prefers-color-scheme: light)", true)]
+    #[test_case("(prefers-color-scheme: dark)", false)]
+    #[test_case("(unknown-feature: 1px)", false)]
+    #[test_case("not (unknown-feature: 1px)", true)]
+    #[test_case("(max-width: 600px) or", false)]
+    #[test_case("(min-width: 1px) and (max-width: 1px) or (width)", false)]
+    #[test_case("screen and", false)]
+    #[test_case("invalid query 42, screen", true)]
+    fn test_matches(query: &str, expected: bool) {
+        let environment = MediaEnvironment::new(375, 667);
+        assert_eq!(environment.matches(query), expected);
+    }
+
+    #[test]
+    fn custom_environment() {
+        let environment = MediaEnvironment::new(800, 600)
+            .media_type(MediaType::Print)
+            .prefers_color_scheme(ColorScheme::Dark);
+        assert!(environment.matches("print and (orientation: landscape)"));
+        assert!(environment.matches("(prefers-color-scheme: dark)"));
+        assert!(!environment.matches("screen"));
+    }
+}
@@ -1,11 +1,27 @@
-use cssparser::ParserState;
+use crate::media::MediaEnvironment;
+use cssparser::{BasicParseErrorKind, Delimiter, ParseErrorKind, ParserState, Token};
+use smallvec::SmallVec;
+use std::ops::Range;
 
-pub(crate) struct CSSRuleListParser<'d, 'i>(&'d mut Vec<Declaration<'i>>);
+pub(crate) struct CSSRuleListParser<'d, 'i> {
+    declarations: &'d mut Vec<Declaration<'i>>,
+    invalid: &'d mut Vec<InvalidCss<'i>>,
+    /// `@media` rules matching this environment are flattened into regular rules.
+    media_environment: Option<&'d MediaEnvironment>,
+}
 
 impl<'d, 'i> CSSRuleListParser<'d, 'i> {
     #[inline]
-    pub(crate) fn new(declarations: &'d mut Vec<Declaration<'i>>) -> CSSRuleListParser<'d, 'i> {
-        CSSRuleListParser(declarations)
+    pub(crate) fn new(
+        declarations: &'d mut Vec<Declaration<'i>>,
+        invalid: &'d mut Vec<InvalidCss<'i>>,
+        media_environment: Option<&'d MediaEnvironment>,
+    ) -> CSSRuleListParser<'d, 'i> {
+        CSSRuleListParser {
+            declarations,
+            invalid,
+            media_environment,
+        }
     }
 }
 
@@ -14,6 +30,51 @@ pub(crate) struct CSSDeclarationListParser;
 pub(crate) type Name<'i> = cssparser::CowRcStr<'i>;
 pub(crate) type Declaration<'i> = (Name<'i>, &'i str);
 pub(crate) type QualifiedRule<'i> = (&'i str, (usize, usize));
+/// Rules produced by a top-level rule: a single one for qualified rules, none or many for
+/// at-rules.
+pub(crate) type Rules<'i> = SmallVec<[QualifiedRule<'i>; 1]>;
+
+/// What kind of CSS construct was skipped by the parser.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum InvalidCssKind {
+    /// An at-rule that can not be inlined, e.g. `@media` or `@font-face`.
+    AtRule,
+    /// A rule that could not be parsed.
+    Rule,
+    /// A declaration that could not be parsed.
+    Declaration,
+}
+
+/// A piece of CSS that was skipped during parsing.
+#[derive(Debug)]
+pub(crate) struct InvalidCss<'i> {
+    pub(crate) kind: InvalidCssKind,
+    /// The skipped source text.
+    pub(crate) text: &'i str,
+}
+
+impl<'i> InvalidCss<'i> {
+    pub(crate) fn new<E>(
+        error: &cssparser::ParseError<'i, E>,
+        text: &'i str,
+        otherwise: InvalidCssKind,
+    ) -> InvalidCss<'i> {
+        let kind = if matches!(
+            error.kind,
+            ParseErrorKind::Basic(
+                BasicParseErrorKind::AtRuleInvalid(_) | BasicParseErrorKind::AtRuleBodyInvalid
+            )
+        ) {
+            InvalidCssKind::AtRule
+        } else {
+            otherwise
+        };
+        InvalidCss {
+            kind,
+            text: text.trim(),
+        }
+    }
+}
 
 fn exhaust<'i>(input: &mut cssparser::Parser<'i, '_>) -> &'i str {
     let start = input.position();
@@ -26,7 +87,7 @@ fn exhaust<'i>(input: &mut cssparser::Parser<'i, '_>) -> &'i str {
 /// Usually these rules are a selector + list of declarations: `p { color: blue; font-size: 2px }`
 impl<'i> cssparser::QualifiedRuleParser<'i> for CSSRuleListParser<'_, 'i> {
     type Prelude = &'i str;
-    type QualifiedRule = QualifiedRule<'i>;
+    type QualifiedRule = Rules<'i>;
     type Error = ();
 
     fn parse_prelude<'t>(
@@ -43,7 +104,10 @@ impl<'i> cssparser::QualifiedRuleParser<'i> for CSSRuleListParser<'_, 'i> {
         _: &ParserState,
         input: &mut cssparser::Parser<'i, 't>,
     ) -> Result<Self::QualifiedRule, cssparser::ParseError<'i, Self::Error>> {
-        Ok((prelude, parse_declarations_into(input, self.0)))
+        Ok(smallvec::smallvec![(
+            prelude,
+            parse_declarations_into(input, self.declarations, self.invalid),
+        )])
     }
 }
 
@@ -62,10 +126,35 @@ impl<'i> cssparser::DeclarationParser<'i> for CSSDeclarationListParser {
     }
 }
 
+/// At-rules can not be inlined and are rejected, except for `@media` rules matching the
+/// configured environment.
 impl<'i> cssparser::AtRuleParser<'i> for CSSRuleListParser<'_, 'i> {
-    type Prelude = &'i str;
-    type AtRule = QualifiedRule<'i>;
+    type Prelude = ();
+    type AtRule = Rules<'i>;
     type Error = ();
+
+    fn parse_prelude<'t>(
+        &mut self,
+        name: cssparser::CowRcStr<'i>,
+        input: &mut cssparser::Parser<'i, 't>,
+    ) -> Result<Self::Prelude, cssparser::ParseError<'i, Self::Error>> {
+        if is_matching_media(self.media_environment, &name, input) {
+            Ok(())
+        } else {
+            Err(input.new_error(BasicParseErrorKind::AtRuleInvalid(name)))
+        }
+    }
+
+    fn parse_block<'t>(
+        &mut self,
+        (): Self::Prelude,
+        _start: &ParserState,
+        input: &mut cssparser::Parser<'i, 't>,
+    ) -> Result<Self::AtRule, cssparser::ParseError<'i, Self::Error>> {
+        let (rules, mut invalid) = parse_nested_rules(input, self);
+        self.invalid.append(&mut invalid);
+        Ok(rules)
+    }
 }
 
 /// Parsing for at-rules, e.g: `@charset "utf-8";`
@@ -94,25 +183,32 @@ impl<'i> cssparser::QualifiedRuleParser<'i> for CSSDeclarationListParser {
 
 pub(crate) struct AtRuleFilteringParser<'d, 'i, 'o> {
     declarations: &'d mut Vec<Declaration<'i>>,
+    invalid: &'d mut Vec<InvalidCss<'i>>,
     at_rules: &'o mut String,
+    /// `@media` rules matching this environment are flattened into regular rules.
+    media_environment: Option<&'d MediaEnvironment>,
 }
 
 impl<'d, 'i, 'o> AtRuleFilteringParser<'d, 'i, 'o> {
     #[inline]
     pub(crate) fn new(
         declarations: &'d mut Vec<Declaration<'i>>,
+        invalid: &'d mut Vec<InvalidCss<'i>>,
         at_rules: &'o mut String,
+        media_environment: Option<&'d MediaEnvironment>,
     ) -> AtRuleFilteringParser<'d, 'i, 'o> {
         AtRuleFilteringParser {
             declarations,
+            invalid,
             at_rules,
+            media_environment,
         }
     }
 }
 
 impl<'i> cssparser::QualifiedRuleParser<'i> for AtRuleFilteringParser<'_, 'i, '_> {
     type Prelude = &'i str;
-    type QualifiedRule = QualifiedRule<'i>;
+    type QualifiedRule = Rules<'i>;
     type Error = ();
 
     fn parse_prelude<'t>(
@@ -128,13 +224,19 @@ impl<'i> cssparser::QualifiedRuleParser<'i> for AtRuleFilteringParser<'_, 'i, '_
         _: &ParserState,
         input: &mut cssparser::Parser<'i, 't>,
     ) -> Result<Self::QualifiedRule, cssparser::ParseError<'i, Self::Error>> {
-        Ok((prelude, parse_declarations_into(input, self.declarations)))
+        Ok(smallvec::smallvec![(
+            prelude,
+            parse_declarations_into(input, self.declarations, self.invalid),
+        )])
     }
 }
 
 impl<'i> cssparser::AtRuleParser<'i> for AtRuleFilteringParser<'_, 'i, '_> {
-    type Prelude = &'i str;
-    type AtRule = QualifiedRule<'i>;
+    /// `None` for `@media` rules that match the configured environment.
+    type Prelude = Option<&'i str>;
+    /// At-rules are written to the output buffer and are not inlined, except for matching
+    /// `@media` rules.
+    type AtRule = Rules<'i>;
     type Error = ();
 
     fn parse_prelude<'t>(
@@ -142,9 +244,14 @@ impl<'i> cssparser::AtRuleParser<'i> for AtRuleFilteringParser<'_, 'i, '_> {
         name: cssparser::CowRcStr<'i>,
         input: &mut cssparser::Parser<'i, 't>,
     ) -> Result<Self::Prelude, cssparser::ParseError<'i, Self::Error>> {
+        let start = input.state();
+        if is_matching_media(self.media_environment, &name, input) {
+            return Ok(None);
+        }
+        input.reset(&start);
         self.at_rules.push('@');
         self.at_rules.push_str(&name);
-        Ok(exhaust(input))
+        Ok(Some(exhaust(input)))
     }
 
     fn parse_block<'t>(
@@ -153,13 +260,17 @@ impl<'i> cssparser::AtRuleParser<'i> for AtRuleFilteringParser<'_, 'i, '_> {
         _start: &ParserState,
         input: &mut cssparser::Parser<'i, 't>,
     ) -> Result<Self::AtRule, cssparser::ParseError<'i, Self::Error>> {
-        let start = self.at_rules.len();
+        let Some(prelude) = prelude else {
+            let (rules, mut invalid) = parse_nested_rules(input, self);
+            self.invalid.append(&mut invalid);
+            return Ok(rules);
+        };
         self.at_rules.push_str(prelude);
         self.at_rules.push('{');
         self.at_rules.push_str(exhaust(input));
         self.at_rules.push('}');
         self.at_rules.push(' ');
-        Ok((prelude, (start, self.at_rules.len())))
+        Ok(Rules::new())
     }
 
     fn rule_without_block(
@@ -170,23 +281,184 @@ impl<'i> cssparser::AtRuleParser<'i> for AtRuleFilteringParser<'_, 'i, '_> {
         // `parse_prelude` has already written `@` + the rule name; without this
         // the name would fuse with the following rule (e.g. `@import@media ...`),
         // producing a stylesheet browsers reject entirely.
-        let start = self.at_rules.len();
-        self.at_rules.push_str(prelude);
-        self.at_rules.push(';');
-        self.at_rules.push(' ');
-        Ok((prelude, (start, self.at_rules.len())))
+        if let Some(prelude) = prelude {
+            self.at_rules.push_str(prelude);
+            self.at_rules.push(';');
+            self.at_rules.push(' ');
+        }
+        Ok(Rules::new())
     }
 }
 
+/// Whether the at-rule is `@media` with a query list matching the environment.
+/// Consumes the at-rule prelude.
+fn is_matching_media(
+    media_environment: Option<&MediaEnvironment>,
+    name: &str,
+    input: &mut cssparser::Parser<'_, '_>,
+) -> bool {
+    media_environment.is_some_and(|media_environment| {
+        name.eq_ignore_ascii_case("media") && media_environment.matches(exhaust(input))
+    })
+}
+
+/// Parse rules inside an at-rule block, e.g. `@media`, as if they were on the top level.
+fn parse_nested_rules<'i, P>(
+    input: &mut cssparser::Parser<'i, '_>,
+    parser: &mut P,
+) -> (Rules<'i>, Vec<InvalidCss<'i>>)
+where
+    P: cssparser::QualifiedRuleParser<'i, QualifiedRule = Rules<'i>, Error = ()>
+        + cssparser::AtRuleParser<'i, AtRule = Rules<'i>, Error = ()>,
+{
+    let mut rules = Rules::new();
+    let mut invalid = Vec::new();
+    for item in cssparser::StyleSheetParser::new(input, parser) {
+        match item {
+            Ok(nested) => rules.extend(nested),
+            Err((error, text)) => invalid.push(InvalidCss::new(&error, text, InvalidCssKind::Rule)),
+        }
+    }
+    (rules, invalid)
+}
+
 fn parse_declarations_into<'i>(
     input: &mut cssparser::Parser<'i, '_>,
     declarations: &mut Vec<Declaration<'i>>,
+    invalid: &mut Vec<InvalidCss<'i>>,
 ) -> (usize, usize) {
     let mut parser = CSSDeclarationListParser;
     let parser = cssparser::RuleBodyParser::new(input, &mut parser);
     let start = declarations.len();
-    for item in parser.flatten() {
-        declarations.push(item);
+    for item in parser {
+        match item {
+            Ok(declaration) => declarations.push(declaration),
+            Err((error, text)) => {
+                invalid.push(InvalidCss::new(&error, text, InvalidCssKind::Declaration));
+            }
+        }
     }
     (start, declarations.len())
 }
+
+/// An `@import` rule from the start of a stylesheet.
+#[derive(Debug)]
+pub(crate) struct Import<'i> {
+    pub(crate) href: cssparser::CowRcStr<'i>,
+    /// Media query list the import is conditional on. Empty for unconditional imports.
+    pub(crate) media: &'i str,
+    /// Location of the whole rule in the stylesheet.
+    pub(crate) range: Range<usize>,
+}
+
+/// Collect `@import` rules. They are only valid before any other rules, except for `@charset`
+/// and `@layer` statements.
+pub(crate) fn parse_imports(css: &str) -> Vec<Import<'_>> {
+    let mut input = cssparser::ParserInput::new(css);
+    let mut parser = cssparser::Parser::new(&mut input);
+    let mut imports = Vec::new();
+    loop {
+        let start = parser.position().byte_index();
+        let name = match parser.next() {
+            Ok(Token::CDO | Token::CDC) => continue,
+            Ok(Token::AtKeyword(name)) => name.clone(),
+            _ => break,
+        };
+        let parsed = if name.eq_ignore_ascii_case("import") {
+            parser.try_parse(parse_import).map(Some)
+        } else if name.eq_ignore_ascii_case("charset") || name.eq_ignore_ascii_case("layer") {
+            parser
+                .try_parse(|parser| {
+                    parser.parse_until_before(
+                        Delimiter::Semicolon | Delimiter::CurlyBracketBlock,
+                        |parser| {
+                            exhaust(parser);
+                            Ok::<_, cssparser::ParseError<'_, ()>>(())
+                        },
+                    )?;
+                    parser.expect_semicolon()?;
+                    Ok(())
+                })
+                .map(|()| None)
+        } else {
+            break;
+        };
+        match parsed {
+            Ok(Some((href, media))) => imports.push(Import {
+                href,
+                media,
+                range: start..parser.position().byte_index(),
+            }),
+            Ok(None) => {}
+            Err(_) => break,
+        }
+    }
+    imports
+}
+
+/// Parse the `@import` prelude: a URL, optional layer & supports conditions and a media query list.
+fn parse_import<'i>(
+    parser: &mut cssparser::Parser<'i, '_>,
+) -> Result<(cssparser::CowRcStr<'i>, &'i str), cssparser::ParseError<'i, ()>> {
+    let location = parser.current_source_location();
+    let href = match parser.next()?.clone() {
+        Token::UnquotedUrl(href) | Token::QuotedString(href) => href,
+        Token::Function(name) if name.eq_ignore_ascii_case("url") => {
+            parser.parse_nested_block(|parser| Ok(parser.expect_string()?.clone()))?
+        }
+        token => return Err(location.new_unexpected_token_error(token)),
+    };
+    // Cascade layers & feature queries are not evaluated
+    for condition in ["layer", "supports"] {
+        let _ = parser.try_parse(|parser| match parser.next()?.clone() {
+            Token::Ident(name) if condition == "layer" && name.eq_ignore_ascii_case("layer") => {
+                Ok(())
+            }
+            Token::Function(name) if name.eq_ignore_ascii_case(condition) => parser
+                .parse_nested_block(|parser| {
+                    exhaust(parser);
+                    Ok(())
+                }),
+            token => Err(parser.new_unexpected_token_error::<()>(token)),
+        });
+    }
+    let media = parser.parse_until_before(Delimiter::Semicolon, |parser| {
+        Ok::<_, cssparser::ParseError<'_, ()>>(exhaust(parser).trim())
+    })?;
+    if !parser.is_exhausted() {
+        parser.expect_semicolon()?;
+    }
+    Ok((href, media))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_imports;
+    use test_case::test_case;
+
+    #[test_case("@import url(a.css);", &[("a.css", "")])]
+    #[test_case("@import 'a.css' screen and (max-width: 600px);", &[("a.css", "screen and (max-width: 600px)")])]
+    #[test_case("@charset \"utf-8\"; @import url('a.css') print; @import \"b.css\"", &[("a.css", "print"), ("b.css", "")])]
+    #[test_case("@layer base; @import url(a.css) layer(base) supports(display: grid) print;", &[("a.css", "print")])]
+    #[test_case("h1 { color: red; } @import url(a.css);", &[])]
+    #[test_case("@media print { h1 { color: red; } } @import url(a.css);", &[])]
+    #[test_case("@import; @import url(a.css);", &[])]
+    fn test_parse_imports(css: &str, expected: &[(&str, &str)]) {
+        let imports = parse_imports(css)
+            .iter()
+            .map(|import| (import.href.to_string(), import.media))
+            .collect::<Vec<_>>();
+        let expected = expected
+            .iter()
+            .map(|(href, media)| ((*href).to_string(), *media))
+            .collect::<Vec<_>>();
+        assert_eq!(imports, expected);
+    }
+
+    #[test]
+    fn import_range() {
+        let css = "  @import url(a.css);\nh1 { color: red; }";
+        let imports = parse_imports(css);
+        assert_eq!(&css[imports[0].range.clone()], "  @import url(a.css);");
+    }
+}
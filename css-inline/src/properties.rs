@@ -0,0 +1,179 @@
+//! Relations between CSS shorthand properties and the properties they set.
+
+/// Properties directly set by a shorthand property.
+///
+/// Some of them are shorthands on their own, e.g. `border` sets `border-top`, which sets
+/// `border-top-width`.
+#[allow(clippy::too_many_lines)]
+fn sub_properties(name: &str) -> Option<&'static [&'static str]> {
+    Some(match name {
+        "margin" => &["margin-top", "margin-right", "margin-bottom", "margin-left"],
+        "padding" => &[
+            "padding-top",
+            "padding-right",
+            "padding-bottom",
+            "padding-left",
+        ],
+        "inset" => &["top", "right", "bottom", "left"],
+        "border" => &[
+            "border-top",
+            "border-right",
+            "border-bottom",
+            "border-left",
+            "border-image",
+        ],
+        "border-top" => &["border-top-width", "border-top-style", "border-top-color"],
+        "border-right" => &[
+            "border-right-width",
+            "border-right-style",
+            "border-right-color",
+        ],
+        "border-bottom" => &[
+            "border-bottom-width",
+            "border-bottom-style",
+            "border-bottom-color",
+        ],
+        "border-left" => &[
+            "border-left-width",
+            "border-left-style",
+            "border-left-color",
+        ],
+        "border-width" => &[
+            "border-top-width",
+            "border-right-width",
+            "border-bottom-width",
+            "border-left-width",
+        ],
+        "border-style" => &[
+            "border-top-style",
+            "border-right-style",
+            "border-bottom-style",
+            "border-left-style",
+        ],
+        "border-color" => &[
+            "border-top-color",
+            "border-right-color",
+            "border-bottom-color",
+            "border-left-color",
+        ],
+        "border-radius" => &[
+            "border-top-left-radius",
+            "border-top-right-radius",
+            "border-bottom-right-radius",
+            "border-bottom-left-radius",
+        ],
+        "border-image" => &[
+            "border-image-source",
+            "border-image-slice",
+            "border-image-width",
+            "border-image-outset",
+            "border-image-repeat",
+        ],
+        "background" => &[
+            "background-color",
+            "background-image",
+            "background-repeat",
+            "background-position",
+            "background-size",
+            "background-attachment",
+            "background-origin",
+            "background-clip",
+        ],
+        "background-position" => &["background-position-x", "background-position-y"],
+        "font" => &[
+            "font-style",
+            "font-variant",
+            "font-weight",
+            "font-stretch",
+            "font-size",
+            "line-height",
+            "font-family",
+        ],
+        "list-style" => &["list-style-type", "list-style-position", "list-style-image"],
+        "outline" => &["outline-color", "outline-style", "outline-width"],
+        "text-decoration" => &[
+            "text-decoration-line",
+            "text-decoration-style",
+            "text-decoration-color",
+            "text-decoration-thickness",
+        ],
+        "overflow" => &["overflow-x", "overflow-y"],
+        "flex" => &["flex-grow", "flex-shrink", "flex-basis"],
+        "flex-flow" => &["flex-direction", "flex-wrap"],
+        "gap" => &["row-gap", "column-gap"],
+        "columns" => &["column-width", "column-count"],
+        "column-rule" => &[
+            "column-rule-width",
+            "column-rule-style",
+            "column-rule-color",
+        ],
+        "transition" => &[
+            "transition-property",
+            "transition-duration",
+            "transition-timing-function",
+            "transition-delay",
+        ],
+        _ => return None,
+    })
+}
+
+/// Whether setting `shorthand` sets `property` too.
+///
+/// Every property covers itself.
+pub(crate) fn covers(shorthand: &str, property: &str) -> bool {
+    shorthand == property
+        || sub_properties(shorthand).is_some_and(|sub_properties| {
+            sub_properties
+                .iter()
+                .any(|sub_property| covers(sub_property, property))
+        })
+}
+
+/// Whether two properties set at least one common property.
+///
+/// E.g. `border-top` and `border-color` both set `border-top-color`.
+pub(crate) fn overlap(first: &str, second: &str) -> bool {
+    if first == second {
+        return true;
+    }
+    match (sub_properties(first), sub_properties(second)) {
+        (None, None) => false,
+        (Some(sub_properties), _) => sub_properties
+            .iter()
+            .any(|sub_property| overlap(sub_property, second)),
+        (None, Some(sub_properties)) => sub_properties
+            .iter()
+            .any(|sub_property| overlap(first, sub_property)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{covers, overlap};
+    use test_case::test_case;
+
+    #[test_case("margin", "margin", true)]
+    #[test_case("margin", "margin-top", true)]
+    #[test_case("margin-top", "margin", false)]
+    #[test_case("border", "border-top-color", true)]
+    #[test_case("border", "border-image-slice", true)]
+    #[test_case("border-color", "border-top-width", false)]
+    #[test_case("font", "line-height", true)]
+    #[test_case("background", "background-position-x", true)]
+    #[test_case("color", "background-color", false)]
+    fn test_covers(shorthand: &str, property: &str, expected: bool) {
+        assert_eq!(covers(shorthand, property), expected);
+    }
+
+    #[test_case("margin", "margin-top", true)]
+    #[test_case("margin-top", "margin", true)]
+    #[test_case("border-top", "border-color", true)]
+    #[test_case("border-color", "border-top", true)]
+    #[test_case("border-color", "border-width", false)]
+    #[test_case("margin-top", "margin-left", false)]
+    #[test_case("margin", "padding", false)]
+    #[test_case("color", "color", true)]
+    fn test_overlap(first: &str, second: &str, expected: bool) {
+        assert_eq!(overlap(first, second), expected);
+    }
+}
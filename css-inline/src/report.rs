@@ -0,0 +1,42 @@
+//! Diagnostics collected while inlining.
+
+/// A summary of CSS that did not make it into the output document.
+///
+/// Inlining is lenient by design: CSS that can not be applied is skipped without an error. This
+/// report lists everything that was skipped, so callers can detect styles that never reach the
+/// resulting HTML.
+///
+/// Obtained via [`CSSInliner::inline_with_report`](crate::CSSInliner::inline_with_report) or
+/// [`CSSInliner::inline_fragment_with_report`](crate::CSSInliner::inline_fragment_with_report).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct InlineReport {
+    /// Selectors that could not be parsed or use features the inliner does not support.
+    pub unsupported_selectors: Vec<String>,
+    /// Valid selectors that did not match any element in the document.
+    pub unmatched_selectors: Vec<String>,
+    /// At-rules that were removed from the output, e.g. `@media` without `keep_at_rules`.
+    pub dropped_at_rules: Vec<String>,
+    /// Rules that could not be parsed.
+    pub invalid_rules: Vec<String>,
+    /// Declarations that could not be parsed.
+    pub invalid_declarations: Vec<String>,
+    /// Locations of all linked stylesheets that were loaded, after resolving relative URLs.
+    pub resolved_stylesheets: Vec<String>,
+    /// Locations of linked stylesheets that resolved to no CSS at all.
+    pub empty_stylesheets: Vec<String>,
+}
+
+impl InlineReport {
+    /// Whether all the collected CSS was applied.
+    ///
+    /// Loaded stylesheets are informational and do not affect the result.
+    #[must_use]
+    pub fn is_clean(&self) -> bool {
+        self.unsupported_selectors.is_empty()
+            && self.unmatched_selectors.is_empty()
+            && self.dropped_at_rules.is_empty()
+            && self.invalid_rules.is_empty()
+            && self.invalid_declarations.is_empty()
+            && self.empty_stylesheets.is_empty()
+    }
+}
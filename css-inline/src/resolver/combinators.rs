@@ -0,0 +1,203 @@
+//! Resolvers that delegate to other resolvers.
+use super::{is_data_url, is_url, ResolverPolicy, StylesheetResolver};
+use crate::{InlineError, Result};
+use percent_encoding::percent_decode_str;
+use std::{borrow::Cow, fmt, sync::Arc};
+
+/// Stylesheet resolver that tries several resolvers in order.
+///
+/// The first successfully retrieved stylesheet is used. If all resolvers fail, the error from
+/// the last one is returned.
+///
+/// ```rust
+/// use css_inline::{ChainResolver, CSSInliner, DefaultStylesheetResolver, MemoryStylesheetResolver};
+/// use std::sync::Arc;
+///
+/// let bundled = MemoryStylesheetResolver::default()
+///     .stylesheet("https://cdn.example.com/main.css", "h1 { color: blue; }");
+/// // Anything that is not bundled is loaded from the network or the filesystem
+/// let resolver = ChainResolver::new(vec![
+///     Arc::new(bundled),
+///     Arc::new(DefaultStylesheetResolver),
+/// ]);
+/// let inliner = CSSInliner::options()
+///     .resolver(Arc::new(resolver))
+///     .build();
+/// ```
+#[derive(Clone)]
+pub struct ChainResolver {
+    resolvers: Vec<Arc<dyn StylesheetResolver>>,
+}
+
+impl ChainResolver {
+    /// Try `resolvers` in the given order.
+    #[must_use]
+    pub fn new(resolvers: Vec<Arc<dyn StylesheetResolver>>) -> ChainResolver {
+        ChainResolver { resolvers }
+    }
+}
+
+impl fmt::Debug for ChainResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChainResolver").finish_non_exhaustive()
+    }
+}
+
+impl ChainResolver {
+    fn first_success(
+        &self,
+        location: &str,
+        retrieve: impl Fn(&dyn StylesheetResolver) -> Result<String>,
+    ) -> Result<String> {
+        let mut last_error = None;
+        for resolver in &self.resolvers {
+            match retrieve(resolver.as_ref()) {
+                Ok(css) => return Ok(css),
+                Err(error) => last_error = Some(error),
+            }
+        }
+        Err(
+            last_error.unwrap_or_else(|| InlineError::MissingStyleSheet {
+                path: location.to_string(),
+            }),
+        )
+    }
+}
+
+impl StylesheetResolver for ChainResolver {
+    fn retrieve(&self, location: &str) -> Result<String> {
+        self.first_success(location, |resolver| resolver.retrieve(location))
+    }
+
+    fn retrieve_with_policy(&self, location: &str, policy: &ResolverPolicy) -> Result<String> {
+        self.first_success(location, |resolver| {
+            resolver.retrieve_with_policy(location, policy)
+        })
+    }
+}
+
+/// Stylesheet resolver that rewrites location prefixes before passing them to another resolver.
+///
+/// For example, stylesheets from a CDN can be read from a local directory instead. The part
+/// of the location after the prefix is appended to the target without its query and fragment.
+/// It is percent-decoded if the target is a local path, e.g. `main%20v2.css` is read from
+/// `main v2.css`. Routes are checked in the order they were added and locations that match none of them are
+/// passed as is.
+///
+/// ```rust
+/// use css_inline::{CSSInliner, DefaultStylesheetResolver, PrefixResolver};
+/// use std::sync::Arc;
+///
+/// // `https://cdn.example.com/css/main.css?v=2` is read from `/srv/static/css/main.css`
+/// let resolver = PrefixResolver::new(Arc::new(DefaultStylesheetResolver))
+///     .route("https://cdn.example.com/", "/srv/static/");
+/// let inliner = CSSInliner::options()
+///     .resolver(Arc::new(resolver))
+///     .build();
+/// ```
+#[derive(Clone)]
+pub struct PrefixResolver {
+    resolver: Arc<dyn StylesheetResolver>,
+    routes: Vec<(String, String)>,
+}
+
+impl PrefixResolver {
+    /// Pass rewritten locations to `resolver`.
+    #[must_use]
+    pub fn new(resolver: Arc<dyn StylesheetResolver>) -> PrefixResolver {
+        PrefixResolver {
+            resolver,
+            routes: Vec::new(),
+        }
+    }
+
+    /// Replace `prefix` at the start of locations with `target`, e.g. a URL with a directory.
+    #[must_use]
+    pub fn route(mut self, prefix: impl Into<String>, target: impl Into<String>) -> Self {
+        self.routes.push((prefix.into(), target.into()));
+        self
+    }
+
+    fn rewrite<'l>(&self, location: &'l str) -> Result<Cow<'l, str>> {
+        let Some((rest, target)) = self.routes.iter().find_map(|(prefix, target)| {
+            location
+                .strip_prefix(prefix.as_str())
+                .map(|rest| (rest, target))
+        }) else {
+            return Ok(Cow::Borrowed(location));
+        };
+        let rest = rest.find(['?', '#']).map_or(rest, |end| &rest[..end]);
+        let decoded = percent_decode_str(rest).decode_utf8_lossy();
+        // The prefix is usually mapped to a directory, which should not be escaped
+        if decoded.split(['/', '\\']).any(|segment| segment == "..") {
+            return Err(InlineError::PolicyViolation {
+                reason: Cow::Borrowed("Path traversal is not allowed"),
+                location: location.to_string(),
+            });
+        }
+        if is_url(target) || is_data_url(target) {
+            Ok(Cow::Owned(format!("{target}{rest}")))
+        } else {
+            Ok(Cow::Owned(format!("{target}{decoded}")))
+        }
+    }
+}
+
+impl fmt::Debug for PrefixResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PrefixResolver")
+            .field("routes", &self.routes)
+            .finish_non_exhaustive()
+    }
+}
+
+impl StylesheetResolver for PrefixResolver {
+    fn retrieve(&self, location: &str) -> Result<String> {
+        self.resolver.retrieve(&self.rewrite(location)?)
+    }
+
+    fn retrieve_with_policy(&self, location: &str, policy: &ResolverPolicy) -> Result<String> {
+        self.resolver
+            .retrieve_with_policy(&self.rewrite(location)?, policy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PrefixResolver;
+    use crate::DefaultStylesheetResolver;
+    use std::sync::Arc;
+    use test_case::test_case;
+
+    #[test_case("https://cdn.example.com/css/main.css", Ok("/srv/static/css/main.css"))]
+    #[test_case("https://cdn.example.com/main.css?v=2#top", Ok("/srv/static/main.css"))]
+    #[test_case("https://fonts.example.com/fonts.css", Ok("styles/fonts.css"))]
+    #[test_case("https://cdn.example.com/main%20v2.css", Ok("/srv/static/main v2.css"))]
+    #[test_case(
+        "https://assets.example.com/main%20v2.css",
+        Ok("https://static.example.com/main%20v2.css")
+    )]
+    #[test_case(
+        "https://cdn.example.com/%2e%2e/secrets.css",
+        Err("Path traversal is not allowed: https://cdn.example.com/%2e%2e/secrets.css")
+    )]
+    #[test_case(
+        "https://example.com/main.css?v=2",
+        Ok("https://example.com/main.css?v=2")
+    )]
+    #[test_case(
+        "https://cdn.example.com/../../etc/passwd",
+        Err("Path traversal is not allowed: https://cdn.example.com/../../etc/passwd")
+    )]
+    fn test_rewrite(location: &str, expected: Result<&str, &str>) {
+        let resolver = PrefixResolver::new(Arc::new(DefaultStylesheetResolver))
+            .route("https://cdn.example.com/", "/srv/static/")
+            .route("https://fonts.example.com/", "styles/")
+            .route("https://assets.example.com/", "https://static.example.com/");
+        let rewritten = resolver.rewrite(location);
+        assert_eq!(
+            rewritten.as_deref().map_err(ToString::to_string),
+            expected.map_err(str::to_string)
+        );
+    }
+}
@@ -0,0 +1,508 @@
+//! Configurable loading of stylesheets over HTTP.
+use super::{
+    is_url,
+    policy::{is_public, violation},
+    ResolverPolicy, StylesheetResolver,
+};
+use crate::{InlineError, Result};
+use encoding_rs::{Encoding, UTF_8};
+use reqwest::{
+    blocking::{Client, Response},
+    dns::{Addrs, Name, Resolve, Resolving},
+    header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE},
+    redirect, StatusCode,
+};
+use std::{
+    borrow::Cow,
+    error::Error,
+    io::{self, ErrorKind, Read},
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Timeout for receiving the response headers and for each read of the response body.
+const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(30);
+/// Redirects followed by default.
+pub(super) const DEFAULT_MAX_REDIRECTS: usize = 10;
+/// Delay before the first retry. It doubles with each next attempt.
+const DEFAULT_RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Stylesheet resolver with configurable limits for HTTP requests.
+///
+/// Responses with non-successful status codes are reported as errors. Local files are loaded
+/// like in [`DefaultStylesheetResolver`](crate::DefaultStylesheetResolver).
+///
+/// ```rust
+/// use css_inline::{CSSInliner, HttpStylesheetResolver};
+/// use std::{sync::Arc, time::Duration};
+///
+/// let resolver = HttpStylesheetResolver::builder()
+///     .connect_timeout(Some(Duration::from_secs(2)))
+///     .max_body_size(Some(512 * 1024))
+///     .header("Authorization", "Bearer secret")
+///     .retries(2)
+///     .build()?;
+/// let inliner = CSSInliner::options()
+///     .resolver(Arc::new(resolver))
+///     .build();
+/// # Ok::<(), css_inline::InlineError>(())
+/// ```
+#[derive(Debug)]
+pub struct HttpStylesheetResolver {
+    client: Client,
+    /// A client that checks redirects against the last used policy.
+    checked_client: Mutex<Option<(ResolverPolicy, Client)>>,
+    builder: HttpStylesheetResolverBuilder,
+    max_body_size: Option<u64>,
+    retries: u32,
+    retry_backoff: Duration,
+}
+
+impl HttpStylesheetResolver {
+    /// Create a builder to configure the resolver.
+    #[must_use]
+    pub fn builder() -> HttpStylesheetResolverBuilder {
+        HttpStylesheetResolverBuilder::default()
+    }
+
+    fn fetch(&self, client: &Client, url: &str) -> Result<String> {
+        let into_error = |error| network_error(error, url);
+        let mut response = client
+            .get(url)
+            .send()
+            .and_then(Response::error_for_status)
+            .map_err(into_error)?;
+        let encoding = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(charset)
+            .and_then(|charset| Encoding::for_label(charset.as_bytes()))
+            .unwrap_or(UTF_8);
+        let mut body = Vec::new();
+        if let Some(max_body_size) = self.max_body_size {
+            if response
+                .content_length()
+                .is_some_and(|length| length > max_body_size)
+            {
+                return Err(body_too_large(url, max_body_size));
+            }
+            // Read one byte more to detect bodies without `Content-Length` that exceed the limit
+            (&mut response)
+                .take(max_body_size.saturating_add(1))
+                .read_to_end(&mut body)?;
+            if u64::try_from(body.len()).map_or(true, |length| length > max_body_size) {
+                return Err(body_too_large(url, max_body_size));
+            }
+        } else {
+            response.read_to_end(&mut body)?;
+        }
+        // A byte order mark overrides the declared charset
+        let (css, _, _) = encoding.decode(&body);
+        Ok(css.into_owned())
+    }
+}
+
+impl Default for HttpStylesheetResolver {
+    fn default() -> Self {
+        HttpStylesheetResolverBuilder::default()
+            .build()
+            .expect("Default HTTP client configuration is valid")
+    }
+}
+
+impl Clone for HttpStylesheetResolver {
+    fn clone(&self) -> Self {
+        HttpStylesheetResolver {
+            client: self.client.clone(),
+            checked_client: Mutex::new(None),
+            builder: self.builder.clone(),
+            max_body_size: self.max_body_size,
+            retries: self.retries,
+            retry_backoff: self.retry_backoff,
+        }
+    }
+}
+
+impl HttpStylesheetResolver {
+    fn fetch_with_retries(&self, client: &Client, url: &str) -> Result<String> {
+        let mut attempt = 0;
+        loop {
+            match self.fetch(client, url) {
+                Err(error) if attempt < self.retries && is_transient(&error) => {
+                    std::thread::sleep(
+                        self.retry_backoff
+                            .saturating_mul(2_u32.saturating_pow(attempt)),
+                    );
+                    attempt = attempt.saturating_add(1);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+impl StylesheetResolver for HttpStylesheetResolver {
+    fn retrieve_from_url(&self, url: &str) -> Result<String> {
+        self.fetch_with_retries(&self.client, url)
+    }
+
+    fn retrieve_with_policy(&self, location: &str, policy: &ResolverPolicy) -> Result<String> {
+        if !is_url(location) {
+            return self.retrieve(location);
+        }
+        // Usually, the same policy is used for all requests
+        let client = {
+            let mut checked_client = self.checked_client.lock().expect("Lock is poisoned");
+            match &*checked_client {
+                Some((cached, client)) if cached == policy => client.clone(),
+                _ => {
+                    let client = self.builder.client(Some(policy))?;
+                    *checked_client = Some((policy.clone(), client.clone()));
+                    client
+                }
+            }
+        };
+        self.fetch_with_retries(&client, location)
+    }
+}
+
+/// Whether the request may succeed if it is sent again.
+fn is_transient(error: &InlineError) -> bool {
+    match error {
+        InlineError::Network { error, .. } => {
+            error.is_timeout()
+                || error.is_connect()
+                || error.status().is_some_and(|status| {
+                    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+                })
+        }
+        InlineError::IO(error) => error.kind() == ErrorKind::TimedOut,
+        _ => false,
+    }
+}
+
+/// The `charset` parameter of a `Content-Type` header value, e.g. `text/css; charset=utf-8`.
+fn charset(content_type: &str) -> Option<&str> {
+    content_type.split(';').skip(1).find_map(|parameter| {
+        let (name, value) = parameter.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("charset")
+            .then(|| value.trim().trim_matches('"'))
+    })
+}
+
+fn body_too_large(url: &str, max_body_size: u64) -> InlineError {
+    io::Error::new(
+        ErrorKind::InvalidData,
+        format!("Stylesheet exceeds the maximum size of {max_body_size} bytes: {url}"),
+    )
+    .into()
+}
+
+/// Builder for [`HttpStylesheetResolver`].
+#[derive(Debug, Clone)]
+pub struct HttpStylesheetResolverBuilder {
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+    max_body_size: Option<u64>,
+    headers: Vec<(String, String)>,
+    user_agent: Option<String>,
+    max_redirects: usize,
+    retries: u32,
+    retry_backoff: Duration,
+}
+
+impl Default for HttpStylesheetResolverBuilder {
+    fn default() -> Self {
+        HttpStylesheetResolverBuilder {
+            connect_timeout: None,
+            read_timeout: Some(DEFAULT_READ_TIMEOUT),
+            max_body_size: None,
+            headers: Vec::new(),
+            user_agent: None,
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            retries: 0,
+            retry_backoff: DEFAULT_RETRY_BACKOFF,
+        }
+    }
+}
+
+impl HttpStylesheetResolverBuilder {
+    /// Timeout for establishing a connection. Not limited by default.
+    #[must_use]
+    pub fn connect_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Timeout for receiving the response headers and for each read of the response body.
+    /// Defaults to 30 seconds.
+    #[must_use]
+    pub fn read_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.read_timeout = timeout;
+        self
+    }
+
+    /// Maximum size of a stylesheet in bytes. Larger responses are reported as errors.
+    /// Not limited by default.
+    #[must_use]
+    pub fn max_body_size(mut self, max_body_size: Option<u64>) -> Self {
+        self.max_body_size = max_body_size;
+        self
+    }
+
+    /// Add a header to send with every request, e.g. `Authorization`.
+    #[must_use]
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Value of the `User-Agent` header.
+    #[must_use]
+    pub fn user_agent(mut self, user_agent: Option<String>) -> Self {
+        self.user_agent = user_agent;
+        self
+    }
+
+    /// Maximum number of redirects to follow. Exceeding it is an error, so zero rejects any
+    /// redirect. Defaults to 10.
+    #[must_use]
+    pub fn max_redirects(mut self, max_redirects: usize) -> Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    /// Number of times to retry requests that failed with a timeout, a connection error,
+    /// a `429` or a `5xx` status code. No retries by default.
+    #[must_use]
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Delay before the first retry, doubled for each next one. Defaults to 100 milliseconds.
+    #[must_use]
+    pub fn retry_backoff(mut self, backoff: Duration) -> Self {
+        self.retry_backoff = backoff;
+        self
+    }
+
+    /// Create the resolver.
+    ///
+    /// # Errors
+    ///
+    /// Invalid header names or values, or a failure to initialize the HTTP client.
+    pub fn build(self) -> Result<HttpStylesheetResolver> {
+        Ok(HttpStylesheetResolver {
+            client: self.client(None)?,
+            checked_client: Mutex::new(None),
+            max_body_size: self.max_body_size,
+            retries: self.retries,
+            retry_backoff: self.retry_backoff,
+            builder: self,
+        })
+    }
+
+    /// A client that checks redirects and resolved addresses against the policy, if any.
+    fn client(&self, policy: Option<&ResolverPolicy>) -> Result<Client> {
+        let mut headers = HeaderMap::with_capacity(self.headers.len());
+        for (name, value) in &self.headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| invalid_header(&format!("Invalid header name: {name}")))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|_| invalid_header(&format!("Invalid value of the `{name}` header")))?;
+            headers.append(name, value);
+        }
+        let mut client = Client::builder()
+            .default_headers(headers)
+            .connect_timeout(self.connect_timeout)
+            .timeout(self.read_timeout);
+        client = match policy {
+            Some(policy) => with_policy(client, policy, self.max_redirects),
+            None => client.redirect(redirect::Policy::limited(self.max_redirects)),
+        };
+        if let Some(user_agent) = &self.user_agent {
+            let user_agent = HeaderValue::from_str(user_agent)
+                .map_err(|_| invalid_header("Invalid value of the `User-Agent` header"))?;
+            client = client.user_agent(user_agent);
+        }
+        Ok(client.build().map_err(io::Error::other)?)
+    }
+}
+
+/// Follow up to `max_redirects` redirects to locations allowed by the policy.
+fn checked_redirects(
+    policy: ResolverPolicy,
+    max_redirects: usize,
+    check: fn(&ResolverPolicy, &str) -> Result<()>,
+) -> redirect::Policy {
+    redirect::Policy::custom(move |attempt| {
+        if attempt.previous().len() > max_redirects {
+            attempt.error(format!("Too many redirects, the limit is {max_redirects}"))
+        } else if let Err(error) = check(&policy, attempt.url().as_str()) {
+            attempt.error(error)
+        } else {
+            attempt.follow()
+        }
+    })
+}
+
+/// Check redirects and resolved addresses against the policy.
+pub(super) fn with_policy(
+    client: reqwest::blocking::ClientBuilder,
+    policy: &ResolverPolicy,
+    max_redirects: usize,
+) -> reqwest::blocking::ClientBuilder {
+    let client = client.redirect(checked_redirects(
+        policy.clone(),
+        max_redirects,
+        ResolverPolicy::check,
+    ));
+    if policy.blocks_private_ips() {
+        client.dns_resolver(Arc::new(PublicDnsResolver))
+    } else {
+        client
+    }
+}
+
+/// Check redirects and resolved addresses against the policy.
+///
+/// Redirect targets are checked without resolving host names, as it would block the executor.
+/// Their addresses are checked when connecting instead.
+#[cfg(feature = "async")]
+pub(super) fn with_policy_async(
+    client: reqwest::ClientBuilder,
+    policy: &ResolverPolicy,
+    max_redirects: usize,
+) -> reqwest::ClientBuilder {
+    let client = client.redirect(checked_redirects(
+        policy.clone(),
+        max_redirects,
+        ResolverPolicy::check_without_lookup,
+    ));
+    if policy.blocks_private_ips() {
+        client.dns_resolver(Arc::new(PublicDnsResolver))
+    } else {
+        client
+    }
+}
+
+/// Resolves host names to public IP addresses only.
+///
+/// The policy checks host names before the request, but they are resolved once more when
+/// connecting. Dropping non-public addresses here ensures that the client connects only to
+/// checked addresses, even if the DNS response changes in between. The lookup runs on the
+/// runtime's blocking pool, so it doesn't stall async clients.
+#[derive(Debug)]
+struct PublicDnsResolver;
+
+impl Resolve for PublicDnsResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let host = name.as_str();
+            // Lookup errors are returned as is, so unresolvable hosts are never connected to
+            let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host, 0)).await?.collect();
+            let Some(address) = addresses.first() else {
+                return Err(violation(
+                    host,
+                    Cow::Owned(format!("Host `{host}` could not be resolved")),
+                )
+                .into());
+            };
+            let address = address.ip();
+            let public: Vec<SocketAddr> = addresses
+                .into_iter()
+                .filter(|address| is_public(address.ip()))
+                .collect();
+            if public.is_empty() {
+                return Err(violation(
+                    host,
+                    Cow::Owned(format!("Private IP address `{address}` is not allowed")),
+                )
+                .into());
+            }
+            let public: Addrs = Box::new(public.into_iter());
+            Ok(public)
+        })
+    }
+}
+
+/// Report redirects to forbidden locations as policy violations.
+pub(super) fn network_error(error: reqwest::Error, url: &str) -> InlineError {
+    let mut source = error.source();
+    while let Some(current) = source {
+        if let Some(InlineError::PolicyViolation { reason, location }) =
+            current.downcast_ref::<InlineError>()
+        {
+            return InlineError::PolicyViolation {
+                reason: reason.clone(),
+                location: location.clone(),
+            };
+        }
+        source = current.source();
+    }
+    InlineError::Network {
+        error,
+        location: url.to_string(),
+    }
+}
+
+fn invalid_header(reason: &str) -> InlineError {
+    io::Error::new(ErrorKind::InvalidInput, reason).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{charset, HttpStylesheetResolver, PublicDnsResolver};
+    use reqwest::dns::Resolve;
+    use test_case::test_case;
+
+    #[test_case("text/css", None)]
+    #[test_case("text/css; charset=utf-8", Some("utf-8"))]
+    #[test_case("text/css;Charset=\"ISO-8859-1\"", Some("ISO-8859-1"))]
+    #[test_case("text/css; boundary=x; charset=windows-1251", Some("windows-1251"))]
+    fn test_charset(content_type: &str, expected: Option<&str>) {
+        assert_eq!(charset(content_type), expected);
+    }
+
+    #[tokio::test]
+    async fn test_public_dns_resolver_drops_private_addresses() {
+        let error = PublicDnsResolver
+            .resolve("localhost".parse().expect("Invalid name"))
+            .await
+            .err()
+            .expect("Loopback addresses should be dropped");
+        assert!(
+            error.to_string().starts_with("Private IP address"),
+            "{error}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_public_dns_resolver_unresolvable() {
+        assert!(PublicDnsResolver
+            .resolve("unknown.invalid".parse().expect("Invalid name"))
+            .await
+            .is_err());
+    }
+
+    #[test_case("Authorization", "Bearer secret", None)]
+    #[test_case("X Token", "secret", Some("Invalid header name: X Token"))]
+    #[test_case(
+        "X-Token",
+        "line\nbreak",
+        Some("Invalid value of the `x-token` header")
+    )]
+    fn test_build_headers(name: &str, value: &str, expected: Option<&str>) {
+        let result = HttpStylesheetResolver::builder()
+            .header(name, value)
+            .build();
+        assert_eq!(
+            result.err().map(|error| error.to_string()).as_deref(),
+            expected
+        );
+    }
+}
@@ -0,0 +1,65 @@
+//! Stylesheets bundled in memory.
+use super::{is_data_url, StylesheetResolver};
+use crate::{InlineError, Result};
+use rustc_hash::FxHashMap;
+
+/// Stylesheet resolver that serves CSS from memory by its exact location.
+///
+/// Useful in tests and in deployments where stylesheets are bundled with the application.
+/// Unknown locations are reported as [`InlineError::MissingStyleSheet`], and `data:` URLs are
+/// decoded as usual.
+///
+/// ```rust
+/// use css_inline::{CSSInliner, MemoryStylesheetResolver};
+/// use std::sync::Arc;
+///
+/// let resolver = MemoryStylesheetResolver::default()
+///     .stylesheet("https://cdn.example.com/main.css", "h1 { color: blue; }");
+/// let inliner = CSSInliner::options()
+///     .resolver(Arc::new(resolver))
+///     .build();
+/// let inlined = inliner.inline(r#"<html><head><link href="https://cdn.example.com/main.css" rel="stylesheet"></head><body><h1>Hello</h1></body></html>"#);
+/// assert_eq!(
+///     inlined.expect("Inlining failed"),
+///     r#"<html><head></head><body><h1 style="color: blue;">Hello</h1></body></html>"#
+/// );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStylesheetResolver {
+    stylesheets: FxHashMap<String, String>,
+}
+
+impl MemoryStylesheetResolver {
+    /// Serve `css` for `location`, e.g. `https://cdn.example.com/main.css` or `styles/main.css`.
+    /// A previously added stylesheet with the same location is replaced.
+    #[must_use]
+    pub fn stylesheet(mut self, location: impl Into<String>, css: impl Into<String>) -> Self {
+        self.stylesheets.insert(location.into(), css.into());
+        self
+    }
+}
+
+impl<L: Into<String>, C: Into<String>> FromIterator<(L, C)> for MemoryStylesheetResolver {
+    fn from_iter<I: IntoIterator<Item = (L, C)>>(iter: I) -> Self {
+        MemoryStylesheetResolver {
+            stylesheets: iter
+                .into_iter()
+                .map(|(location, css)| (location.into(), css.into()))
+                .collect(),
+        }
+    }
+}
+
+impl StylesheetResolver for MemoryStylesheetResolver {
+    fn retrieve(&self, location: &str) -> Result<String> {
+        if let Some(css) = self.stylesheets.get(location) {
+            Ok(css.clone())
+        } else if is_data_url(location) {
+            self.retrieve_from_data_url(location)
+        } else {
+            Err(InlineError::MissingStyleSheet {
+                path: location.to_string(),
+            })
+        }
+    }
+}
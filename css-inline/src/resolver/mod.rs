@@ -0,0 +1,370 @@
+use crate::{InlineError, Result};
+use data_url::DataUrl;
+use encoding_rs::{Encoding, UTF_8};
+use std::io::ErrorKind;
+#[cfg(feature = "async")]
+use std::{future::Future, pin::Pin};
+
+mod combinators;
+#[cfg(feature = "http")]
+mod http;
+mod memory;
+mod policy;
+pub use combinators::{ChainResolver, PrefixResolver};
+#[cfg(feature = "http")]
+pub use http::{HttpStylesheetResolver, HttpStylesheetResolverBuilder};
+pub use memory::MemoryStylesheetResolver;
+pub use policy::ResolverPolicy;
+
+/// Blocking way of resolving stylesheets from various sources.
+pub trait StylesheetResolver: Send + Sync {
+    /// Retrieve a stylesheet from a network or local filesystem location.
+    ///
+    /// # Errors
+    ///
+    /// Any network or filesystem related error, or an error during response parsing.
+    fn retrieve(&self, location: &str) -> Result<String> {
+        if is_data_url(location) {
+            self.retrieve_from_data_url(location)
+        } else if is_url(location) {
+            #[cfg(feature = "http")]
+            {
+                self.retrieve_from_url(location)
+            }
+
+            #[cfg(not(feature = "http"))]
+            {
+                Err(std::io::Error::new(
+                    ErrorKind::Unsupported,
+                    "Loading external URLs requires the `http` feature",
+                )
+                .into())
+            }
+        } else {
+            #[cfg(feature = "file")]
+            {
+                self.retrieve_from_path(location)
+            }
+            #[cfg(not(feature = "file"))]
+            {
+                Err(std::io::Error::new(
+                    ErrorKind::Unsupported,
+                    "Loading local files requires the `file` feature",
+                )
+                .into())
+            }
+        }
+    }
+    /// Retrieve a stylesheet from a network location.
+    ///
+    /// # Errors
+    ///
+    /// Any network-related error, or an error during response parsing.
+    fn retrieve_from_url(&self, url: &str) -> Result<String> {
+        Err(self.unsupported(&format!("Loading external URLs is not supported: {url}")))
+    }
+    /// Retrieve a stylesheet from the local filesystem.
+    ///
+    /// # Errors
+    ///
+    /// Any filesystem-related error.
+    fn retrieve_from_path(&self, path: &str) -> Result<String> {
+        read_from_path(path)
+    }
+    /// Decode a stylesheet embedded in a `data:` URL.
+    ///
+    /// # Errors
+    ///
+    /// Malformed URL or invalid base64 content.
+    fn retrieve_from_data_url(&self, url: &str) -> Result<String> {
+        decode_data_url(url)
+    }
+    /// Retrieve a stylesheet from a location that is allowed by the policy.
+    ///
+    /// Used instead of `retrieve` when
+    /// [`InlineOptions::resolver_policy`](crate::InlineOptions::resolver_policy) is set.
+    /// The location itself is already checked, but resolvers that follow redirects should
+    /// check every redirect target with [`ResolverPolicy::check`].
+    ///
+    /// # Errors
+    ///
+    /// Any error from `retrieve`, or a redirect to a location that is not allowed.
+    fn retrieve_with_policy(&self, location: &str, policy: &ResolverPolicy) -> Result<String> {
+        let _ = policy;
+        self.retrieve(location)
+    }
+    /// Return the "Unsupported" kind of error.
+    fn unsupported(&self, reason: &str) -> InlineError {
+        unsupported(reason)
+    }
+}
+
+/// A future resolving to a stylesheet.
+#[cfg(feature = "async")]
+pub type StylesheetFuture<'a> = Pin<Box<dyn Future<Output = Result<String>> + Send + 'a>>;
+
+/// Non-blocking way of resolving stylesheets from various sources.
+///
+/// Used by [`CSSInliner::inline_async`](crate::CSSInliner::inline_async) to load all linked
+/// stylesheets concurrently.
+#[cfg(feature = "async")]
+pub trait AsyncStylesheetResolver: Send + Sync {
+    /// Retrieve a stylesheet from a network or local filesystem location.
+    ///
+    /// # Errors
+    ///
+    /// Any network or filesystem related error, or an error during response parsing.
+    fn retrieve<'a>(&'a self, location: &'a str) -> StylesheetFuture<'a> {
+        if is_data_url(location) {
+            self.retrieve_from_data_url(location)
+        } else if is_url(location) {
+            #[cfg(feature = "http")]
+            {
+                self.retrieve_from_url(location)
+            }
+
+            #[cfg(not(feature = "http"))]
+            {
+                Box::pin(std::future::ready(Err(unsupported(
+                    "Loading external URLs requires the `http` feature",
+                ))))
+            }
+        } else {
+            #[cfg(feature = "file")]
+            {
+                self.retrieve_from_path(location)
+            }
+            #[cfg(not(feature = "file"))]
+            {
+                Box::pin(std::future::ready(Err(unsupported(
+                    "Loading local files requires the `file` feature",
+                ))))
+            }
+        }
+    }
+    /// Retrieve a stylesheet from a network location.
+    ///
+    /// # Errors
+    ///
+    /// Any network-related error, or an error during response parsing.
+    fn retrieve_from_url<'a>(&'a self, url: &'a str) -> StylesheetFuture<'a> {
+        Box::pin(std::future::ready(Err(unsupported(&format!(
+            "Loading external URLs is not supported: {url}"
+        )))))
+    }
+    /// Retrieve a stylesheet from the local filesystem.
+    ///
+    /// The file is read on the current thread, as local stylesheets are usually small.
+    ///
+    /// # Errors
+    ///
+    /// Any filesystem-related error.
+    fn retrieve_from_path<'a>(&'a self, path: &'a str) -> StylesheetFuture<'a> {
+        Box::pin(std::future::ready(read_from_path(path)))
+    }
+    /// Decode a stylesheet embedded in a `data:` URL.
+    ///
+    /// # Errors
+    ///
+    /// Malformed URL or invalid base64 content.
+    fn retrieve_from_data_url<'a>(&'a self, url: &'a str) -> StylesheetFuture<'a> {
+        Box::pin(std::future::ready(decode_data_url(url)))
+    }
+    /// Retrieve a stylesheet from a location that is allowed by the policy.
+    ///
+    /// See [`StylesheetResolver::retrieve_with_policy`].
+    ///
+    /// # Errors
+    ///
+    /// Any error from `retrieve`, or a redirect to a location that is not allowed.
+    fn retrieve_with_policy<'a>(
+        &'a self,
+        location: &'a str,
+        policy: &'a ResolverPolicy,
+    ) -> StylesheetFuture<'a> {
+        let _ = policy;
+        self.retrieve(location)
+    }
+}
+
+fn read_from_path(path: &str) -> Result<String> {
+    let path = path.trim_start_matches("file://");
+    std::fs::read_to_string(path).map_err(|error| match error.kind() {
+        ErrorKind::NotFound => InlineError::MissingStyleSheet {
+            path: path.to_string(),
+        },
+        #[cfg(target_family = "wasm")]
+        ErrorKind::Unsupported => unsupported(&format!(
+            "Loading local files is not supported on WASM: {path}"
+        )),
+        _ => InlineError::IO(error),
+    })
+}
+
+/// Whether the location is loaded with `retrieve_from_url`. Everything else, except `data:`
+/// URLs, is a local path.
+fn is_url(location: &str) -> bool {
+    location.starts_with("https") || location.starts_with("http")
+}
+
+fn is_data_url(location: &str) -> bool {
+    location
+        .get(..5)
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("data:"))
+}
+
+/// Decode base64 or percent-encoded content of a `data:` URL using its declared charset.
+fn decode_data_url(url: &str) -> Result<String> {
+    let invalid = |reason: &str| -> InlineError {
+        std::io::Error::new(
+            ErrorKind::InvalidData,
+            format!("Invalid data URL: {reason}"),
+        )
+        .into()
+    };
+    let data_url = DataUrl::process(url)
+        .map_err(|_| invalid("expected `data:[<media-type>][;base64],<data>`"))?;
+    let (body, _) = data_url
+        .decode_to_vec()
+        .map_err(|_| invalid("malformed base64 content"))?;
+    // `US-ASCII` is implied when the media type is omitted. UTF-8 is its superset
+    let encoding = data_url
+        .mime_type()
+        .get_parameter("charset")
+        .filter(|charset| !charset.eq_ignore_ascii_case("us-ascii"))
+        .and_then(|charset| Encoding::for_label(charset.as_bytes()))
+        .unwrap_or(UTF_8);
+    // A byte order mark overrides the declared charset
+    let (css, _, _) = encoding.decode(&body);
+    Ok(css.into_owned())
+}
+
+fn unsupported(reason: &str) -> InlineError {
+    std::io::Error::new(ErrorKind::Unsupported, reason).into()
+}
+
+/// Default stylesheet resolver.
+#[derive(Debug, Default)]
+pub struct DefaultStylesheetResolver;
+
+impl StylesheetResolver for DefaultStylesheetResolver {
+    #[cfg(feature = "http")]
+    fn retrieve_from_url(&self, url: &str) -> Result<String> {
+        let into_error = |error| InlineError::Network {
+            error,
+            location: url.to_string(),
+        };
+        reqwest::blocking::get(url)
+            .map_err(into_error)?
+            .text()
+            .map_err(into_error)
+    }
+
+    #[cfg(feature = "http")]
+    fn retrieve_with_policy(&self, location: &str, policy: &ResolverPolicy) -> Result<String> {
+        if !is_url(location) {
+            return StylesheetResolver::retrieve(self, location);
+        }
+        let into_error = |error| http::network_error(error, location);
+        // `reqwest::blocking::get` also creates a new client for every request
+        http::with_policy(
+            reqwest::blocking::Client::builder(),
+            policy,
+            http::DEFAULT_MAX_REDIRECTS,
+        )
+        .build()
+        .map_err(into_error)?
+        .get(location)
+        .send()
+        .map_err(into_error)?
+        .text()
+        .map_err(into_error)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncStylesheetResolver for DefaultStylesheetResolver {
+    #[cfg(feature = "http")]
+    fn retrieve_from_url<'a>(&'a self, url: &'a str) -> StylesheetFuture<'a> {
+        Box::pin(async move {
+            let into_error = |error| InlineError::Network {
+                error,
+                location: url.to_string(),
+            };
+            reqwest::get(url)
+                .await
+                .map_err(into_error)?
+                .text()
+                .await
+                .map_err(into_error)
+        })
+    }
+    #[cfg(feature = "http")]
+    fn retrieve_with_policy<'a>(
+        &'a self,
+        location: &'a str,
+        policy: &'a ResolverPolicy,
+    ) -> StylesheetFuture<'a> {
+        if !is_url(location) {
+            return AsyncStylesheetResolver::retrieve(self, location);
+        }
+        Box::pin(async move {
+            let into_error = |error| http::network_error(error, location);
+            http::with_policy_async(
+                reqwest::Client::builder(),
+                policy,
+                http::DEFAULT_MAX_REDIRECTS,
+            )
+            .build()
+            .map_err(into_error)?
+            .get(location)
+            .send()
+            .await
+            .map_err(into_error)?
+            .text()
+            .await
+            .map_err(into_error)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode_data_url;
+    use test_case::test_case;
+
+    #[test_case(
+        "data:text/css,h1%20%7B%20color%3A%20red%3B%20%7D",
+        "h1 { color: red; }"
+    )]
+    #[test_case("data:text/css;base64,aDEgeyBjb2xvcjogcmVkOyB9", "h1 { color: red; }")]
+    #[test_case("DATA:text/css;BASE64,aDEgeyBjb2xvcjogcmVkOyB9", "h1 { color: red; }")]
+    #[test_case(
+        "data:text/css;charset=utf-8,h1::before%7Bcontent:'%E2%86%92'%7D",
+        "h1::before{content:'→'}"
+    )]
+    #[test_case(
+        "data:text/css;charset=iso-8859-1,h1::before%7Bcontent:'%E9'%7D",
+        "h1::before{content:'é'}"
+    )]
+    #[test_case("data:,h1::before%7Bcontent:'%E2%86%92'%7D", "h1::before{content:'→'}"; "implied us-ascii")]
+    #[test_case("data:text/css;charset=unknown,%E2%86%92", "→")]
+    fn test_decode_data_url(url: &str, expected: &str) {
+        assert_eq!(decode_data_url(url).expect("Invalid data URL"), expected);
+    }
+
+    #[test_case(
+        "data:text/css",
+        "Invalid data URL: expected `data:[<media-type>][;base64],<data>`"
+    )]
+    #[test_case(
+        "data:text/css;base64,aDEg!",
+        "Invalid data URL: malformed base64 content"
+    )]
+    fn test_decode_data_url_error(url: &str, expected: &str) {
+        assert_eq!(
+            decode_data_url(url).expect_err("Should fail").to_string(),
+            expected
+        );
+    }
+}
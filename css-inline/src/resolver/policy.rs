@@ -0,0 +1,461 @@
+//! Restrictions on locations stylesheets are loaded from.
+use super::{is_data_url, is_url};
+use crate::InlineError;
+use std::{
+    borrow::Cow,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, ToSocketAddrs},
+    path::{Component, Path, PathBuf},
+};
+use url::{Host, Url};
+
+/// Restrictions on locations of linked and imported stylesheets.
+///
+/// Documents from untrusted sources may reference local files or internal services, e.g.
+/// `<link rel="stylesheet" href="/etc/passwd">`. Every location is checked before it is passed
+/// to the resolver, and violations are reported as [`InlineError::PolicyViolation`].
+/// Locations that are not URLs are local paths with the `file` scheme.
+///
+/// Built-in resolvers check every redirect target too. Custom resolvers that follow redirects
+/// should do the same in `StylesheetResolver::retrieve_with_policy`.
+///
+/// ```rust
+/// use css_inline::{CSSInliner, ResolverPolicy};
+///
+/// let inliner = CSSInliner::options()
+///     .resolver_policy(Some(
+///         ResolverPolicy::default()
+///             .allowed_schemes(Some(vec!["https".to_string()]))
+///             .allowed_hosts(Some(vec!["*.example.com".to_string()]))
+///             .block_private_ips(true),
+///     ))
+///     .build();
+/// let error = inliner
+///     .inline(r#"<html><head><link href="/etc/passwd" rel="stylesheet"></head></html>"#)
+///     .expect_err("Local files are not allowed");
+/// assert_eq!(error.to_string(), "Scheme `file` is not allowed: /etc/passwd");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResolverPolicy {
+    allowed_schemes: Option<Vec<String>>,
+    allowed_hosts: Option<Vec<String>>,
+    base_directory: Option<PathBuf>,
+    block_private_ips: bool,
+}
+
+impl ResolverPolicy {
+    /// Allow only locations with these URL schemes, e.g. `https` or `file`.
+    /// All schemes are allowed if it is `None`.
+    #[must_use]
+    pub fn allowed_schemes(mut self, schemes: Option<Vec<String>>) -> Self {
+        self.allowed_schemes = schemes.map(|schemes| {
+            schemes
+                .into_iter()
+                .map(|scheme| scheme.trim().to_ascii_lowercase())
+                .collect()
+        });
+        self
+    }
+
+    /// Allow only URLs with these hosts. A `*.` prefix matches all subdomains, e.g.
+    /// `*.example.com` matches `cdn.example.com`, but not `example.com`.
+    /// All hosts are allowed if it is `None`.
+    #[must_use]
+    pub fn allowed_hosts(mut self, hosts: Option<Vec<String>>) -> Self {
+        self.allowed_hosts = hosts.map(|hosts| {
+            hosts
+                .into_iter()
+                .map(|host| host.trim().to_ascii_lowercase())
+                .collect()
+        });
+        self
+    }
+
+    /// Allow only local files inside this directory. Paths with `..` components are rejected.
+    /// All paths are allowed if it is `None`.
+    #[must_use]
+    pub fn base_directory(mut self, directory: Option<PathBuf>) -> Self {
+        self.base_directory = directory;
+        self
+    }
+
+    /// Reject URLs with hosts that are or resolve to loopback, private, link-local and other
+    /// non-public IP addresses.
+    ///
+    /// IPv6 addresses with embedded IPv4 ones, like NAT64 or 6to4, are checked by the IPv4
+    /// address. Hosts that can't be resolved are rejected too. Built-in resolvers also drop
+    /// non-public addresses when they resolve host names themselves, so a DNS server under
+    /// an attacker's control can't point them to a private address after the check.
+    ///
+    /// Host names are resolved with a blocking system call in [`ResolverPolicy::check`].
+    /// [`CSSInliner::inline_async`](crate::CSSInliner::inline_async) doesn't resolve them
+    /// before the request and relies on the resolver to drop non-public addresses when
+    /// connecting, as the built-in ones do.
+    #[must_use]
+    pub fn block_private_ips(mut self, block: bool) -> Self {
+        self.block_private_ips = block;
+        self
+    }
+
+    /// Check whether a stylesheet may be loaded from the location.
+    ///
+    /// Locations are classified like in
+    /// [`StylesheetResolver::retrieve`](crate::StylesheetResolver::retrieve): anything that is not
+    /// a `data:` or an HTTP URL is a local path.
+    ///
+    /// # Errors
+    ///
+    /// [`InlineError::PolicyViolation`] if the location is not allowed.
+    pub fn check(&self, location: &str) -> Result<(), InlineError> {
+        self.check_location(location, true)
+    }
+
+    /// Like [`ResolverPolicy::check`], but without resolving host names, so it never blocks.
+    /// Only literal IP addresses are checked against private ranges.
+    #[cfg(feature = "async")]
+    pub(crate) fn check_without_lookup(&self, location: &str) -> Result<(), InlineError> {
+        self.check_location(location, false)
+    }
+
+    fn check_location(&self, location: &str, lookup: bool) -> Result<(), InlineError> {
+        if is_data_url(location) {
+            return self.check_scheme(location, "data");
+        }
+        if !is_url(location) {
+            match Url::parse(location) {
+                // E.g. `C:/styles/main.css`. The resolver reads it as a path, so such schemes
+                // are only allowed explicitly
+                Ok(url) if url.scheme() != "file" => {
+                    let is_allowed = self.allowed_schemes.as_ref().is_some_and(|schemes| {
+                        schemes.iter().any(|allowed| allowed == url.scheme())
+                    });
+                    if !is_allowed {
+                        return Err(scheme_violation(location, url.scheme()));
+                    }
+                }
+                _ => self.check_scheme(location, "file")?,
+            }
+            return self.check_path(location);
+        }
+        let Ok(url) = Url::parse(location) else {
+            return Err(violation(location, Cow::Borrowed("Invalid URL")));
+        };
+        self.check_scheme(location, url.scheme())?;
+        let Some(host) = url.host() else {
+            if self.allowed_hosts.is_some() || self.block_private_ips {
+                return Err(violation(
+                    location,
+                    Cow::Borrowed("URLs without a host are not allowed"),
+                ));
+            }
+            return Ok(());
+        };
+        if let Some(hosts) = &self.allowed_hosts {
+            let name = host.to_string();
+            if !hosts.iter().any(|allowed| host_matches(allowed, &name)) {
+                return Err(violation(
+                    location,
+                    Cow::Owned(format!("Host `{name}` is not allowed")),
+                ));
+            }
+        }
+        if self.block_private_ips {
+            let port = url.port_or_known_default().unwrap_or(0);
+            let addresses: Vec<IpAddr> = match host {
+                Host::Ipv4(address) => vec![IpAddr::V4(address)],
+                Host::Ipv6(address) => vec![IpAddr::V6(address)],
+                // Checked by the resolver when connecting
+                Host::Domain(_) if !lookup => return Ok(()),
+                Host::Domain(domain) => (domain, port)
+                    .to_socket_addrs()
+                    .map(|addresses| addresses.map(|address| address.ip()).collect())
+                    .unwrap_or_default(),
+            };
+            // Hosts that can't be checked are not allowed
+            if addresses.is_empty() {
+                return Err(violation(
+                    location,
+                    Cow::Owned(format!("Host `{host}` could not be resolved")),
+                ));
+            }
+            if let Some(address) = addresses.into_iter().find(|address| !is_public(*address)) {
+                return Err(violation(
+                    location,
+                    Cow::Owned(format!("Private IP address `{address}` is not allowed")),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether resolved addresses of hosts should be checked too.
+    #[cfg(feature = "http")]
+    pub(super) fn blocks_private_ips(&self) -> bool {
+        self.block_private_ips
+    }
+
+    fn check_scheme(&self, location: &str, scheme: &str) -> Result<(), InlineError> {
+        match &self.allowed_schemes {
+            Some(schemes) if !schemes.iter().any(|allowed| allowed == scheme) => {
+                Err(scheme_violation(location, scheme))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn check_path(&self, location: &str) -> Result<(), InlineError> {
+        let Some(base_directory) = &self.base_directory else {
+            return Ok(());
+        };
+        // The same path the resolver reads
+        let path = Path::new(location.trim_start_matches("file://"));
+        if path
+            .components()
+            .any(|component| component == Component::ParentDir)
+        {
+            return Err(violation(
+                location,
+                Cow::Borrowed("Path traversal is not allowed"),
+            ));
+        }
+        let is_inside = path.starts_with(base_directory)
+            // Symbolic links may point outside of the directory
+            && match (path.canonicalize(), base_directory.canonicalize()) {
+                (Ok(path), Ok(base_directory)) => path.starts_with(base_directory),
+                _ => true,
+            };
+        if is_inside {
+            Ok(())
+        } else {
+            Err(violation(
+                location,
+                Cow::Borrowed("Paths outside of the base directory are not allowed"),
+            ))
+        }
+    }
+}
+
+fn scheme_violation(location: &str, scheme: &str) -> InlineError {
+    violation(
+        location,
+        Cow::Owned(format!("Scheme `{scheme}` is not allowed")),
+    )
+}
+
+pub(super) fn violation(location: &str, reason: Cow<'static, str>) -> InlineError {
+    InlineError::PolicyViolation {
+        reason,
+        location: location.to_string(),
+    }
+}
+
+fn host_matches(allowed: &str, host: &str) -> bool {
+    match allowed.strip_prefix("*.") {
+        Some(domain) => host
+            .strip_suffix(domain)
+            .is_some_and(|subdomain| subdomain.len() > 1 && subdomain.ends_with('.')),
+        None => allowed == host,
+    }
+}
+
+pub(super) fn is_public(address: IpAddr) -> bool {
+    match address {
+        IpAddr::V4(address) => is_public_ipv4(address),
+        IpAddr::V6(address) => is_public_ipv6(address),
+    }
+}
+
+fn is_public_ipv4(address: Ipv4Addr) -> bool {
+    let [first, second, ..] = address.octets();
+    !(address.is_private()
+        || address.is_loopback()
+        || address.is_link_local()
+        || address.is_unspecified()
+        || address.is_documentation()
+        || address.is_multicast()
+        // "This network"
+        || first == 0
+        // Shared address space for carrier-grade NAT
+        || (first == 100 && (64..128).contains(&second))
+        // Benchmarking
+        || (first == 198 && (18..20).contains(&second))
+        // Reserved, including the broadcast address
+        || first >= 240)
+}
+
+fn is_public_ipv6(address: Ipv6Addr) -> bool {
+    let segments = address.segments();
+    // IPv4-mapped `::ffff:a.b.c.d` and deprecated IPv4-compatible `::a.b.c.d`
+    if let Some(address) = address.to_ipv4() {
+        // `::` and `::1` are in the "this network" block
+        return is_public_ipv4(address);
+    }
+    // Addresses that are translated to IPv4 ones: NAT64 `64:ff9b::/96` and 6to4 `2002::/16`
+    if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+        return is_public_ipv4(embedded_ipv4(segments[6], segments[7]));
+    }
+    if segments[0] == 0x2002 {
+        return is_public_ipv4(embedded_ipv4(segments[1], segments[2]));
+    }
+    !(address.is_loopback()
+        || address.is_unspecified()
+        || address.is_multicast()
+        // Unique local addresses
+        || (segments[0] & 0xfe00) == 0xfc00
+        // Link-local addresses
+        || (segments[0] & 0xffc0) == 0xfe80
+        // Local-use NAT64 addresses
+        || (segments[0] == 0x64 && segments[1] == 0xff9b && segments[2] == 1)
+        // Documentation
+        || (segments[0] == 0x2001 && segments[1] == 0x0db8))
+}
+
+fn embedded_ipv4(high: u16, low: u16) -> Ipv4Addr {
+    Ipv4Addr::from((u32::from(high) << 16) | u32::from(low))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{host_matches, is_public, ResolverPolicy};
+    use std::path::PathBuf;
+    use test_case::test_case;
+
+    #[test_case("example.com", "example.com", true)]
+    #[test_case("example.com", "cdn.example.com", false)]
+    #[test_case("*.example.com", "cdn.example.com", true)]
+    #[test_case("*.example.com", "example.com", false)]
+    #[test_case("*.example.com", "badexample.com", false)]
+    fn test_host_matches(allowed: &str, host: &str, expected: bool) {
+        assert_eq!(host_matches(allowed, host), expected);
+    }
+
+    #[test_case("8.8.8.8", true)]
+    #[test_case("127.0.0.1", false)]
+    #[test_case("10.1.2.3", false)]
+    #[test_case("172.16.0.1", false)]
+    #[test_case("192.168.1.1", false)]
+    #[test_case("169.254.169.254", false)]
+    #[test_case("100.64.0.1", false)]
+    #[test_case("0.0.0.0", false)]
+    #[test_case("2001:4860:4860::8888", true)]
+    #[test_case("::1", false)]
+    #[test_case("fd00::1", false)]
+    #[test_case("fe80::1", false)]
+    #[test_case("::ffff:127.0.0.1", false)]
+    #[test_case("::ffff:8.8.8.8", true)]
+    #[test_case("198.18.0.1", false)]
+    #[test_case("198.19.255.255", false)]
+    #[test_case("198.20.0.1", true)]
+    #[test_case("224.0.0.1", false)]
+    #[test_case("239.255.255.250", false)]
+    #[test_case("240.0.0.1", false)]
+    #[test_case("255.255.255.255", false)]
+    #[test_case("ff02::1", false)]
+    #[test_case("64:ff9b::10.0.0.1", false)]
+    #[test_case("64:ff9b::8.8.8.8", true)]
+    #[test_case("64:ff9b:1::1", false)]
+    #[test_case("2002:a9fe:a9fe::1", false)]
+    #[test_case("2002:0808:0808::1", true)]
+    #[test_case("::127.0.0.1", false)]
+    #[test_case("::169.254.169.254", false)]
+    #[test_case("2001:db8::1", false)]
+    fn test_is_public(address: &str, expected: bool) {
+        assert_eq!(
+            is_public(address.parse().expect("Invalid address")),
+            expected
+        );
+    }
+
+    #[test_case("/srv/styles/main.css", None)]
+    #[test_case("file:///srv/styles/main.css", None)]
+    #[test_case(
+        "/srv/styles/../secrets/key.css",
+        Some("Path traversal is not allowed: /srv/styles/../secrets/key.css")
+    )]
+    #[test_case(
+        "/etc/passwd",
+        Some("Paths outside of the base directory are not allowed: /etc/passwd")
+    )]
+    #[test_case(
+        "/srv/styles-private/main.css",
+        Some("Paths outside of the base directory are not allowed: /srv/styles-private/main.css")
+    )]
+    #[test_case(
+        "C:/Windows/win.ini",
+        Some("Scheme `c` is not allowed: C:/Windows/win.ini")
+    )]
+    #[test_case(
+        "c:\\secrets\\a.css",
+        Some("Scheme `c` is not allowed: c:\\secrets\\a.css")
+    )]
+    #[test_case(
+        "x:/../../etc/passwd",
+        Some("Scheme `x` is not allowed: x:/../../etc/passwd")
+    )]
+    #[test_case(
+        "HTTP://127.0.0.1/style.css",
+        Some("Scheme `http` is not allowed: HTTP://127.0.0.1/style.css")
+    )]
+    fn test_base_directory(location: &str, expected: Option<&str>) {
+        let policy = ResolverPolicy::default().base_directory(Some(PathBuf::from("/srv/styles")));
+        assert_eq!(
+            policy
+                .check(location)
+                .err()
+                .map(|error| error.to_string())
+                .as_deref(),
+            expected
+        );
+    }
+
+    #[test_case(
+        "C:/Windows/win.ini",
+        Some("Paths outside of the base directory are not allowed: C:/Windows/win.ini")
+    )]
+    #[test_case(
+        "x:/../../etc/passwd",
+        Some("Path traversal is not allowed: x:/../../etc/passwd")
+    )]
+    #[test_case("data:text/css,h1{}", None)]
+    fn test_base_directory_allowed_schemes(location: &str, expected: Option<&str>) {
+        let policy = ResolverPolicy::default()
+            .allowed_schemes(Some(vec![
+                "c".to_string(),
+                "x".to_string(),
+                "data".to_string(),
+            ]))
+            .base_directory(Some(PathBuf::from("/srv/styles")));
+        assert_eq!(
+            policy
+                .check(location)
+                .err()
+                .map(|error| error.to_string())
+                .as_deref(),
+            expected
+        );
+    }
+
+    #[test_case("https://example.com/main.css", None)]
+    #[test_case(
+        "data:text/css,h1{}",
+        Some("Scheme `data` is not allowed: data:text/css,h1{}")
+    )]
+    #[test_case(
+        "styles/main.css",
+        Some("Scheme `file` is not allowed: styles/main.css")
+    )]
+    #[test_case(
+        "C:/styles/main.css",
+        Some("Scheme `c` is not allowed: C:/styles/main.css")
+    )]
+    fn test_allowed_schemes(location: &str, expected: Option<&str>) {
+        let policy = ResolverPolicy::default().allowed_schemes(Some(vec!["https".to_string()]));
+        assert_eq!(
+            policy
+                .check(location)
+                .err()
+                .map(|error| error.to_string())
+                .as_deref(),
+            expected
+        );
+    }
+}
@@ -0,0 +1,194 @@
+//! Stylesheets that are parsed once and applied to many documents.
+use crate::{
+    html::{split_selector_list, Selectors, Specificity},
+    parser::{self, InvalidCss, InvalidCssKind},
+    pseudo_element_reason, selector_error_reason, source_location, InlineError,
+};
+use std::{borrow::Cow, fmt};
+
+/// CSS that is parsed and compiled once, then applied to any number of documents.
+///
+/// Useful when the same stylesheet is inlined into many documents: parsing CSS and compiling
+/// selectors happens only when the stylesheet is created, instead of on every inlining call.
+/// It is cheap to share between threads behind an [`Arc`](std::sync::Arc) and attach to
+/// [`InlineOptions::compiled_stylesheet`](crate::InlineOptions::compiled_stylesheet).
+///
+/// Its rules are applied after all other CSS, therefore they take precedence over rules with the
+/// same specificity from the document, `extra_css` or the CSS passed to `inline_fragment`.
+///
+/// ```rust
+/// use css_inline::{CSSInliner, CompiledStylesheet};
+/// use std::sync::Arc;
+///
+/// let stylesheet = Arc::new(CompiledStylesheet::new("h1 { color: blue; }"));
+/// let inliner = CSSInliner::options()
+///     .compiled_stylesheet(Some(Arc::clone(&stylesheet)))
+///     .build();
+/// let inlined = inliner.inline("<html><body><h1>Hello</h1></body></html>");
+/// assert_eq!(
+///     inlined.expect("Inlining failed"),
+///     r#"<html><head></head><body><h1 style="color: blue;">Hello</h1></body></html>"#
+/// );
+/// ```
+pub struct CompiledStylesheet {
+    pub(crate) rules: Vec<CompiledRule>,
+    /// At-rules in their source form, emitted when `keep_at_rules` is enabled.
+    pub(crate) at_rules: String,
+    /// CSS that was skipped during parsing.
+    pub(crate) skipped: Vec<SkippedCss>,
+}
+
+/// A style rule with its selector list split into individual selectors.
+pub(crate) struct CompiledRule {
+    pub(crate) selectors: Vec<CompiledSelector>,
+    /// Pairs of property name & value.
+    pub(crate) declarations: Vec<(Box<str>, Box<str>)>,
+}
+
+pub(crate) struct CompiledSelector {
+    /// The selector source, for diagnostics.
+    pub(crate) text: Box<str>,
+    /// The compiled selector with its specificity or the reason why it can not be used.
+    pub(crate) compiled: Result<(Selectors, Specificity), SkippedCss>,
+    /// The reason to skip a selector with a pseudo-element if pseudo-elements are not
+    /// materialized.
+    pub(crate) pseudo_element: Option<SkippedCss>,
+}
+
+/// A piece of CSS that can not be inlined, with its location in the stylesheet.
+pub(crate) struct SkippedCss {
+    pub(crate) kind: InvalidCssKind,
+    pub(crate) reason: Cow<'static, str>,
+    pub(crate) text: Box<str>,
+    pub(crate) line: usize,
+    pub(crate) column: usize,
+}
+
+impl SkippedCss {
+    fn new(source: &str, kind: InvalidCssKind, reason: Cow<'static, str>, text: &str) -> Self {
+        let (line, column) = source_location(source, &[], text);
+        SkippedCss {
+            kind,
+            reason,
+            text: text.into(),
+            line,
+            column,
+        }
+    }
+
+    /// The error reported in strict mode.
+    pub(crate) fn to_error(&self) -> InlineError {
+        crate::parse_error(&self.reason, self.line, self.column, &self.text)
+    }
+}
+
+impl CompiledStylesheet {
+    /// Parse the given CSS and compile its selectors.
+    ///
+    /// Parsing is lenient: CSS that can not be inlined is skipped and reported on every
+    /// inlining call, either via [`InlineReport`](crate::InlineReport) or as an error in
+    /// [`strict`](crate::InlineOptions::strict) mode.
+    #[must_use]
+    pub fn new(css: &str) -> CompiledStylesheet {
+        let mut declarations = Vec::new();
+        let mut invalid_nested = Vec::new();
+        let mut invalid = Vec::new();
+        let mut rule_list = Vec::new();
+        let mut input = cssparser::ParserInput::new(css);
+        let mut parser = cssparser::Parser::new(&mut input);
+        for rule in cssparser::StyleSheetParser::new(
+            &mut parser,
+            &mut parser::CSSRuleListParser::new(&mut declarations, &mut invalid_nested, None),
+        ) {
+            match rule {
+                Ok(rules) => rule_list.extend(rules),
+                Err((error, text)) => {
+                    invalid.push(InvalidCss::new(&error, text, InvalidCssKind::Rule));
+                }
+            }
+        }
+        invalid.append(&mut invalid_nested);
+        invalid.sort_by_key(|item| item.text.as_ptr());
+        let mut skipped = Vec::with_capacity(invalid.len());
+        for item in invalid {
+            let reason = match item.kind {
+                InvalidCssKind::AtRule => "At-rule",
+                InvalidCssKind::Rule => "Invalid rule",
+                InvalidCssKind::Declaration => "Invalid declaration",
+            };
+            skipped.push(SkippedCss::new(
+                css,
+                item.kind,
+                Cow::Borrowed(reason),
+                item.text,
+            ));
+        }
+        let rules = rule_list
+            .into_iter()
+            .map(|(selectors, (start, end))| CompiledRule {
+                selectors: split_selector_list(selectors)
+                    .into_iter()
+                    .map(|selector| {
+                        let text = selector.trim();
+                        let mut pseudo_element = None;
+                        let compiled = match Selectors::compile(selector) {
+                            Ok(compiled) => {
+                                pseudo_element = compiled.pseudo_element().map(|pseudo| {
+                                    SkippedCss::new(
+                                        css,
+                                        InvalidCssKind::Rule,
+                                        Cow::Owned(pseudo_element_reason(pseudo)),
+                                        text,
+                                    )
+                                });
+                                let specificity = compiled.specificity();
+                                Ok((compiled, specificity))
+                            }
+                            Err(error) => Err(SkippedCss::new(
+                                css,
+                                InvalidCssKind::Rule,
+                                Cow::Owned(selector_error_reason(&error)),
+                                text,
+                            )),
+                        };
+                        CompiledSelector {
+                            text: text.into(),
+                            compiled,
+                            pseudo_element,
+                        }
+                    })
+                    .collect(),
+                declarations: declarations[start..end]
+                    .iter()
+                    .map(|(name, value)| (name.as_ref().into(), (*value).into()))
+                    .collect(),
+            })
+            .collect();
+        // A separate pass, so that at-rules are serialized the same way as in `<style>` tags
+        let mut at_rules = String::new();
+        let mut input = cssparser::ParserInput::new(css);
+        let mut parser = cssparser::Parser::new(&mut input);
+        for _ in cssparser::StyleSheetParser::new(
+            &mut parser,
+            &mut parser::AtRuleFilteringParser::new(
+                &mut Vec::new(),
+                &mut Vec::new(),
+                &mut at_rules,
+                None,
+            ),
+        ) {}
+        CompiledStylesheet {
+            rules,
+            at_rules,
+            skipped,
+        }
+    }
+}
+
+impl fmt::Debug for CompiledStylesheet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompiledStylesheet")
+            .field("rules", &self.rules.len())
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,310 @@
+//! Protection of template syntax (e.g. `{{ name }}` or `{% for %}`) from HTML parsing.
+//!
+//! Before parsing, every delimited template segment is replaced with a placeholder that the
+//! HTML parser keeps in place. Between elements, the placeholder is wrapped in a comment, so
+//! it is not moved out of tables (foster parenting). After serialization, placeholders are
+//! replaced with the original segments verbatim.
+use crate::InlineError;
+use memchr::memmem;
+use std::io::Write;
+
+/// Placeholders are made of Private Use Area characters, which do not appear in real content.
+const PLACEHOLDER_START: &str = "\u{E000}";
+const PLACEHOLDER_END: &str = "\u{E001}";
+
+/// Elements whose content is not parsed as HTML.
+const RAW_TEXT_ELEMENTS: [&str; 9] = [
+    "iframe", "noembed", "noframes", "noscript", "script", "style", "textarea", "title", "xmp",
+];
+
+/// Where a template segment is located in the HTML source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Context {
+    /// Between elements, the placeholder is a comment.
+    Text,
+    /// Inside a tag, the placeholder is an attribute name.
+    Tag,
+    /// Inside an attribute value or a raw text element.
+    Value,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum State<'h> {
+    Data,
+    Comment,
+    Tag {
+        quote: Option<u8>,
+        raw_text: Option<&'h str>,
+    },
+    RawText(&'h str),
+}
+
+/// Original template segments, indexed by their placeholder numbers.
+#[derive(Debug)]
+pub(crate) struct Templates<'h> {
+    segments: Vec<(&'h str, Context)>,
+}
+
+/// Whether the value contains a template placeholder.
+#[inline]
+pub(crate) fn contains_placeholder(value: &str) -> bool {
+    value.contains(PLACEHOLDER_START)
+}
+
+/// Replace template segments enclosed in `delimiters` with placeholders.
+///
+/// Returns `None` if there are no template segments.
+#[allow(clippy::arithmetic_side_effects)]
+pub(crate) fn protect<'h>(
+    html: &'h str,
+    delimiters: &[(String, String)],
+) -> Option<(String, Templates<'h>)> {
+    let bytes = html.as_bytes();
+    let mut output = String::new();
+    let mut segments = Vec::new();
+    let mut state = State::Data;
+    let mut last = 0;
+    let mut idx = 0;
+    while idx < bytes.len() {
+        if !matches!(state, State::Comment) {
+            if let Some(end) = find_template(bytes, idx, delimiters) {
+                let context = match state {
+                    State::Data => Context::Text,
+                    State::Tag { quote: None, .. } => Context::Tag,
+                    _ => Context::Value,
+                };
+                output.push_str(&html[last..idx]);
+                push_placeholder(&mut output, segments.len(), context);
+                segments.push((&html[idx..end], context));
+                idx = end;
+                last = end;
+                continue;
+            }
+        }
+        let rest = &bytes[idx..];
+        match state {
+            State::Data if rest.starts_with(b"<!--") => {
+                state = State::Comment;
+                idx += 4;
+                continue;
+            }
+            State::Data if rest.first() == Some(&b'<') => {
+                let name_start = if rest.get(1) == Some(&b'/') { 2 } else { 1 };
+                if let Some(first) = rest.get(name_start) {
+                    if first.is_ascii_alphabetic() || matches!(first, b'!' | b'?') {
+                        let name_end = rest[name_start..]
+                            .iter()
+                            .position(|byte| {
+                                byte.is_ascii_whitespace() || matches!(byte, b'/' | b'>')
+                            })
+                            .map_or(rest.len(), |position| name_start + position);
+                        let name = &html[idx + name_start..idx + name_end];
+                        let raw_text = (name_start == 1)
+                            .then(|| {
+                                RAW_TEXT_ELEMENTS
+                                    .into_iter()
+                                    .find(|element| element.eq_ignore_ascii_case(name))
+                            })
+                            .flatten();
+                        state = State::Tag {
+                            quote: None,
+                            raw_text,
+                        };
+                        idx += name_end;
+                        continue;
+                    }
+                }
+            }
+            State::Comment if rest.starts_with(b"-->") => {
+                state = State::Data;
+                idx += 3;
+                continue;
+            }
+            State::Tag { quote, raw_text } => match (quote, rest[0]) {
+                (Some(quote), byte) if byte == quote => {
+                    state = State::Tag {
+                        quote: None,
+                        raw_text,
+                    };
+                }
+                (None, byte @ (b'"' | b'\'')) => {
+                    state = State::Tag {
+                        quote: Some(byte),
+                        raw_text,
+                    };
+                }
+                (None, b'>') => state = raw_text.map_or(State::Data, State::RawText),
+                _ => {}
+            },
+            State::RawText(name)
+                if rest.starts_with(b"</")
+                    && rest
+                        .get(2..2 + name.len())
+                        .is_some_and(|tag| tag.eq_ignore_ascii_case(name.as_bytes())) =>
+            {
+                state = State::Tag {
+                    quote: None,
+                    raw_text: None,
+                };
+                idx += 2 + name.len();
+                continue;
+            }
+            _ => {}
+        }
+        idx += 1;
+    }
+    if segments.is_empty() {
+        return None;
+    }
+    output.push_str(&html[last..]);
+    Some((output, Templates { segments }))
+}
+
+fn push_placeholder(output: &mut String, number: usize, context: Context) {
+    let placeholder = format!("{PLACEHOLDER_START}{number}{PLACEHOLDER_END}");
+    match context {
+        Context::Text => {
+            output.push_str("<!--");
+            output.push_str(&placeholder);
+            output.push_str("-->");
+        }
+        // Separate it from the tag name and other attributes
+        Context::Tag => {
+            output.push(' ');
+            output.push_str(&placeholder);
+            output.push(' ');
+        }
+        Context::Value => output.push_str(&placeholder),
+    }
+}
+
+/// Find the end of a template segment starting at `idx`.
+#[allow(clippy::arithmetic_side_effects)]
+fn find_template(bytes: &[u8], idx: usize, delimiters: &[(String, String)]) -> Option<usize> {
+    delimiters.iter().find_map(|(open, close)| {
+        if open.is_empty() || close.is_empty() || !bytes[idx..].starts_with(open.as_bytes()) {
+            return None;
+        }
+        let content_start = idx + open.len();
+        memmem::find(&bytes[content_start..], close.as_bytes())
+            .map(|position| content_start + position + close.len())
+    })
+}
+
+impl Templates<'_> {
+    /// Write the serialized document with placeholders replaced by the original segments.
+    #[allow(clippy::arithmetic_side_effects)]
+    pub(crate) fn restore<W: Write>(
+        &self,
+        output: &[u8],
+        writer: &mut W,
+    ) -> Result<(), InlineError> {
+        let mut last = 0;
+        for start in memmem::find_iter(output, PLACEHOLDER_START) {
+            if start < last {
+                continue;
+            }
+            let number_start = start + PLACEHOLDER_START.len();
+            let number_end = output[number_start..]
+                .iter()
+                .position(|byte| !byte.is_ascii_digit())
+                .map_or(output.len(), |position| number_start + position);
+            if !output[number_end..].starts_with(PLACEHOLDER_END.as_bytes()) {
+                continue;
+            }
+            let Some((segment, context)) = std::str::from_utf8(&output[number_start..number_end])
+                .ok()
+                .and_then(|number| number.parse::<usize>().ok())
+                .and_then(|number| self.segments.get(number))
+            else {
+                continue;
+            };
+            let mut replace_start = start;
+            let mut replace_end = number_end + PLACEHOLDER_END.len();
+            match context {
+                Context::Text => {
+                    if output[last..start].ends_with(b"<!--")
+                        && output[replace_end..].starts_with(b"-->")
+                    {
+                        replace_start -= 4;
+                        replace_end += 3;
+                    }
+                }
+                // The parser gives placeholders in tags an empty value
+                Context::Tag => {
+                    if output[replace_end..].starts_with(b"=\"\"") {
+                        replace_end += 3;
+                    }
+                }
+                Context::Value => {}
+            }
+            writer.write_all(&output[last..replace_start])?;
+            writer.write_all(segment.as_bytes())?;
+            last = replace_end;
+        }
+        writer.write_all(&output[last..])?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::protect;
+    use test_case::test_case;
+
+    fn delimiters() -> Vec<(String, String)> {
+        vec![
+            ("{{".to_string(), "}}".to_string()),
+            ("{%".to_string(), "%}".to_string()),
+        ]
+    }
+
+    #[test_case(
+        "<p>{{ name }}</p>",
+        "<p><!--\u{E000}0\u{E001}--></p>";
+        "text"
+    )]
+    #[test_case(
+        r#"<a href="{{ url }}" {% if x %}target="_blank"{% endif %}>"#,
+        "<a href=\"\u{E000}0\u{E001}\"  \u{E000}1\u{E001} target=\"_blank\" \u{E000}2\u{E001} >";
+        "tag"
+    )]
+    #[test_case(
+        "<style>h1 { color: {{ color }}; }</style>{{ x }}",
+        "<style>h1 { color: \u{E000}0\u{E001}; }</style><!--\u{E000}1\u{E001}-->";
+        "raw text"
+    )]
+    #[test_case(
+        "<!-- {{ x }} --><p title='a > {{ b }}'>",
+        "<!-- {{ x }} --><p title='a > \u{E000}0\u{E001}'>";
+        "comments and quotes"
+    )]
+    fn test_protect(html: &str, expected: &str) {
+        let (protected, _) = protect(html, &delimiters()).expect("Templates are not found");
+        assert_eq!(protected, expected);
+    }
+
+    #[test_case("<p>No templates</p>")]
+    #[test_case("<p>{{ unclosed</p>")]
+    fn test_no_templates(html: &str) {
+        assert!(protect(html, &delimiters()).is_none());
+    }
+
+    #[test]
+    fn test_restore() {
+        let html = r#"<a href="{{ url }}" {% if x %}>{{ name }}</a>"#;
+        let (_, templates) = protect(html, &delimiters()).expect("Templates are not found");
+        let mut buffer = Vec::new();
+        templates
+            .restore(
+                "<a href=\"\u{E000}0\u{E001}\" \u{E000}1\u{E001}=\"\"><!--\u{E000}2\u{E001}--></a>"
+                    .as_bytes(),
+                &mut buffer,
+            )
+            .expect("Failed to restore");
+        assert_eq!(
+            String::from_utf8(buffer).expect("Invalid UTF-8"),
+            r#"<a href="{{ url }}" {% if x %}>{{ name }}</a>"#
+        );
+    }
+}
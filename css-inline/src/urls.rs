@@ -0,0 +1,247 @@
+//! Rewriting of relative URLs.
+use crate::templates;
+use cssparser::{ParseError, Parser, ParserInput, Token};
+use std::{borrow::Cow, ops::Range};
+use url::Url;
+
+/// Resolve a relative URL against `base`.
+///
+/// Returns `None` for absolute URLs, fragment-only references like `#icon`, templates and
+/// unresolvable ones.
+pub(crate) fn absolutize(url: &str, base: &Url) -> Option<String> {
+    let url = url.trim();
+    if url.is_empty()
+        || url.starts_with('#')
+        || templates::contains_placeholder(url)
+        || Url::parse(url).is_ok()
+    {
+        return None;
+    }
+    base.join(url).ok().map(String::from)
+}
+
+/// Resolve relative URLs of image candidates in a `srcset` attribute against `base`.
+///
+/// Returns `None` if there are no relative URLs.
+pub(crate) fn absolutize_srcset(srcset: &str, base: &Url) -> Option<String> {
+    let mut output = String::with_capacity(srcset.len());
+    let mut changed = false;
+    let mut rest = srcset;
+    loop {
+        // Separators between candidates
+        let start = rest
+            .find(|c: char| !c.is_ascii_whitespace() && c != ',')
+            .unwrap_or(rest.len());
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if rest.is_empty() {
+            break;
+        }
+        let url_end = rest
+            .find(|c: char| c.is_ascii_whitespace())
+            .unwrap_or(rest.len());
+        // Trailing commas are not a part of the URL and mean there are no descriptors
+        let url = rest[..url_end].trim_end_matches(',');
+        if let Some(absolute) = absolutize(url, base) {
+            output.push_str(&absolute);
+            changed = true;
+        } else {
+            output.push_str(url);
+        }
+        rest = &rest[url.len()..];
+        let descriptors_end = if url.len() < url_end {
+            0
+        } else {
+            rest.find(',').unwrap_or(rest.len())
+        };
+        output.push_str(&rest[..descriptors_end]);
+        rest = &rest[descriptors_end..];
+    }
+    changed.then_some(output)
+}
+
+#[inline]
+fn has_url_function(css: &str) -> bool {
+    css.as_bytes()
+        .windows(4)
+        .any(|window| window.eq_ignore_ascii_case(b"url("))
+}
+
+/// A `url()` function rewritten by [`absolutize_css_urls`]: its byte range in the output and
+/// its original text.
+pub(crate) type RewrittenUrl<'c> = (Range<usize>, &'c str);
+
+/// Resolve relative URLs in `url()` functions against `base`.
+pub(crate) fn absolutize_css_urls<'c>(
+    css: &'c str,
+    base: &Url,
+) -> (Cow<'c, str>, Vec<RewrittenUrl<'c>>) {
+    if !has_url_function(css) {
+        return (Cow::Borrowed(css), Vec::new());
+    }
+    let mut input = ParserInput::new(css);
+    let mut parser = Parser::new(&mut input);
+    let mut replacements = Vec::new();
+    collect_url_replacements(&mut parser, base, &mut replacements);
+    if replacements.is_empty() {
+        return (Cow::Borrowed(css), Vec::new());
+    }
+    let mut output = String::with_capacity(css.len());
+    let mut rewritten = Vec::with_capacity(replacements.len());
+    let mut last = 0;
+    for (range, replacement) in replacements {
+        output.push_str(&css[last..range.start]);
+        let start = output.len();
+        output.push_str(&replacement);
+        rewritten.push((start..output.len(), &css[range.clone()]));
+        last = range.end;
+    }
+    output.push_str(&css[last..]);
+    (Cow::Owned(output), rewritten)
+}
+
+fn collect_url_replacements(
+    parser: &mut Parser<'_, '_>,
+    base: &Url,
+    replacements: &mut Vec<(Range<usize>, String)>,
+) {
+    loop {
+        let start = parser.position().byte_index();
+        let Ok(token) = parser.next_including_whitespace_and_comments() else {
+            break;
+        };
+        match token.clone() {
+            Token::UnquotedUrl(url) => {
+                if let Some(url) = absolutize(&url, base) {
+                    let range = start..parser.position().byte_index();
+                    replacements.push((range, serialize_url(&url)));
+                }
+            }
+            Token::Function(name) if name.eq_ignore_ascii_case("url") => {
+                let url = parser.parse_nested_block(|parser| {
+                    Ok::<_, ParseError<'_, ()>>(parser.expect_string()?.clone())
+                });
+                if let Some(url) = url.ok().and_then(|url| absolutize(&url, base)) {
+                    let range = start..parser.position().byte_index();
+                    replacements.push((range, serialize_url(&url)));
+                }
+            }
+            Token::Function(_)
+            | Token::ParenthesisBlock
+            | Token::SquareBracketBlock
+            | Token::CurlyBracketBlock => {
+                let _ = parser.parse_nested_block(|parser| {
+                    collect_url_replacements(parser, base, replacements);
+                    Ok::<_, ParseError<'_, ()>>(())
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+fn serialize_url(url: &str) -> String {
+    if url.bytes().any(|byte| {
+        matches!(byte, b'(' | b')' | b'"' | b'\'' | b'\\') || byte.is_ascii_whitespace()
+    }) {
+        format!(
+            "url(\"{}\")",
+            url.replace('\\', "\\\\").replace('"', "\\\"")
+        )
+    } else {
+        format!("url({url})")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{absolutize_css_urls, absolutize_srcset};
+    use test_case::test_case;
+    use url::Url;
+
+    #[test_case("h1 { color: red; }", "h1 { color: red; }"; "no urls")]
+    #[test_case(
+        "h1 { background: url(../img/bg.png) no-repeat; }",
+        "h1 { background: url(https://cdn.example.com/img/bg.png) no-repeat; }";
+        "unquoted"
+    )]
+    #[test_case(
+        "h1 { background-image: URL('bg.png'); }",
+        "h1 { background-image: url(https://cdn.example.com/css/bg.png); }";
+        "quoted"
+    )]
+    #[test_case(
+        "h1 { background-image: url(\"a b.png\"); }",
+        "h1 { background-image: url(https://cdn.example.com/css/a%20b.png); }";
+        "encoded"
+    )]
+    #[test_case(
+        "h1 { background: url(//other.com/a.png), url(/a.png); }",
+        "h1 { background: url(https://other.com/a.png), url(https://cdn.example.com/a.png); }";
+        "multiple"
+    )]
+    #[test_case(
+        "@font-face { src: url(font.woff2) format('woff2'); }",
+        "@font-face { src: url(https://cdn.example.com/css/font.woff2) format('woff2'); }";
+        "at-rule"
+    )]
+    #[test_case(
+        "h1 { background: url(data:image/png;base64,iVBORw0KGgo=); filter: url(#blur); }",
+        "h1 { background: url(data:image/png;base64,iVBORw0KGgo=); filter: url(#blur); }";
+        "absolute and fragments"
+    )]
+    #[test_case(
+        "h1 { background: image-set(url(a.png) 1x); }",
+        "h1 { background: image-set(url(https://cdn.example.com/css/a.png) 1x); }";
+        "nested"
+    )]
+    #[test_case(
+        "h1 { content: 'url(a.png)'; }",
+        "h1 { content: 'url(a.png)'; }";
+        "string"
+    )]
+    fn test_absolutize_css_urls(css: &str, expected: &str) {
+        let base = Url::parse("https://cdn.example.com/css/main.css").expect("Invalid URL");
+        assert_eq!(absolutize_css_urls(css, &base).0, expected);
+    }
+
+    #[test]
+    fn test_absolutize_css_urls_rewritten() {
+        let base = Url::parse("https://cdn.example.com/css/main.css").expect("Invalid URL");
+        let (css, rewritten) = absolutize_css_urls(
+            "h1 { background: url( a.png ), url(#x), url('b.png'); }",
+            &base,
+        );
+        let rewritten: Vec<_> = rewritten
+            .into_iter()
+            .map(|(range, original)| (&css[range], original))
+            .collect();
+        assert_eq!(
+            rewritten,
+            [
+                ("url(https://cdn.example.com/css/a.png)", "url( a.png )"),
+                ("url(https://cdn.example.com/css/b.png)", "url('b.png')"),
+            ]
+        );
+    }
+
+    #[test_case("a.png", Some("https://cdn.example.com/css/a.png"))]
+    #[test_case(
+        "a.png 1x, /b.png 2x",
+        Some("https://cdn.example.com/css/a.png 1x, https://cdn.example.com/b.png 2x")
+    )]
+    #[test_case(
+        " a.png  480w ,b.png, c.png,, d.png 2x",
+        Some(" https://cdn.example.com/css/a.png  480w ,https://cdn.example.com/css/b.png, https://cdn.example.com/css/c.png,, https://cdn.example.com/css/d.png 2x")
+    )]
+    #[test_case(
+        "https://other.com/a.png 1x, b.png 2x",
+        Some("https://other.com/a.png 1x, https://cdn.example.com/css/b.png 2x")
+    )]
+    #[test_case("https://other.com/a.png 1x", None)]
+    #[test_case("", None)]
+    fn test_absolutize_srcset(srcset: &str, expected: Option<&str>) {
+        let base = Url::parse("https://cdn.example.com/css/main.css").expect("Invalid URL");
+        assert_eq!(absolutize_srcset(srcset, &base).as_deref(), expected);
+    }
+}
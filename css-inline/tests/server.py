@@ -1,21 +1,59 @@
-from http.server import HTTPServer, BaseHTTPRequestHandler
+import time
+from collections import Counter
+from http.server import ThreadingHTTPServer, BaseHTTPRequestHandler
+from urllib.parse import unquote
+
+# Number of requests per path, for endpoints that fail before succeeding
+REQUESTS = Counter()
 
 
 class Handler(BaseHTTPRequestHandler):
     def do_GET(self):
+        REQUESTS[self.path] += 1
         if self.path == "/external.css":
-            self.send_response(200)
-            self.send_header("Content-Type", "text/css")
+            self.send_css(b"h1 { color: blue; }")
+        elif self.path == "/redirect.css":
+            self.send_response(302)
+            self.send_header("Location", "/external.css")
+            self.end_headers()
+        elif self.path.startswith("/redirect-to?"):
+            self.send_response(302)
+            self.send_header("Location", unquote(self.path.split("?", 1)[1]))
             self.end_headers()
-            self.wfile.write(b"h1 { color: blue; }")
+        elif self.path == "/large.css":
+            self.send_css(b"h1 { color: blue; }" + b" " * 1024)
+        elif self.path == "/headers.css":
+            color = self.headers.get("X-Color", "black")
+            user_agent = self.headers.get("User-Agent", "")
+            self.send_css(f'h1 {{ color: {color}; font-family: "{user_agent}"; }}'.encode())
+        elif self.path == "/latin1.css":
+            self.send_css("h1::before { content: 'é'; }".encode("latin-1"), "iso-8859-1")
+        elif self.path == "/slow.css":
+            time.sleep(2)
+            self.send_css(b"h1 { color: blue; }")
+        elif self.path.startswith("/flaky.css"):
+            # Every path, including its query, fails twice
+            if REQUESTS[self.path] <= 2:
+                self.send_response(503)
+                self.end_headers()
+            else:
+                self.send_css(b"h1 { color: blue; }")
         else:
             self.send_response(404)
             self.end_headers()
 
+    def send_css(self, body, charset=None):
+        self.send_response(200)
+        content_type = f"text/css; charset={charset}" if charset else "text/css"
+        self.send_header("Content-Type", content_type)
+        self.send_header("Content-Length", str(len(body)))
+        self.end_headers()
+        self.wfile.write(body)
+
     def log_message(self, format, *args):
         pass  # Suppress logging
 
 
 if __name__ == "__main__":
-    server = HTTPServer(("0.0.0.0", 1234), Handler)
+    server = ThreadingHTTPServer(("0.0.0.0", 1234), Handler)
     server.serve_forever()
@@ -306,6 +306,167 @@ pub mod tests {
             .stdout("<html><head><style>h2 { color: red; }</style></head><body><h1 style=\"color: blue;\">Test</h1></body></html>");
     }
 
+    #[test]
+    fn strict() {
+        css_inline()
+            .write_stdin(r#"<html><head><style>h1 { colr red; }</style></head><body><h1>Test</h1></body></html>"#)
+            .arg("--strict")
+            .assert()
+            .failure()
+            .stderr("Status: ERROR\nDetails: Invalid declaration at line 1, column 6: colr red;\n");
+    }
+
+    #[test]
+    fn resolve_css_variables() {
+        css_inline()
+            .write_stdin(r#"<html><head><style>:root { --brand: red; } h1 { color: var(--brand); }</style></head><body><h1>Test</h1></body></html>"#)
+            .arg("--resolve-css-variables")
+            .assert()
+            .success()
+            .stdout("<html><head></head><body><h1 style=\"color: red;\">Test</h1></body></html>");
+    }
+
+    #[cfg(feature = "file")]
+    #[test]
+    fn follow_imports() {
+        css_inline()
+            .write_stdin(r#"<html><head><style>@import url(tests/external.css);</style></head><body><h1>Test</h1></body></html>"#)
+            .arg("--follow-imports")
+            .assert()
+            .success()
+            .stdout("<html><head></head><body><h1 style=\"color: blue;\">Test</h1></body></html>");
+    }
+
+    #[test]
+    fn absolutize_urls() {
+        css_inline()
+            .write_stdin(r#"<html><head></head><body><a href="page.html">Page</a></body></html>"#)
+            .arg("--absolutize-urls")
+            .arg("--base-url=https://example.com/emails/")
+            .assert()
+            .success()
+            .stdout("<html><head></head><body><a href=\"https://example.com/emails/page.html\">Page</a></body></html>");
+    }
+
+    #[test]
+    fn attribute_mappings() {
+        css_inline()
+            .write_stdin(r#"<html><head><style>table { background-color: red; } td { padding: 4px; text-align: center; }</style></head><body><table><tr><td>A</td></tr></table></body></html>"#)
+            .arg("--attribute-mappings=bgcolor, cellpadding")
+            .assert()
+            .success()
+            .stdout("<html><head></head><body><table bgcolor=\"red\" cellpadding=\"4\" style=\"background-color: red;\"><tbody><tr><td style=\"padding: 4px;text-align: center;\">A</td></tr></tbody></table></body></html>");
+    }
+
+    #[test]
+    fn invalid_attribute_mappings() {
+        css_inline()
+            .arg("--attribute-mappings=bgcolor,colour")
+            .assert()
+            .failure()
+            .stderr("Failed to parse value 'bgcolor,colour' for flag 'attribute-mappings': unknown attribute 'colour'\n");
+    }
+
+    #[test]
+    fn xhtml() {
+        css_inline()
+            .write_stdin(r#"<html><head><style>p { color: red; }</style></head><body><p>A<br>B</p></body></html>"#)
+            .arg("--xhtml")
+            .assert()
+            .success()
+            .stdout("<html xmlns=\"http://www.w3.org/1999/xhtml\"><head></head><body><p style=\"color: red;\">A<br />B</p></body></html>");
+    }
+
+    #[test]
+    fn remove_conditional_comments() {
+        css_inline()
+            .write_stdin(
+                r#"<html><head></head><body><!--[if mso]><p>Outlook</p><![endif]--></body></html>"#,
+            )
+            .arg("--remove-conditional-comments")
+            .assert()
+            .success()
+            .stdout("<html><head></head><body></body></html>");
+    }
+
+    #[test]
+    fn keep_dynamic_rules() {
+        css_inline()
+            .write_stdin(
+                r#"<html><head><style>a { color: red; } a:hover { color: blue; }</style></head><body><a href="/">Home</a></body></html>"#,
+            )
+            .arg("--keep-dynamic-rules")
+            .assert()
+            .success()
+            .stdout(r#"<html><head><style>a:hover { color: blue; }</style></head><body><a href="/" style="color: red;">Home</a></body></html>"#);
+    }
+
+    #[test]
+    fn materialize_pseudo_elements() {
+        css_inline()
+            .write_stdin(
+                r#"<html><head><style>li::before { content: "- "; }</style></head><body><ul><li>A</li></ul></body></html>"#,
+            )
+            .arg("--materialize-pseudo-elements")
+            .assert()
+            .success()
+            .stdout("<html><head></head><body><ul><li><span>- </span>A</li></ul></body></html>");
+    }
+
+    #[test]
+    #[cfg(feature = "http")]
+    fn http_resolver_flags() {
+        css_inline()
+            .write_stdin(
+                r#"<html><head><link href="http://127.0.0.1:1234/headers.css" rel="stylesheet"></head><body><h1>Hello</h1></body></html>"#,
+            )
+            .args([
+                "--load-remote-stylesheets=true",
+                "--http-header",
+                "X-Color: red",
+                "--http-user-agent=css-inline-test",
+                "--http-connect-timeout=5",
+                "--http-read-timeout=5",
+                "--http-max-body-size=1024",
+                "--http-max-redirects=0",
+                "--http-retries=1",
+                "--http-retry-backoff=10",
+            ])
+            .assert()
+            .success()
+            .stdout(r#"<html><head></head><body><h1 style="color: red;font-family: 'css-inline-test';">Hello</h1></body></html>"#);
+    }
+
+    #[test]
+    #[cfg(feature = "http")]
+    fn http_resolver_only_with_http_flags() {
+        let html = r#"<html><head><link href="http://127.0.0.1:1234/missing.css" rel="stylesheet"></head><body><h1>Hello</h1></body></html>"#;
+        // The default resolver does not check the response status
+        css_inline()
+            .write_stdin(html)
+            .arg("--load-remote-stylesheets=true")
+            .assert()
+            .success()
+            .stdout("<html><head></head><body><h1>Hello</h1></body></html>");
+        css_inline()
+            .write_stdin(html)
+            .args(["--load-remote-stylesheets=true", "--http-retries=0"])
+            .assert()
+            .failure();
+    }
+
+    #[test]
+    #[cfg(feature = "http")]
+    fn invalid_http_header() {
+        css_inline()
+            .arg("--http-header=X-Color")
+            .assert()
+            .failure()
+            .stderr(
+                "Failed to parse value 'X-Color' for flag 'http-header': expected 'Name: value'\n",
+            );
+    }
+
     #[test]
     fn unknown_short_flag() {
         css_inline()
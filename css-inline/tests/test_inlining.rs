@@ -3,7 +3,10 @@ use std::{error::Error, sync::Arc};
 #[macro_use]
 mod utils;
 
-use css_inline::{inline, CSSInliner, InlineOptions, Url};
+use css_inline::{
+    inline, AttributeMapping, CSSInliner, ColorScheme, CompiledStylesheet, InlineOptions,
+    MediaEnvironment, MediaType, PropertyPropagation, Url,
+};
 use test_case::test_case;
 
 #[cfg(not(feature = "file"))]
@@ -158,8 +161,8 @@ fn specificity_same_selector() {
     padding-left: 12px;
 }"#,
         body = r#"<a class="test-class">Test</a>"#,
-        // Then the final style should come from the more specific selector
-        expected = r#"<a class="test-class" style="padding-top: 15px;padding: 10px;padding-left: 12px;">Test</a>"#
+        // Then `padding` overrides the preceding `padding-top`, and `padding-left` goes after it
+        expected = r#"<a class="test-class" style="padding: 10px;padding-left: 12px;">Test</a>"#
     )
 }
 
@@ -194,6 +197,78 @@ fn specificity_merge_with_existing_style() {
     )
 }
 
+#[test_case(
+    "#test { margin: 0; } h1 { margin-top: 10px; }",
+    r#"<h1 id="test" style="margin: 0;"></h1>"#;
+    "shorthand with higher specificity"
+)]
+#[test_case(
+    "h1 { margin: 0; } #test { margin-top: 10px; }",
+    r#"<h1 id="test" style="margin: 0;margin-top: 10px;"></h1>"#;
+    "longhand with higher specificity"
+)]
+#[test_case(
+    "h1 { margin-top: 10px; } h1 { margin: 0; }",
+    r#"<h1 id="test" style="margin: 0;"></h1>"#;
+    "shorthand later in source order"
+)]
+#[test_case(
+    "h1 { margin: 0; } h1 { margin-top: 10px; }",
+    r#"<h1 id="test" style="margin: 0;margin-top: 10px;"></h1>"#;
+    "longhand later in source order"
+)]
+#[test_case(
+    "#test { margin: 0; } h1 { margin-top: 10px !important; }",
+    r#"<h1 id="test" style="margin-top: 10px !important;margin: 0;"></h1>"#;
+    "important longhand"
+)]
+#[test_case(
+    "#test { border-color: red; } h1 { border: 1px solid blue; }",
+    r#"<h1 id="test" style="border: 1px solid blue;border-color: red;"></h1>"#;
+    "nested shorthands"
+)]
+#[test_case(
+    "#test { border-top: 1px solid blue; } h1 { border-color: red; }",
+    r#"<h1 id="test" style="border-color: red;border-top: 1px solid blue;"></h1>"#;
+    "partially overlapping shorthands"
+)]
+#[test_case(
+    "#test { font: 12px serif; } h1 { line-height: 2; font-weight: bold; }",
+    r#"<h1 id="test" style="font: 12px serif;"></h1>"#;
+    "font"
+)]
+#[test_case(
+    "#test { background: red; } h1 { background-image: url(a.png); color: blue; }",
+    r#"<h1 id="test" style="color: blue;background: red;"></h1>"#;
+    "background"
+)]
+fn shorthand_cascade(style: &str, expected: &str) {
+    assert_inlined!(
+        style = style,
+        body = r#"<h1 id="test"></h1>"#,
+        expected = expected
+    )
+}
+
+#[test]
+fn shorthand_in_existing_style() {
+    assert_inlined!(
+        style = "h1 { margin-top: 10px; padding-top: 5px !important; color: red; }",
+        body = r#"<h1 style="margin: 0;padding: 0"></h1>"#,
+        expected =
+            r#"<h1 style="padding-top: 5px !important;color: red;margin: 0;padding: 0"></h1>"#
+    )
+}
+
+#[test]
+fn important_shorthand_in_existing_style() {
+    assert_inlined!(
+        style = "h1 { margin-top: 10px !important; }",
+        body = r#"<h1 style="margin: 0 !important"></h1>"#,
+        expected = r#"<h1 style="margin: 0 !important"></h1>"#
+    )
+}
+
 #[test]
 fn overlap_styles() {
     // When two selectors match the same element
@@ -1541,6 +1616,17 @@ h2 { color: red; }
     );
 }
 
+#[test]
+fn remove_inlined_selectors_nested_selector_lists() {
+    let inliner = CSSInliner::options().remove_inlined_selectors(true).build();
+    let html = r#"<html><head><style>:is(h1, h2) > a, a:not(.x, .y), [title="a,b"] { color: blue; }</style></head><body><h1><a class="x">Test</a></h1></body></html>"#;
+    let result = inliner.inline(html).unwrap();
+    assert_eq!(
+        result,
+        r#"<html><head><style>a:not(.x, .y), [title="a,b"] { color: blue; }</style></head><body><h1><a class="x" style="color: blue;">Test</a></h1></body></html>"#
+    );
+}
+
 #[test]
 fn remove_inlined_selectors_multiple_blocks() {
     let inliner = CSSInliner::options().remove_inlined_selectors(true).build();
@@ -2167,25 +2253,2271 @@ fn ignore_shorthand_dimensions() {
     assert!(!result.contains(r#" width=""#));
 }
 
-// Fragments with leading/trailing whitespace were silently returning only the whitespace.
-// See GH-692
-#[test_case(" <h1>Hello World!</h1>", " <h1 style=\"color: blue;\">Hello World!</h1>"; "one-space")]
-#[test_case("\n<h1>Hello World!</h1>", "\n<h1 style=\"color: blue;\">Hello World!</h1>"; "newline")]
-#[test_case("  <h1>Hello World!</h1>  ", "  <h1 style=\"color: blue;\">Hello World!</h1>  "; "two-spaces")]
-fn inline_fragment_surrounding_whitespace(input: &str, expected: &str) {
-    let inlined = css_inline::inline_fragment(input, "h1 { color: blue; }").unwrap();
-    assert_eq!(inlined, expected);
+#[test]
+fn attribute_mappings() {
+    let inliner = CSSInliner::options()
+        .attribute_mappings(AttributeMapping::ALL.to_vec())
+        .build();
+    let html = r#"<html><head><style>table { border: 1px solid #000; border-spacing: 0; width: 100%; } td { background-color: #FFCC00; text-align: Center; vertical-align: top; padding: 4px; } p { text-align: right; background-color: red; }</style></head><body><table><tr><td>A</td></tr></table><p>B</p></body></html>"#;
+    assert_eq!(
+        inliner.inline(html).expect("Inlining failed"),
+        r##"<html><head></head><body><table width="100%" cellspacing="0" cellpadding="4" border="1" style="border: 1px solid #000;border-spacing: 0;width: 100%;"><tbody><tr><td bgcolor="#FFCC00" align="center" valign="top" style="background-color: #FFCC00;text-align: Center;vertical-align: top;padding: 4px;">A</td></tr></tbody></table><p align="right" style="text-align: right;background-color: red;">B</p></body></html>"##
+    );
 }
 
-// Fragments containing structural HTML tags (<html>, <head>, <body>, <style>) should be handled
-// correctly: structural tags are stripped and content is inlined as expected.
-// See GH-693
-#[test_case("<body><h1>Hello</h1></body>", "", "<h1>Hello</h1>"; "body-tag")]
-#[test_case("<body><style>h1 { color: red; }</style><h1>Hello</h1></body>", "", "<h1 style=\"color: red;\">Hello</h1>"; "body-with-style")]
-#[test_case("<head></head><body><h1>Hello</h1></body>", "h1 { color: red; }", "<h1 style=\"color: red;\">Hello</h1>"; "head-and-body")]
-#[test_case("<style>h1 { color: red; }</style><h1>Hello</h1>", "", "<h1 style=\"color: red;\">Hello</h1>"; "style-tag-and-content")]
-#[test_case("<html><head><style>h1 { color: red; }</style></head><body><h1>Hello</h1></body></html>", "", "<h1 style=\"color: red;\">Hello</h1>"; "full-html-page")]
-fn inline_fragment_structural_tags(input: &str, css: &str, expected: &str) {
-    let inlined = css_inline::inline_fragment(input, css).unwrap();
-    assert_eq!(inlined, expected);
+#[test]
+fn attribute_mappings_cell_padding() {
+    let inliner = CSSInliner::options()
+        .attribute_mappings(vec![AttributeMapping::Padding])
+        .build();
+    // `cellpadding` is taken from the cells, not from the table itself
+    let html = r#"<html><head><style>table { padding: 10px; } td { padding: 4px; } .wide { padding: 8px; } .top { padding-top: 0; }</style></head><body><table id="a"><tr><td>A</td><th style="padding: 4px">B</th></tr></table><table id="b"><tr><td>A</td><td class="wide">B</td></tr></table><table id="c"><tr><td class="top">A</td></tr></table><table id="d"><tr><td>A<table><tr><td class="wide">B</td></tr></table></td></tr></table></body></html>"#;
+    assert_eq!(
+        inliner.inline(html).expect("Inlining failed"),
+        r#"<html><head></head><body><table cellpadding="4" id="a" style="padding: 10px;"><tbody><tr><td style="padding: 4px;">A</td><th style="padding: 4px">B</th></tr></tbody></table><table id="b" style="padding: 10px;"><tbody><tr><td style="padding: 4px;">A</td><td class="wide" style="padding: 8px;">B</td></tr></tbody></table><table id="c" style="padding: 10px;"><tbody><tr><td class="top" style="padding: 4px;padding-top: 0;">A</td></tr></tbody></table><table cellpadding="4" id="d" style="padding: 10px;"><tbody><tr><td style="padding: 4px;">A<table cellpadding="8" style="padding: 10px;"><tbody><tr><td class="wide" style="padding: 8px;">B</td></tr></tbody></table></td></tr></tbody></table></body></html>"#
+    );
+}
+
+#[test]
+fn attribute_mappings_existing_attributes() {
+    let inliner = CSSInliner::options()
+        .attribute_mappings(vec![
+            AttributeMapping::BackgroundColor,
+            AttributeMapping::Border,
+        ])
+        .build();
+    let html = r#"<html><head><style>td { background-color: rgb(0, 0, 0); } table { border: none; background-color: blue; }</style></head><body><table bgcolor="red"><tr><td>A</td></tr></table></body></html>"#;
+    // Existing attributes are kept and unsupported values are skipped
+    assert_eq!(
+        inliner.inline(html).expect("Inlining failed"),
+        r#"<html><head></head><body><table border="0" bgcolor="red" style="border: none;background-color: blue;"><tbody><tr><td style="background-color: rgb(0, 0, 0);">A</td></tr></tbody></table></body></html>"#
+    );
+}
+
+#[test]
+fn propagate_properties() {
+    let inliner = CSSInliner::options()
+        .propagate_properties(Some(PropertyPropagation::default()))
+        .build();
+    let html = r#"<html><head><style>body { font-family: Arial; color: #333 !important; } td { color: red; }</style></head><body><table><tr><td>A</td><th style="font: 12px Georgia">B</th></tr></table></body></html>"#;
+    // Elements declaring a property, directly or via a shorthand, keep their own values
+    assert_eq!(
+        inliner.inline(html).expect("Inlining failed"),
+        r#"<html><head></head><body style="font-family: Arial;color: #333 !important;"><table style="font-family: Arial;color: #333;"><tbody style="font-family: Arial;color: #333;"><tr style="font-family: Arial;color: #333;"><td style="color: red;font-family: Arial;">A</td><th style="color: #333;font: 12px Georgia">B</th></tr></tbody></table></body></html>"#
+    );
+}
+
+#[test]
+fn propagate_properties_relative_values() {
+    let inliner = CSSInliner::options()
+        .propagate_properties(Some(PropertyPropagation::default()))
+        .build();
+    let html = r#"<html><head><style>body { font-size: 16px; line-height: 20px; } .large { font-size: 1.5em; line-height: 150%; }</style></head><body><div class="large"><div class="large"><p>A</p></div></div></body></html>"#;
+    // Relative values are resolved once instead of compounding on every descendant
+    assert_eq!(
+        inliner.inline(html).expect("Inlining failed"),
+        r#"<html><head></head><body style="font-size: 16px;line-height: 20px;"><div class="large" style="font-size: 1.5em;line-height: 150%;"><div class="large" style="font-size: 1.5em;line-height: 150%;"><p style="font-size: 36px;">A</p></div></div></body></html>"#
+    );
+}
+
+#[test]
+fn propagate_properties_browser_defaults() {
+    let inliner = CSSInliner::options()
+        .propagate_properties(Some(PropertyPropagation::default()))
+        .build();
+    let html = r#"<html><head><style>body { font-size: 14px; color: #333; }</style></head><body><h1>Title <span>A</span></h1><p><a href="/">Link <b>B</b></a> <code>C</code></p></body></html>"#;
+    // Headings and links keep the browser's font size and color, also for their descendants
+    assert_eq!(
+        inliner.inline(html).expect("Inlining failed"),
+        r#"<html><head></head><body style="font-size: 14px;color: #333;"><h1 style="color: #333;">Title <span style="color: #333;">A</span></h1><p style="color: #333;font-size: 14px;"><a href="/" style="font-size: 14px;">Link <b style="font-size: 14px;">B</b></a> <code style="color: #333;font-size: 14px;">C</code></p></body></html>"#
+    );
+}
+
+#[test]
+fn propagate_properties_table_cells_only() {
+    let inliner = CSSInliner::options()
+        .propagate_properties(Some(
+            PropertyPropagation::new(vec!["Font-Family".to_string()]).table_cells_only(true),
+        ))
+        .build();
+    let html = r#"<html><head></head><body style="font-family: Verdana; color: blue"><div><table><tr><td><p>A</p></td></tr></table></div></body></html>"#;
+    assert_eq!(
+        inliner.inline(html).expect("Inlining failed"),
+        r#"<html><head></head><body style="font-family: Verdana; color: blue"><div><table style="font-family: Verdana;"><tbody><tr><td style="font-family: Verdana;"><p>A</p></td></tr></tbody></table></div></body></html>"#
+    );
+}
+
+#[test]
+fn keep_dynamic_rules() {
+    let inliner = CSSInliner::options().keep_dynamic_rules(true).build();
+    let html = r#"<html><head><style>a, a:hover { color: red; } .btn:focus-within, p { margin: 0; } a:not(:visited) { font-weight: bold !important; }</style></head><body><a href="/">Home</a><p class="btn">Text</p></body></html>"#;
+    assert_eq!(
+        inliner.inline(html).expect("Inlining failed"),
+        r#"<html><head><style>a:hover { color: red; } .btn:focus-within { margin: 0; } a:not(:visited) { font-weight: bold !important; }</style></head><body><a href="/" style="color: red;">Home</a><p class="btn" style="margin: 0;">Text</p></body></html>"#
+    );
+}
+
+#[test]
+fn keep_dynamic_rules_with_at_rules() {
+    let inliner = CSSInliner::options()
+        .keep_dynamic_rules(true)
+        .keep_at_rules(true)
+        .minify_css(true)
+        .extra_css(Some(
+            "a:hover { color: blue; text-decoration: underline; }".into(),
+        ))
+        .build();
+    let html = r#"<html><head><style>@media (max-width: 600px) { a { color: red; } }</style></head><body><a href="/">Home</a></body></html>"#;
+    assert_eq!(
+        inliner.inline(html).expect("Inlining failed"),
+        r#"<html><head><style>a:hover{color:blue;text-decoration:underline} @media (max-width: 600px) { a { color: red; } } </style></head><body><a href="/">Home</a></body></html>"#
+    );
+}
+
+#[test]
+fn keep_dynamic_rules_with_keep_style_tags() {
+    let inliner = CSSInliner::options()
+        .keep_dynamic_rules(true)
+        .keep_style_tags(true)
+        .build();
+    let html = r#"<html><head><style>a:hover { color: red; }</style></head><body><a href="/">Home</a></body></html>"#;
+    // Rules are not duplicated
+    assert_eq!(inliner.inline(html).expect("Inlining failed"), html);
+}
+
+#[test]
+fn materialize_pseudo_elements() {
+    let inliner = CSSInliner::options()
+        .materialize_pseudo_elements(true)
+        .build();
+    let html = r#"<html><head><style>.btn::before { content: "\2192  "; color: red; } .btn:after { content: " [" attr(title) "]"; font-weight: bold; } a.btn::after { font-weight: normal; } br::before, p::before { content: counter(item); }</style></head><body><a class="btn" title="Go &amp; see">Link</a><br><p>Text</p></body></html>"#;
+    assert_eq!(
+        inliner.inline(html).expect("Inlining failed"),
+        r#"<html><head></head><body><a class="btn" title="Go &amp; see"><span style="color: red;">→ </span>Link<span style="font-weight: normal;"> [Go &amp; see]</span></a><br><p>Text</p></body></html>"#
+    );
+}
+
+#[test]
+fn pseudo_elements_are_skipped_by_default() {
+    let html = r#"<html><head><style>p::before { content: "A"; color: red; }</style></head><body><p>Text</p></body></html>"#;
+    assert_eq!(
+        inline(html).expect("Inlining failed"),
+        "<html><head></head><body><p>Text</p></body></html>"
+    );
+    let inliner = CSSInliner::options().strict(true).build();
+    assert_eq!(
+        inliner.inline(html).expect_err("Should fail").to_string(),
+        "Unsupported pseudo-class or pseudo-element `before` at line 1, column 1: p::before"
+    );
+}
+
+#[test]
+fn selector_lists_in_pseudo_classes() {
+    let html = html!(
+        ":is(h1, h2) > a { color: red; } a:not(.x, .y) { font-weight: bold; } [title=\"a,b\"] { margin: 0; }",
+        r#"<h1><a title="a,b">A</a></h1><p><a class="x">B</a></p>"#
+    );
+    assert_eq!(
+        inline(&html).unwrap(),
+        r#"<html><head></head><body><h1><a title="a,b" style="color: red;margin: 0;font-weight: bold;">A</a></h1><p><a class="x">B</a></p></body></html>"#
+    );
+}
+
+#[test_case(":where(#main) p { color: red; } p { color: blue; }", "color: blue;"; "where has zero specificity")]
+#[test_case("p { color: blue; } :where(#main) p { color: red; }", "color: red;"; "where by source order")]
+#[test_case(":is(#main, .content) p { color: red; } div.content p { color: blue; }", "color: red;"; "is takes the most specific argument")]
+fn is_and_where_specificity(css: &str, expected: &str) {
+    let html = html!(css, r#"<div class="content" id="main"><p>A</p></div>"#);
+    assert_eq!(
+        inline(&html).unwrap(),
+        format!(
+            r#"<html><head></head><body><div class="content" id="main"><p style="{expected}">A</p></div></body></html>"#
+        )
+    );
+}
+
+#[test]
+fn has_pseudo_class() {
+    let html = html!(
+        "td:has(> img) { padding: 0; } tr:has(+ tr) td { border-bottom: 1px solid; }",
+        r#"<table><tr><td><img src="a.png"></td></tr><tr><td>B</td></tr></table>"#
+    );
+    assert_eq!(
+        inline(&html).unwrap(),
+        r#"<html><head></head><body><table><tbody><tr><td style="padding: 0;border-bottom: 1px solid;"><img src="a.png"></td></tr><tr><td>B</td></tr></tbody></table></body></html>"#
+    );
+}
+
+#[test]
+fn linked_stylesheets_document_order() {
+    let resolver = ImportResolver::new(vec![
+        ("https://example.com/emails/b.css", "h1 { color: blue; }"),
+        ("https://example.com/emails/a.css", "h1 { color: red; }"),
+    ]);
+    let html = r#"<html><head>
+<link href="b.css" rel="stylesheet">
+<link href="a.css" rel="stylesheet">
+<link href="b.css" rel="stylesheet">
+</head><body><h1>Hello</h1></body></html>"#;
+    // Later stylesheets win, and duplicates are loaded once
+    assert_eq!(
+        inline_with_imports(&resolver, html),
+        "<html><head>\n\n\n\n</head><body><h1 style=\"color: red;\">Hello</h1></body></html>"
+    );
+    assert_eq!(
+        resolver.requests(),
+        vec![
+            "https://example.com/emails/b.css",
+            "https://example.com/emails/a.css"
+        ]
+    );
+}
+
+#[cfg(feature = "async")]
+#[derive(Default)]
+struct AsyncResolver {
+    stylesheets: Vec<(&'static str, &'static str)>,
+    in_flight: std::sync::atomic::AtomicUsize,
+    max_in_flight: std::sync::atomic::AtomicUsize,
+}
+
+#[cfg(feature = "async")]
+impl css_inline::AsyncStylesheetResolver for AsyncResolver {
+    fn retrieve<'a>(&'a self, location: &'a str) -> css_inline::StylesheetFuture<'a> {
+        use std::sync::atomic::Ordering;
+        Box::pin(async move {
+            let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+            tokio::task::yield_now().await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            self.stylesheets
+                .iter()
+                .find(|(url, _)| *url == location)
+                .map(|(_, css)| (*css).to_string())
+                .ok_or_else(|| css_inline::InlineError::MissingStyleSheet {
+                    path: location.to_string(),
+                })
+        })
+    }
+}
+
+#[cfg(feature = "async")]
+fn async_inliner(resolver: &Arc<AsyncResolver>) -> CSSInliner<'static> {
+    CSSInliner::options()
+        .follow_imports(true)
+        .base_url(Some(
+            Url::parse("https://example.com/emails/").expect("Invalid URL"),
+        ))
+        .async_resolver(resolver.clone())
+        .build()
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn inline_async() {
+    let resolver = Arc::new(AsyncResolver {
+        stylesheets: vec![
+            (
+                "https://example.com/emails/b.css",
+                "h1 { color: blue; margin: 0; }",
+            ),
+            ("https://example.com/emails/a.css", "h1 { color: red; }"),
+            ("https://example.com/emails/c.css", "p { margin: 0; }"),
+        ],
+        ..AsyncResolver::default()
+    });
+    let html = r#"<html><head>
+<link href="b.css" rel="stylesheet">
+<link href="a.css" rel="stylesheet">
+<link href="c.css" rel="stylesheet">
+</head><body><h1>Hello</h1><p>World</p></body></html>"#;
+    let inlined = async_inliner(&resolver)
+        .inline_async(html)
+        .await
+        .expect("Inlining failed");
+    assert_eq!(
+        inlined,
+        "<html><head>\n\n\n\n</head><body><h1 style=\"margin: 0;color: red;\">Hello</h1><p style=\"margin: 0;\">World</p></body></html>"
+    );
+    // All stylesheets are loaded concurrently
+    assert_eq!(
+        resolver
+            .max_in_flight
+            .load(std::sync::atomic::Ordering::SeqCst),
+        3
+    );
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn inline_async_follow_imports() {
+    let resolver = Arc::new(AsyncResolver {
+        stylesheets: vec![
+            (
+                "https://example.com/emails/main.css",
+                "@import url(base.css); h1 { color: red; }",
+            ),
+            (
+                "https://example.com/emails/base.css",
+                "@import url(reset.css); h1 { color: blue; }",
+            ),
+            ("https://example.com/emails/reset.css", "h1 { margin: 0; }"),
+            ("https://example.com/emails/other.css", "p { margin: 0; }"),
+        ],
+        ..AsyncResolver::default()
+    });
+    let html = r#"<html><head>
+<style>@import url(other.css);</style>
+<link href="main.css" rel="stylesheet">
+</head><body><h1>Hello</h1><p>World</p></body></html>"#;
+    let inlined = async_inliner(&resolver)
+        .inline_async(html)
+        .await
+        .expect("Inlining failed");
+    assert_eq!(
+        inlined,
+        "<html><head>\n\n\n</head><body><h1 style=\"margin: 0;color: red;\">Hello</h1><p style=\"margin: 0;\">World</p></body></html>"
+    );
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn inline_fragment_async() {
+    let resolver = Arc::new(AsyncResolver {
+        stylesheets: vec![("https://example.com/emails/a.css", "h1 { color: red; }")],
+        ..AsyncResolver::default()
+    });
+    let inlined = async_inliner(&resolver)
+        .inline_fragment_async(
+            r#"<link href="a.css" rel="stylesheet"><h1>Hello</h1>"#,
+            "h1 { margin: 0; }",
+        )
+        .await
+        .expect("Inlining failed");
+    assert_eq!(inlined, r#"<h1 style="color: red;margin: 0;">Hello</h1>"#);
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn inline_async_missing_stylesheet() {
+    let resolver = Arc::new(AsyncResolver::default());
+    let error = async_inliner(&resolver)
+        .inline_async(r#"<html><head><link href="a.css" rel="stylesheet"></head></html>"#)
+        .await
+        .expect_err("Should fail");
+    assert_eq!(
+        error.to_string(),
+        "Missing stylesheet file: https://example.com/emails/a.css"
+    );
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn inline_async_conditional_comments() {
+    let resolver = Arc::new(AsyncResolver {
+        stylesheets: vec![("https://example.com/emails/a.css", "h1 { color: red; }")],
+        ..AsyncResolver::default()
+    });
+    // Stylesheets inside conditional comments are not loaded by the blocking resolver
+    let inlined = async_inliner(&resolver)
+        .inline_async(r#"<html><head><link href="a.css" rel="stylesheet"></head><body><!--[if mso]><link href="b.css" rel="stylesheet"><h1>Hello</h1><![endif]--></body></html>"#)
+        .await
+        .expect("Inlining failed");
+    assert_eq!(
+        inlined,
+        r#"<html><head></head><body><!--[if mso]><link href="b.css" rel="stylesheet"><h1 style="color: red;">Hello</h1><![endif]--></body></html>"#
+    );
+}
+
+#[cfg(feature = "http")]
+fn inline_with_http_resolver(
+    resolver: css_inline::HttpStylesheetResolver,
+    href: &str,
+) -> css_inline::Result<String> {
+    CSSInliner::options()
+        .base_url(Some(
+            Url::parse("http://127.0.0.1:1234").expect("Invalid URL"),
+        ))
+        .resolver(Arc::new(resolver))
+        .build()
+        .inline_fragment(
+            &format!(r#"<link href="{href}" rel="stylesheet"><h1>Hello</h1>"#),
+            "",
+        )
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn http_resolver_headers() {
+    let resolver = css_inline::HttpStylesheetResolver::builder()
+        .header("X-Color", "red")
+        .user_agent(Some("css-inline-test".to_string()))
+        .build()
+        .expect("Invalid resolver");
+    assert_eq!(
+        inline_with_http_resolver(resolver, "/headers.css").expect("Inlining failed"),
+        r#"<h1 style="color: red;font-family: 'css-inline-test';">Hello</h1>"#
+    );
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn http_resolver_charset() {
+    use css_inline::StylesheetResolver;
+    let css = css_inline::HttpStylesheetResolver::default()
+        .retrieve("http://127.0.0.1:1234/latin1.css")
+        .expect("Failed to load");
+    assert_eq!(css, "h1::before { content: 'é'; }");
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn http_resolver_max_body_size() {
+    let build = |max_body_size| {
+        css_inline::HttpStylesheetResolver::builder()
+            .max_body_size(Some(max_body_size))
+            .build()
+            .expect("Invalid resolver")
+    };
+    let error = inline_with_http_resolver(build(512), "/large.css").expect_err("Should fail");
+    assert_eq!(
+        error.to_string(),
+        "Stylesheet exceeds the maximum size of 512 bytes: http://127.0.0.1:1234/large.css"
+    );
+    assert!(inline_with_http_resolver(build(2048), "/large.css").is_ok());
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn http_resolver_redirects() {
+    let build = |max_redirects| {
+        css_inline::HttpStylesheetResolver::builder()
+            .max_redirects(max_redirects)
+            .build()
+            .expect("Invalid resolver")
+    };
+    assert_eq!(
+        inline_with_http_resolver(build(1), "/redirect.css").expect("Inlining failed"),
+        r#"<h1 style="color: blue;">Hello</h1>"#
+    );
+    let error = inline_with_http_resolver(build(0), "/redirect.css").expect_err("Should fail");
+    let css_inline::InlineError::Network { error, .. } = error else {
+        panic!("Unexpected error: {error}");
+    };
+    assert!(error.is_redirect());
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn http_resolver_not_found() {
+    let error = inline_with_http_resolver(
+        css_inline::HttpStylesheetResolver::default(),
+        "/missing.css",
+    )
+    .expect_err("Should fail");
+    assert!(error.to_string().contains("404 Not Found"), "{error}");
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn http_resolver_read_timeout() {
+    let resolver = css_inline::HttpStylesheetResolver::builder()
+        .read_timeout(Some(std::time::Duration::from_millis(200)))
+        .build()
+        .expect("Invalid resolver");
+    let error = inline_with_http_resolver(resolver, "/slow.css").expect_err("Should fail");
+    let css_inline::InlineError::Network { error, .. } = error else {
+        panic!("Unexpected error: {error}");
+    };
+    assert!(error.is_timeout());
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn http_resolver_retries() {
+    let build = |retries| {
+        css_inline::HttpStylesheetResolver::builder()
+            .retries(retries)
+            .retry_backoff(std::time::Duration::from_millis(10))
+            .build()
+            .expect("Invalid resolver")
+    };
+    // The server fails twice for every distinct URL
+    let nonce = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("Invalid time")
+        .as_nanos();
+    let error = inline_with_http_resolver(build(1), &format!("/flaky.css?{nonce}-1"))
+        .expect_err("Should fail");
+    assert!(error.to_string().contains("503"), "{error}");
+    assert_eq!(
+        inline_with_http_resolver(build(2), &format!("/flaky.css?{nonce}-2"))
+            .expect("Inlining failed"),
+        r#"<h1 style="color: blue;">Hello</h1>"#
+    );
+}
+
+fn inline_with_policy(
+    policy: css_inline::ResolverPolicy,
+    href: &str,
+) -> css_inline::Result<String> {
+    CSSInliner::options()
+        .resolver_policy(Some(policy))
+        .build()
+        .inline_fragment(
+            &format!(r#"<link href="{href}" rel="stylesheet"><h1>Hello</h1>"#),
+            "",
+        )
+}
+
+#[test_case(
+    "tests/external.css",
+    "Scheme `file` is not allowed: tests/external.css"
+)]
+#[test_case(
+    "ftp://example.com/style.css",
+    "Scheme `ftp` is not allowed: ftp://example.com/style.css"
+)]
+fn policy_allowed_schemes(href: &str, expected: &str) {
+    let policy =
+        css_inline::ResolverPolicy::default().allowed_schemes(Some(vec!["https".to_string()]));
+    let error = inline_with_policy(policy, href).expect_err("Should fail");
+    assert!(
+        matches!(error, css_inline::InlineError::PolicyViolation { .. }),
+        "{error:?}"
+    );
+    assert_eq!(error.to_string(), expected);
+}
+
+#[test]
+fn policy_allowed_hosts() {
+    let policy = css_inline::ResolverPolicy::default()
+        .allowed_hosts(Some(vec!["*.example.com".to_string()]));
+    let error =
+        inline_with_policy(policy, "https://example.org/style.css").expect_err("Should fail");
+    assert_eq!(
+        error.to_string(),
+        "Host `example.org` is not allowed: https://example.org/style.css"
+    );
+}
+
+#[test_case("http://127.0.0.1:1234/external.css", "127.0.0.1")]
+#[test_case("http://[::1]:1234/external.css", "::1")]
+#[test_case("http://169.254.169.254/latest/meta-data", "169.254.169.254")]
+fn policy_block_private_ips(href: &str, address: &str) {
+    let policy = css_inline::ResolverPolicy::default().block_private_ips(true);
+    let error = inline_with_policy(policy, href).expect_err("Should fail");
+    assert!(
+        error
+            .to_string()
+            .starts_with(&format!("Private IP address `{address}` is not allowed")),
+        "{error}"
+    );
+}
+
+#[test]
+fn policy_block_private_ips_unresolvable_host() {
+    let policy = css_inline::ResolverPolicy::default().block_private_ips(true);
+    let error =
+        inline_with_policy(policy, "http://unknown.invalid/style.css").expect_err("Should fail");
+    assert_eq!(
+        error.to_string(),
+        "Host `unknown.invalid` could not be resolved: http://unknown.invalid/style.css"
+    );
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn policy_block_private_ips_http_resolver() {
+    let resolver = css_inline::HttpStylesheetResolver::builder()
+        .build()
+        .expect("Invalid resolver");
+    let error = CSSInliner::options()
+        .resolver(std::sync::Arc::new(resolver))
+        .resolver_policy(Some(
+            css_inline::ResolverPolicy::default().block_private_ips(true),
+        ))
+        .build()
+        .inline_fragment(
+            r#"<link href="http://localhost:1234/external.css" rel="stylesheet"><h1>Hello</h1>"#,
+            "",
+        )
+        .expect_err("Should fail");
+    assert!(
+        matches!(error, css_inline::InlineError::PolicyViolation { .. }),
+        "{error:?}"
+    );
+}
+
+#[cfg(feature = "file")]
+#[test]
+fn policy_base_directory() {
+    let policy = css_inline::ResolverPolicy::default().base_directory(Some("tests".into()));
+    assert_eq!(
+        inline_with_policy(policy.clone(), "tests/external.css").expect("Inlining failed"),
+        r#"<h1 style="color: blue;">Hello</h1>"#
+    );
+    let error = inline_with_policy(policy.clone(), "tests/../Cargo.toml").expect_err("Should fail");
+    assert_eq!(
+        error.to_string(),
+        "Path traversal is not allowed: tests/../Cargo.toml"
+    );
+    let error = inline_with_policy(policy, "src/lib.rs").expect_err("Should fail");
+    assert_eq!(
+        error.to_string(),
+        "Paths outside of the base directory are not allowed: src/lib.rs"
+    );
+}
+
+#[test]
+fn policy_applies_to_imports() {
+    let resolver = ImportResolver::new(vec![(
+        "https://example.com/emails/main.css",
+        "@import url(https://internal.example.org/secrets.css); h1 { color: blue; }",
+    )]);
+    let error = CSSInliner::options()
+        .follow_imports(true)
+        .resolver(resolver.clone())
+        .resolver_policy(Some(
+            css_inline::ResolverPolicy::default()
+                .allowed_hosts(Some(vec!["example.com".to_string()])),
+        ))
+        .build()
+        .inline(r#"<html><head><link href="https://example.com/emails/main.css" rel="stylesheet"></head><body><h1>Hello</h1></body></html>"#)
+        .expect_err("Should fail");
+    assert_eq!(
+        error.to_string(),
+        "Host `internal.example.org` is not allowed: https://internal.example.org/secrets.css"
+    );
+    // The forbidden stylesheet is never requested
+    assert_eq!(
+        resolver.requests(),
+        vec!["https://example.com/emails/main.css"]
+    );
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn policy_inline_async() {
+    let resolver = Arc::new(AsyncResolver::default());
+    let error = CSSInliner::options()
+        .async_resolver(resolver.clone())
+        .resolver_policy(Some(
+            css_inline::ResolverPolicy::default().allowed_schemes(Some(vec!["https".to_string()])),
+        ))
+        .build()
+        .inline_async(
+            r#"<html><head><link href="http://example.com/a.css" rel="stylesheet"></head></html>"#,
+        )
+        .await
+        .expect_err("Should fail");
+    assert_eq!(
+        error.to_string(),
+        "Scheme `http` is not allowed: http://example.com/a.css"
+    );
+}
+
+#[cfg(feature = "http")]
+fn inline_with_redirect(
+    resolver: Arc<dyn css_inline::StylesheetResolver>,
+    target: &str,
+) -> css_inline::Result<String> {
+    CSSInliner::options()
+        .resolver(resolver)
+        .resolver_policy(Some(
+            css_inline::ResolverPolicy::default()
+                .allowed_hosts(Some(vec!["127.0.0.1".to_string()])),
+        ))
+        .build()
+        .inline_fragment(
+            &format!(
+                r#"<link href="http://127.0.0.1:1234/redirect-to?{target}" rel="stylesheet"><h1>Hello</h1>"#
+            ),
+            "",
+        )
+}
+
+#[cfg(feature = "http")]
+#[test_case(Arc::new(css_inline::DefaultStylesheetResolver); "default")]
+#[test_case(Arc::new(css_inline::HttpStylesheetResolver::default()); "http")]
+fn policy_redirects(resolver: Arc<dyn css_inline::StylesheetResolver>) {
+    assert_eq!(
+        inline_with_redirect(resolver.clone(), "http://127.0.0.1:1234/external.css")
+            .expect("Inlining failed"),
+        r#"<h1 style="color: blue;">Hello</h1>"#
+    );
+    let error = inline_with_redirect(resolver, "http://localhost:1234/external.css")
+        .expect_err("Should fail");
+    assert!(
+        matches!(error, css_inline::InlineError::PolicyViolation { .. }),
+        "{error:?}"
+    );
+    assert_eq!(
+        error.to_string(),
+        "Host `localhost` is not allowed: http://localhost:1234/external.css"
+    );
+}
+
+#[cfg(all(feature = "async", feature = "http"))]
+#[tokio::test]
+async fn policy_redirects_async() {
+    let error = CSSInliner::options()
+        .resolver_policy(Some(
+            css_inline::ResolverPolicy::default()
+                .allowed_hosts(Some(vec!["127.0.0.1".to_string()])),
+        ))
+        .build()
+        .inline_fragment_async(
+            r#"<link href="http://127.0.0.1:1234/redirect-to?http://localhost:1234/external.css" rel="stylesheet"><h1>Hello</h1>"#,
+            "",
+        )
+        .await
+        .expect_err("Should fail");
+    assert_eq!(
+        error.to_string(),
+        "Host `localhost` is not allowed: http://localhost:1234/external.css"
+    );
+}
+
+#[cfg(all(feature = "async", feature = "http"))]
+#[tokio::test]
+async fn policy_block_private_ips_async() {
+    let error = CSSInliner::options()
+        .resolver_policy(Some(
+            css_inline::ResolverPolicy::default().block_private_ips(true),
+        ))
+        .build()
+        .inline_fragment_async(
+            r#"<link href="http://localhost:1234/external.css" rel="stylesheet"><h1>Hello</h1>"#,
+            "",
+        )
+        .await
+        .expect_err("Should fail");
+    assert!(
+        matches!(error, css_inline::InlineError::PolicyViolation { .. }),
+        "{error:?}"
+    );
+    assert!(
+        error.to_string().starts_with("Private IP address"),
+        "{error}"
+    );
+}
+
+#[test_case("data:text/css;base64,aDEgeyBjb2xvcjogYmx1ZTsgfQ=="; "base64")]
+#[test_case("data:text/css,h1%20%7B%20color%3A%20blue%3B%20%7D"; "percent-encoded")]
+#[test_case("data:text/css;charset=utf-8,h1 { color: blue; }"; "charset")]
+fn data_url_stylesheet(href: &str) {
+    let inlined = css_inline::inline_fragment(
+        &format!(r#"<link href="{href}" rel="stylesheet"><h1>Hello</h1>"#),
+        "",
+    )
+    .expect("Inlining failed");
+    assert_eq!(inlined, r#"<h1 style="color: blue;">Hello</h1>"#);
+}
+
+#[test]
+fn data_url_stylesheet_charset() {
+    // `é` in ISO-8859-1
+    let inlined = css_inline::inline_fragment(
+        r#"<link href="data:text/css;charset=iso-8859-1,h1 { font-family: Caf%E9; }" rel="stylesheet"><h1>Hello</h1>"#,
+        "",
+    )
+    .expect("Inlining failed");
+    assert_eq!(inlined, r#"<h1 style="font-family: Café;">Hello</h1>"#);
+}
+
+#[test]
+fn data_url_stylesheet_relative_urls() {
+    // There is nothing to resolve against in a `data:` URL, so the base URL is used
+    let inlined = CSSInliner::options()
+        .absolutize_urls(true)
+        .base_url(Some(
+            Url::parse("https://example.com/emails/").expect("Invalid URL"),
+        ))
+        .build()
+        .inline_fragment(
+            r#"<link href="data:text/css,h1 { background: url(bg.png); }" rel="stylesheet"><h1>Hello</h1>"#,
+            "",
+        )
+        .expect("Inlining failed");
+    assert_eq!(
+        inlined,
+        r#"<h1 style="background: url(https://example.com/emails/bg.png);">Hello</h1>"#
+    );
+}
+
+#[cfg(feature = "file")]
+#[test]
+fn data_url_stylesheet_imports() {
+    let inlined = CSSInliner::options()
+        .follow_imports(true)
+        .build()
+        .inline_fragment(
+            r#"<link href="data:text/css,@import url(tests/external.css); h1 { margin: 0; }" rel="stylesheet"><h1>Hello</h1>"#,
+            "",
+        )
+        .expect("Inlining failed");
+    assert_eq!(inlined, r#"<h1 style="color: blue;margin: 0;">Hello</h1>"#);
+}
+
+#[test]
+fn data_url_stylesheet_invalid() {
+    let error = css_inline::inline_fragment(
+        r#"<link href="data:text/css;base64,!!!" rel="stylesheet"><h1>Hello</h1>"#,
+        "",
+    )
+    .expect_err("Should fail");
+    assert_eq!(
+        error.to_string(),
+        "Invalid data URL: malformed base64 content"
+    );
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn data_url_stylesheet_async() {
+    let inlined = CSSInliner::options()
+        .build()
+        .inline_fragment_async(
+            r#"<link href="data:text/css;base64,aDEgeyBjb2xvcjogYmx1ZTsgfQ==" rel="stylesheet"><h1>Hello</h1>"#,
+            "",
+        )
+        .await
+        .expect("Inlining failed");
+    assert_eq!(inlined, r#"<h1 style="color: blue;">Hello</h1>"#);
+}
+
+#[test]
+fn memory_resolver() {
+    let resolver = css_inline::MemoryStylesheetResolver::default()
+        .stylesheet(
+            "https://example.com/css/main.css",
+            "@import 'reset.css'; h1 { color: blue; }",
+        )
+        .stylesheet("https://example.com/css/reset.css", "h1 { margin: 0; }");
+    let inliner = CSSInliner::options()
+        .follow_imports(true)
+        .base_url(Some(
+            Url::parse("https://example.com/").expect("Invalid URL"),
+        ))
+        .resolver(Arc::new(resolver))
+        .build();
+    assert_eq!(
+        inliner
+            .inline_fragment(
+                r#"<link href="/css/main.css" rel="stylesheet"><h1>Hello</h1>"#,
+                ""
+            )
+            .expect("Inlining failed"),
+        r#"<h1 style="margin: 0;color: blue;">Hello</h1>"#
+    );
+    let error = inliner
+        .inline_fragment(r#"<link href="/css/missing.css" rel="stylesheet">"#, "")
+        .expect_err("Should fail");
+    assert_eq!(
+        error.to_string(),
+        "Missing stylesheet file: https://example.com/css/missing.css"
+    );
+}
+
+#[test]
+fn memory_resolver_from_iter() {
+    let resolver: css_inline::MemoryStylesheetResolver =
+        [("styles/main.css", "h1 { color: blue; }")]
+            .into_iter()
+            .collect();
+    assert_eq!(
+        css_inline::StylesheetResolver::retrieve(&resolver, "styles/main.css")
+            .expect("Missing stylesheet"),
+        "h1 { color: blue; }"
+    );
+}
+
+#[cfg(feature = "file")]
+#[test]
+fn chain_resolver() {
+    let resolver = css_inline::ChainResolver::new(vec![
+        Arc::new(
+            css_inline::MemoryStylesheetResolver::default()
+                .stylesheet("bundled.css", "h1 { margin: 0; }"),
+        ),
+        Arc::new(css_inline::DefaultStylesheetResolver),
+    ]);
+    let inliner = CSSInliner::options().resolver(Arc::new(resolver)).build();
+    assert_eq!(
+        inliner
+            .inline_fragment(
+                r#"<link href="bundled.css" rel="stylesheet"><link href="tests/external.css" rel="stylesheet"><h1>Hello</h1>"#,
+                ""
+            )
+            .expect("Inlining failed"),
+        r#"<h1 style="margin: 0;color: blue;">Hello</h1>"#
+    );
+    // The error is from the last resolver
+    let error = inliner
+        .inline_fragment(r#"<link href="tests/missing.css" rel="stylesheet">"#, "")
+        .expect_err("Should fail");
+    assert_eq!(
+        error.to_string(),
+        "Missing stylesheet file: tests/missing.css"
+    );
+}
+
+#[test]
+fn chain_resolver_empty() {
+    let error = CSSInliner::options()
+        .resolver(Arc::new(css_inline::ChainResolver::new(Vec::new())))
+        .build()
+        .inline_fragment(r#"<link href="main.css" rel="stylesheet">"#, "")
+        .expect_err("Should fail");
+    assert_eq!(error.to_string(), "Missing stylesheet file: main.css");
+}
+
+#[cfg(feature = "file")]
+#[test]
+fn prefix_resolver() {
+    let resolver = css_inline::PrefixResolver::new(Arc::new(css_inline::DefaultStylesheetResolver))
+        .route("https://cdn.example.com/", "tests/");
+    let inliner = CSSInliner::options().resolver(Arc::new(resolver)).build();
+    assert_eq!(
+        inliner
+            .inline_fragment(
+                r#"<link href="https://cdn.example.com/external.css?v=2" rel="stylesheet"><h1>Hello</h1>"#,
+                ""
+            )
+            .expect("Inlining failed"),
+        r#"<h1 style="color: blue;">Hello</h1>"#
+    );
+    let error = inliner
+        .inline_fragment(
+            r#"<link href="https://cdn.example.com/../Cargo.toml" rel="stylesheet">"#,
+            "",
+        )
+        .expect_err("Should fail");
+    assert_eq!(
+        error.to_string(),
+        "Path traversal is not allowed: https://cdn.example.com/../Cargo.toml"
+    );
+}
+
+#[test]
+fn prefix_resolver_memory() {
+    let resolver = css_inline::PrefixResolver::new(Arc::new(
+        css_inline::MemoryStylesheetResolver::default()
+            .stylesheet("main.css", "h1 { color: blue; }"),
+    ))
+    .route("https://cdn.example.com/v1/", "")
+    .route("https://cdn.example.com/", "");
+    assert_eq!(
+        CSSInliner::options()
+            .resolver(Arc::new(resolver))
+            .build()
+            .inline_fragment(
+                r#"<link href="https://cdn.example.com/v1/main.css" rel="stylesheet"><h1>Hello</h1>"#,
+                ""
+            )
+            .expect("Inlining failed"),
+        r#"<h1 style="color: blue;">Hello</h1>"#
+    );
+}
+
+// Fragments with leading/trailing whitespace were silently returning only the whitespace.
+// See GH-692
+#[test_case(" <h1>Hello World!</h1>", " <h1 style=\"color: blue;\">Hello World!</h1>"; "one-space")]
+#[test_case("\n<h1>Hello World!</h1>", "\n<h1 style=\"color: blue;\">Hello World!</h1>"; "newline")]
+#[test_case("  <h1>Hello World!</h1>  ", "  <h1 style=\"color: blue;\">Hello World!</h1>  "; "two-spaces")]
+fn inline_fragment_surrounding_whitespace(input: &str, expected: &str) {
+    let inlined = css_inline::inline_fragment(input, "h1 { color: blue; }").unwrap();
+    assert_eq!(inlined, expected);
+}
+
+// Fragments containing structural HTML tags (<html>, <head>, <body>, <style>) should be handled
+// correctly: structural tags are stripped and content is inlined as expected.
+// See GH-693
+#[test_case("<body><h1>Hello</h1></body>", "", "<h1>Hello</h1>"; "body-tag")]
+#[test_case("<body><style>h1 { color: red; }</style><h1>Hello</h1></body>", "", "<h1 style=\"color: red;\">Hello</h1>"; "body-with-style")]
+#[test_case("<head></head><body><h1>Hello</h1></body>", "h1 { color: red; }", "<h1 style=\"color: red;\">Hello</h1>"; "head-and-body")]
+#[test_case("<style>h1 { color: red; }</style><h1>Hello</h1>", "", "<h1 style=\"color: red;\">Hello</h1>"; "style-tag-and-content")]
+#[test_case("<html><head><style>h1 { color: red; }</style></head><body><h1>Hello</h1></body></html>", "", "<h1 style=\"color: red;\">Hello</h1>"; "full-html-page")]
+fn inline_fragment_structural_tags(input: &str, css: &str, expected: &str) {
+    let inlined = css_inline::inline_fragment(input, css).unwrap();
+    assert_eq!(inlined, expected);
+}
+
+#[test]
+fn report_skipped_css() {
+    let html = html!(
+        r#"h1 { color: blue; colr red; }
+h2 { color: red; }
+a:hover { color: green; }
+p:unknown { color: red; }
+@media (max-width: 600px) { h1 { color: red; } }
+h1 { @media print { color: red; } }"#,
+        r#"<h1>Hello</h1><a href="https://example.com">Link</a>"#
+    );
+    let (inlined, report) = CSSInliner::default()
+        .inline_with_report(&html)
+        .expect("Inlining failed");
+    assert_eq!(
+        inlined,
+        html!(r#"<h1 style="color: blue;">Hello</h1><a href="https://example.com">Link</a>"#)
+    );
+    assert_eq!(report.unsupported_selectors, vec!["p:unknown"]);
+    assert_eq!(report.unmatched_selectors, vec!["h2", "a:hover"]);
+    assert_eq!(
+        report.dropped_at_rules,
+        vec!["@media (max-width: 600px)", "@media print"]
+    );
+    assert_eq!(report.invalid_declarations, vec!["colr red;"]);
+    assert!(report.invalid_rules.is_empty());
+    assert!(!report.is_clean());
+}
+
+#[test]
+fn report_clean() {
+    let html = html!("h1 { color: blue; }", "<h1>Hello</h1>");
+    let (inlined, report) = CSSInliner::default()
+        .inline_with_report(&html)
+        .expect("Inlining failed");
+    assert_eq!(inlined, inline(&html).expect("Inlining failed"));
+    assert!(report.is_clean());
+}
+
+#[test]
+fn report_kept_at_rules_are_not_dropped() {
+    let html = html!(
+        "@media (max-width: 600px) { h1 { color: red; } }",
+        "<h1>Hello</h1>"
+    );
+    let (_, report) = CSSInliner::options()
+        .keep_at_rules(true)
+        .build()
+        .inline_with_report(&html)
+        .expect("Inlining failed");
+    assert!(report.dropped_at_rules.is_empty());
+}
+
+#[test]
+fn report_unsupported_selector_without_anchor() {
+    // Large documents skip selectors whose anchor is missing, but the report still tells
+    // unsupported selectors apart from unmatched ones
+    let html = make_large_html(".missing:unknown { color: red; } .missing { color: blue; }");
+    let (_, report) = CSSInliner::default()
+        .inline_with_report(&html)
+        .expect("Inlining failed");
+    assert_eq!(report.unsupported_selectors, vec![".missing:unknown"]);
+    assert_eq!(report.unmatched_selectors, vec![".missing"]);
+}
+
+#[test]
+fn report_fragment() {
+    let (inlined, report) = CSSInliner::default()
+        .inline_fragment_with_report("<h1>Hello</h1>", "h1 { color: blue; } h2")
+        .expect("Inlining failed");
+    assert_eq!(inlined, r#"<h1 style="color: blue;">Hello</h1>"#);
+    // A prelude without a block
+    assert_eq!(report.invalid_rules, vec!["h2"]);
+}
+
+#[test]
+fn report_resolved_stylesheets() {
+    #[derive(Debug, Default)]
+    struct StaticResolver;
+
+    impl css_inline::StylesheetResolver for StaticResolver {
+        fn retrieve(&self, location: &str) -> css_inline::Result<String> {
+            if location.ends_with("empty.css") {
+                Ok(String::from("  "))
+            } else {
+                Ok(String::from("h1 { color: blue; }"))
+            }
+        }
+    }
+
+    let html = r#"<html><head>
+<link href="style.css" rel="stylesheet">
+<link href="empty.css" rel="stylesheet">
+</head><body><h1>Hello</h1></body></html>"#;
+    let (_, report) = CSSInliner::options()
+        .base_url(Some(
+            Url::parse("https://example.com/css/").expect("Invalid URL"),
+        ))
+        .resolver(Arc::new(StaticResolver))
+        .build()
+        .inline_with_report(html)
+        .expect("Inlining failed");
+    assert_eq!(
+        report.resolved_stylesheets,
+        vec![
+            "https://example.com/css/style.css",
+            "https://example.com/css/empty.css"
+        ]
+    );
+    assert_eq!(
+        report.empty_stylesheets,
+        vec!["https://example.com/css/empty.css"]
+    );
+}
+
+#[test]
+fn strict_invalid_declaration() {
+    let html = "<html><head><style>
+h1 {
+  colr red;
+  color: blue;
+}
+</style></head><body><h1>Hello</h1></body></html>";
+    let error = CSSInliner::options()
+        .strict(true)
+        .build()
+        .inline(html)
+        .expect_err("Should fail");
+    assert_eq!(
+        error.to_string(),
+        "Invalid declaration at line 3, column 3: colr red;"
+    );
+}
+
+#[test]
+fn strict_unsupported_pseudo_class() {
+    let html = "<html><head><style>h1 { color: blue; }
+p:unknown { color: red; }</style></head><body><h1>Hello</h1></body></html>";
+    let error = CSSInliner::options()
+        .strict(true)
+        .build()
+        .inline(html)
+        .expect_err("Should fail");
+    assert_eq!(
+        error.to_string(),
+        "Unsupported pseudo-class or pseudo-element `unknown` at line 2, column 1: p:unknown"
+    );
+}
+
+#[test]
+fn strict_invalid_rule() {
+    let error = CSSInliner::options()
+        .strict(true)
+        .build()
+        .inline_fragment("<h1>Hello</h1>", "h1 { color: blue; } h2")
+        .expect_err("Should fail");
+    assert_eq!(error.to_string(), "Invalid rule at line 1, column 21: h2");
+}
+
+#[test]
+fn strict_allows_at_rules() {
+    let html = r#"<html><head><style>h1 { color: blue; } @media (max-width: 600px) { h1 { color: red; } }</style></head><body><h1>Hello</h1></body></html>"#;
+    let inlined = CSSInliner::options()
+        .strict(true)
+        .build()
+        .inline(html)
+        .expect("Inlining failed");
+    assert_eq!(
+        inlined,
+        r#"<html><head></head><body><h1 style="color: blue;">Hello</h1></body></html>"#
+    );
+}
+
+#[test]
+fn lenient_by_default() {
+    let html = "<html><head><style>h1 { colr red; color: blue; } p:unknown { color: red; }</style></head><body><h1>Hello</h1></body></html>";
+    let inlined = inline(html).expect("Inlining failed");
+    assert_eq!(
+        inlined,
+        r#"<html><head></head><body><h1 style="color: blue;">Hello</h1></body></html>"#
+    );
+}
+
+#[test]
+fn compiled_stylesheet() {
+    let stylesheet = Arc::new(CompiledStylesheet::new(
+        "h1, .title { color: blue; } p { margin: 0; }",
+    ));
+    let inliner = CSSInliner::options()
+        .compiled_stylesheet(Some(stylesheet))
+        .build();
+    assert_eq!(
+        inliner
+            .inline(r#"<html><head></head><body><h1>Hello</h1><p>World</p></body></html>"#)
+            .expect("Inlining failed"),
+        r#"<html><head></head><body><h1 style="color: blue;">Hello</h1><p style="margin: 0;">World</p></body></html>"#
+    );
+    assert_eq!(
+        inliner
+            .inline(r#"<html><head></head><body><span class="title">Hello</span></body></html>"#)
+            .expect("Inlining failed"),
+        r#"<html><head></head><body><span class="title" style="color: blue;">Hello</span></body></html>"#
+    );
+}
+
+#[test]
+fn compiled_stylesheet_cascade() {
+    let stylesheet = Arc::new(CompiledStylesheet::new(
+        "h1 { color: blue; padding: 0; } #title { margin: 0; }",
+    ));
+    let html = r#"<html><head><style>h1 { color: red; } h1#title { margin: 10px; }</style></head><body><h1 id="title" style="padding: 5px">Hello</h1></body></html>"#;
+    let inlined = CSSInliner::options()
+        .compiled_stylesheet(Some(stylesheet))
+        .extra_css(Some("h1 { color: green; }".into()))
+        .build()
+        .inline(html)
+        .expect("Inlining failed");
+    // Equal specificity - the compiled stylesheet goes last and wins
+    assert_eq!(
+        inlined,
+        r#"<html><head></head><body><h1 id="title" style="color: blue;margin: 10px;padding: 5px">Hello</h1></body></html>"#
+    );
+}
+
+#[test]
+fn compiled_stylesheet_fragment() {
+    let inlined = CSSInliner::options()
+        .compiled_stylesheet(Some(Arc::new(CompiledStylesheet::new(
+            "p { color: blue; }",
+        ))))
+        .build()
+        .inline_fragment("<h1>Hello</h1><p>World</p>", "h1 { color: red; }")
+        .expect("Inlining failed");
+    assert_eq!(
+        inlined,
+        r#"<h1 style="color: red;">Hello</h1><p style="color: blue;">World</p>"#
+    );
+}
+
+#[test]
+fn compiled_stylesheet_at_rules() {
+    let stylesheet = Arc::new(CompiledStylesheet::new(
+        "h1 { color: blue; } @media (max-width: 600px) { h1 { color: red; } }",
+    ));
+    let html = "<html><head></head><body><h1>Hello</h1></body></html>";
+    let inlined = CSSInliner::options()
+        .keep_at_rules(true)
+        .compiled_stylesheet(Some(stylesheet))
+        .build()
+        .inline(html)
+        .expect("Inlining failed");
+    assert_eq!(
+        inlined,
+        r#"<html><head><style>@media (max-width: 600px) { h1 { color: red; } } </style></head><body><h1 style="color: blue;">Hello</h1></body></html>"#
+    );
+}
+
+#[test]
+fn compiled_stylesheet_report() {
+    let stylesheet = Arc::new(CompiledStylesheet::new(
+        "h1, p:unknown { color: blue; colr red; } h2 { color: red; } @font-face { font-family: x; }",
+    ));
+    let (_, report) = CSSInliner::options()
+        .compiled_stylesheet(Some(stylesheet))
+        .build()
+        .inline_with_report("<html><head></head><body><h1>Hello</h1></body></html>")
+        .expect("Inlining failed");
+    assert_eq!(report.unsupported_selectors, vec!["p:unknown"]);
+    assert_eq!(report.unmatched_selectors, vec!["h2"]);
+    assert_eq!(report.invalid_declarations, vec!["colr red;"]);
+    assert_eq!(report.dropped_at_rules, vec!["@font-face"]);
+}
+
+#[test]
+fn compiled_stylesheet_strict() {
+    let stylesheet = Arc::new(CompiledStylesheet::new(
+        "h1 { color: blue; }\np:unknown { color: red; }",
+    ));
+    let error = CSSInliner::options()
+        .strict(true)
+        .compiled_stylesheet(Some(stylesheet))
+        .build()
+        .inline("<html><head></head><body><h1>Hello</h1></body></html>")
+        .expect_err("Should fail");
+    assert_eq!(
+        error.to_string(),
+        "Unsupported pseudo-class or pseudo-element `unknown` at line 2, column 1: p:unknown"
+    );
+}
+
+fn inline_with_variables(html: &str) -> String {
+    CSSInliner::options()
+        .resolve_css_variables(true)
+        .build()
+        .inline(html)
+        .expect("Inlining failed")
+}
+
+#[test]
+fn css_variables() {
+    let html = html!(
+        ":root { --brand: red; --space: 4px; } h1 { color: var(--brand); margin: var(--space) 0; }",
+        "<h1>Hello</h1>"
+    );
+    assert_eq!(
+        inline_with_variables(&html),
+        html!(r#"<h1 style="color: red;margin: 4px 0;">Hello</h1>"#)
+    );
+}
+
+#[test]
+fn css_variables_inheritance() {
+    let html = html!(
+        ":root { --brand: red; } .card { --brand: blue; } p { color: var(--brand); }",
+        r#"<p>Outside</p><div class="card"><div><p>Inside</p></div></div>"#
+    );
+    assert_eq!(
+        inline_with_variables(&html),
+        html!(
+            r#"<p style="color: red;">Outside</p><div class="card"><div><p style="color: blue;">Inside</p></div></div>"#
+        )
+    );
+}
+
+#[test]
+fn css_variables_specificity() {
+    let html = html!(
+        "#main { --brand: blue; } div { --brand: red; } p { color: var(--brand); }",
+        r#"<div id="main"><p>Hello</p></div>"#
+    );
+    assert_eq!(
+        inline_with_variables(&html),
+        html!(r#"<div id="main"><p style="color: blue;">Hello</p></div>"#)
+    );
+}
+
+#[test]
+fn css_variables_style_attribute() {
+    let html = html!(
+        ":root { --brand: red; } div { --brand: green !important; } h1 { color: var(--brand); }",
+        r#"<div style="--brand: blue"><h1 style="--size: 2px; border: var(--size) solid var(--brand)">Hello</h1></div><p style="--brand: blue">Text</p>"#
+    );
+    assert_eq!(
+        inline_with_variables(&html),
+        html!(
+            r#"<div><h1 style="color: green;border: 2px solid green">Hello</h1></div><p>Text</p>"#
+        )
+    );
+}
+
+#[test]
+fn css_variables_references() {
+    let html = html!(
+        ":root { --base: 4px; --double: calc(var(--base) * 2); } div { --base: 8px; } p { padding: var(--double); margin: var(--base); }",
+        "<div><p>Hello</p></div>"
+    );
+    // Custom properties inherit computed values
+    assert_eq!(
+        inline_with_variables(&html),
+        html!(r#"<div><p style="padding: calc(4px * 2);margin: 8px;">Hello</p></div>"#)
+    );
+}
+
+#[test]
+fn css_variables_fallbacks() {
+    let html = html!(
+        ":root { --brand: red; } p { color: var(--missing, var(--brand)); margin: var(--missing, 1px 2px); padding: var(--missing); }",
+        "<p>Hello</p>"
+    );
+    assert_eq!(
+        inline_with_variables(&html),
+        html!(r#"<p style="color: red;margin: 1px 2px;">Hello</p>"#)
+    );
+}
+
+#[test]
+fn css_variables_cycle() {
+    let html = html!(
+        "p { --a: var(--b); --b: var(--a); color: var(--a, blue); width: var(--b); }",
+        "<p>Hello</p>"
+    );
+    assert_eq!(
+        inline_with_variables(&html),
+        html!(r#"<p style="color: blue;">Hello</p>"#)
+    );
+}
+
+#[test]
+fn css_variables_not_resolved_by_default() {
+    let html = html!(
+        ":root { --brand: red; } h1 { color: var(--brand); }",
+        "<h1>Hello</h1>"
+    );
+    assert_eq!(
+        inline(&html).expect("Inlining failed"),
+        r#"<html style="--brand: red;"><head></head><body><h1 style="color: var(--brand);">Hello</h1></body></html>"#
+    );
+}
+
+#[test]
+fn empty_style_attribute_is_kept() {
+    let html = r#"<html><head></head><body><p style="">Hello</p></body></html>"#;
+    assert_eq!(inline(html).expect("Inlining failed"), html);
+    // Only an attribute that held nothing but custom properties is dropped
+    assert_eq!(inline_with_variables(html), html);
+}
+
+fn inline_with_media_environment(html: &str, keep_at_rules: bool) -> String {
+    CSSInliner::options()
+        .media_environment(Some(MediaEnvironment::new(375, 667)))
+        .keep_at_rules(keep_at_rules)
+        .build()
+        .inline(html)
+        .expect("Inlining failed")
+}
+
+#[test]
+fn media_environment() {
+    let html = html!(
+        "h1 { color: blue; margin: 0; } @media (max-width: 600px) { h1 { color: red; } } @media (min-width: 601px) { h1 { margin: 10px; } }",
+        "<h1>Hello</h1>"
+    );
+    assert_eq!(
+        inline_with_media_environment(&html, false),
+        html!(r#"<h1 style="margin: 0;color: red;">Hello</h1>"#)
+    );
+}
+
+#[test]
+fn media_environment_source_order() {
+    // Matching rules are placed where the `@media` block is
+    let html = html!(
+        "@media screen { h1 { color: red; } } h1 { color: blue; } @media screen and (orientation: portrait) { p { color: green; } }",
+        "<h1>Hello</h1><p>World</p>"
+    );
+    assert_eq!(
+        inline_with_media_environment(&html, false),
+        html!(r#"<h1 style="color: blue;">Hello</h1><p style="color: green;">World</p>"#)
+    );
+}
+
+#[test]
+fn media_environment_nested() {
+    let html = html!(
+        "@media screen { @media (max-width: 600px) { h1 { color: red; } } @media print { h1 { margin: 0; } } }",
+        "<h1>Hello</h1>"
+    );
+    assert_eq!(
+        inline_with_media_environment(&html, false),
+        html!(r#"<h1 style="color: red;">Hello</h1>"#)
+    );
+}
+
+#[test]
+fn media_environment_keep_at_rules() {
+    let html = html!(
+        "@media (max-width: 600px) { h1 { color: red; } } @media (prefers-color-scheme: dark) { h1 { color: white; } } @font-face { font-family: x; }",
+        "<h1>Hello</h1>"
+    );
+    assert_eq!(
+        inline_with_media_environment(&html, true),
+        r#"<html><head><style>@media (prefers-color-scheme: dark) { h1 { color: white; } } @font-face { font-family: x; } </style></head><body><h1 style="color: red;">Hello</h1></body></html>"#
+    );
+}
+
+#[test]
+fn media_environment_color_scheme() {
+    let html = html!(
+        "h1 { color: black; } @media (prefers-color-scheme: dark) { h1 { color: white; } }",
+        "<h1>Hello</h1>"
+    );
+    let inlined = CSSInliner::options()
+        .media_environment(Some(
+            MediaEnvironment::default().prefers_color_scheme(ColorScheme::Dark),
+        ))
+        .build()
+        .inline(&html)
+        .expect("Inlining failed");
+    assert_eq!(inlined, html!(r#"<h1 style="color: white;">Hello</h1>"#));
+}
+
+#[test]
+fn media_environment_report() {
+    let html = html!(
+        "@media (max-width: 600px) { h1 { color: red; } } @media print { h1 { color: black; } }",
+        "<h1>Hello</h1>"
+    );
+    let (_, report) = CSSInliner::options()
+        .media_environment(Some(MediaEnvironment::new(375, 667)))
+        .build()
+        .inline_with_report(&html)
+        .expect("Inlining failed");
+    assert_eq!(report.dropped_at_rules, vec!["@media print"]);
+}
+
+#[test]
+fn media_environment_not_set() {
+    let html = html!(
+        "h1 { color: blue; } @media (max-width: 600px) { h1 { color: red; } }",
+        "<h1>Hello</h1>"
+    );
+    assert_eq!(
+        inline(&html).expect("Inlining failed"),
+        html!(r#"<h1 style="color: blue;">Hello</h1>"#)
+    );
+}
+
+#[test]
+fn style_media_attribute() {
+    let html = r#"<html><head><style media="print">h1 { color: black; }</style><style media="screen and (min-width: 600px)">h1 { margin: 0; }</style></head><body><h1>Hello</h1></body></html>"#;
+    // Non-matching "style" tags are kept untouched
+    assert_eq!(
+        inline(html).expect("Inlining failed"),
+        r#"<html><head><style media="print">h1 { color: black; }</style></head><body><h1 style="margin: 0;">Hello</h1></body></html>"#
+    );
+}
+
+#[test]
+fn style_media_attribute_with_environment() {
+    let html = r#"<html><head><style media="print">h1 { color: black; }</style><style media="(min-width: 600px)">h1 { margin: 0; }</style></head><body><h1>Hello</h1></body></html>"#;
+    let inlined = CSSInliner::options()
+        .media_environment(Some(
+            MediaEnvironment::new(375, 667).media_type(MediaType::Print),
+        ))
+        .build()
+        .inline(html)
+        .expect("Inlining failed");
+    assert_eq!(
+        inlined,
+        r#"<html><head><style media="(min-width: 600px)">h1 { margin: 0; }</style></head><body><h1 style="color: black;">Hello</h1></body></html>"#
+    );
+}
+
+#[test]
+fn disabled_style() {
+    let html = r#"<html><head><style disabled="">h1 { color: black; }</style></head><body><h1>Hello</h1></body></html>"#;
+    assert_eq!(inline(html).expect("Inlining failed"), html);
+}
+
+#[test]
+fn linked_stylesheets_media_disabled_and_alternate() {
+    let resolver = ImportResolver::new(vec![
+        ("https://example.com/screen.css", "h1 { color: blue; }"),
+        ("https://example.com/other.css", "h1 { color: red; }"),
+    ]);
+    let html = r#"<html><head><link href="https://example.com/screen.css" rel="Preload StyleSheet" media="all"><link href="https://example.com/other.css" rel="stylesheet" media="print"><link href="https://example.com/other.css" rel="stylesheet" disabled><link href="https://example.com/other.css" rel="alternate stylesheet" title="Dark"></head><body><h1>Hello</h1></body></html>"#;
+    let inlined = CSSInliner::options()
+        .resolver(resolver.clone())
+        .build()
+        .inline(html)
+        .expect("Inlining failed");
+    assert_eq!(
+        inlined,
+        r#"<html><head><link href="https://example.com/other.css" rel="stylesheet" media="print"><link href="https://example.com/other.css" rel="stylesheet" disabled=""><link href="https://example.com/other.css" rel="alternate stylesheet" title="Dark"></head><body><h1 style="color: blue;">Hello</h1></body></html>"#
+    );
+    assert_eq!(resolver.requests(), vec!["https://example.com/screen.css"]);
+}
+
+#[test]
+fn remove_inapplicable_stylesheets() {
+    let resolver = ImportResolver::new(vec![]);
+    let html = r#"<html><head><style media="print">h1 { color: black; }</style><link href="https://example.com/print.css" rel="stylesheet" media="print"><link href="https://example.com/print.css" rel="stylesheet" disabled></head><body><h1>Hello</h1></body></html>"#;
+    let inlined = CSSInliner::options()
+        .remove_inapplicable_stylesheets(true)
+        .resolver(resolver.clone())
+        .build()
+        .inline(html)
+        .expect("Inlining failed");
+    assert_eq!(
+        inlined,
+        "<html><head></head><body><h1>Hello</h1></body></html>"
+    );
+    assert!(resolver.requests().is_empty());
+}
+
+/// Serves stylesheets from memory and records requested locations.
+struct ImportResolver {
+    stylesheets: Vec<(&'static str, &'static str)>,
+    requests: std::sync::Mutex<Vec<String>>,
+}
+
+impl ImportResolver {
+    fn new(stylesheets: Vec<(&'static str, &'static str)>) -> Arc<ImportResolver> {
+        Arc::new(ImportResolver {
+            stylesheets,
+            requests: std::sync::Mutex::default(),
+        })
+    }
+
+    fn requests(&self) -> Vec<String> {
+        self.requests.lock().expect("Lock is poisoned").clone()
+    }
+}
+
+impl css_inline::StylesheetResolver for ImportResolver {
+    fn retrieve(&self, location: &str) -> css_inline::Result<String> {
+        self.requests
+            .lock()
+            .expect("Lock is poisoned")
+            .push(location.to_string());
+        self.stylesheets
+            .iter()
+            .find(|(url, _)| *url == location)
+            .map(|(_, css)| (*css).to_string())
+            .ok_or_else(|| css_inline::InlineError::MissingStyleSheet {
+                path: location.to_string(),
+            })
+    }
+}
+
+fn inline_with_imports(resolver: &Arc<ImportResolver>, html: &str) -> String {
+    CSSInliner::options()
+        .follow_imports(true)
+        .base_url(Some(
+            Url::parse("https://example.com/emails/").expect("Invalid URL"),
+        ))
+        .resolver(resolver.clone())
+        .build()
+        .inline(html)
+        .expect("Inlining failed")
+}
+
+#[test]
+fn follow_imports_in_style_tags() {
+    let resolver = ImportResolver::new(vec![(
+        "https://example.com/emails/base.css",
+        "h1 { color: blue; margin: 0; }",
+    )]);
+    let html = html!(
+        "@import url(base.css); h1 { color: red; }",
+        "<h1>Hello</h1>"
+    );
+    // Rules from the importing stylesheet come later in the cascade
+    assert_eq!(
+        inline_with_imports(&resolver, &html),
+        html!(r#"<h1 style="margin: 0;color: red;">Hello</h1>"#)
+    );
+}
+
+#[test]
+fn follow_imports_relative_to_importer() {
+    let resolver = ImportResolver::new(vec![
+        (
+            "https://example.com/css/main.css",
+            "@import 'common/reset.css'; h1 { color: blue; }",
+        ),
+        (
+            "https://example.com/css/common/reset.css",
+            "@import url(\"/fonts.css\"); h1 { margin: 0; color: red; }",
+        ),
+        (
+            "https://example.com/fonts.css",
+            "h1 { font-family: serif; }",
+        ),
+    ]);
+    let html = r#"<html><head><link href="/css/main.css" rel="stylesheet"></head><body><h1>Hello</h1></body></html>"#;
+    assert_eq!(
+        inline_with_imports(&resolver, html),
+        r#"<html><head></head><body><h1 style="font-family: serif;margin: 0;color: blue;">Hello</h1></body></html>"#
+    );
+    assert_eq!(
+        resolver.requests(),
+        vec![
+            "https://example.com/css/main.css",
+            "https://example.com/css/common/reset.css",
+            "https://example.com/fonts.css",
+        ]
+    );
+}
+
+#[test]
+fn follow_imports_media_conditions() {
+    let resolver = ImportResolver::new(vec![
+        (
+            "https://example.com/emails/mobile.css",
+            "h1 { color: red; }",
+        ),
+        (
+            "https://example.com/emails/print.css",
+            "h1 { color: black; }",
+        ),
+    ]);
+    let html = html!(
+        "@import url(mobile.css) screen and (max-width: 600px); @import url(print.css) print; h1 { margin: 0; }",
+        "<h1>Hello</h1>"
+    );
+    let inlined = CSSInliner::options()
+        .follow_imports(true)
+        .media_environment(Some(MediaEnvironment::new(375, 667)))
+        .base_url(Some(
+            Url::parse("https://example.com/emails/").expect("Invalid URL"),
+        ))
+        .resolver(resolver)
+        .build()
+        .inline(&html)
+        .expect("Inlining failed");
+    assert_eq!(
+        inlined,
+        html!(r#"<h1 style="color: red;margin: 0;">Hello</h1>"#)
+    );
+}
+
+#[test]
+fn follow_imports_keep_at_rules() {
+    let resolver = ImportResolver::new(vec![(
+        "https://example.com/emails/print.css",
+        "h1 { color: black; }",
+    )]);
+    let html = html!(
+        "@import url(print.css) print; h1 { color: blue; }",
+        "<h1>Hello</h1>"
+    );
+    let inlined = CSSInliner::options()
+        .follow_imports(true)
+        .keep_at_rules(true)
+        .base_url(Some(
+            Url::parse("https://example.com/emails/").expect("Invalid URL"),
+        ))
+        .resolver(resolver)
+        .build()
+        .inline(&html)
+        .expect("Inlining failed");
+    // The imported stylesheet is kept in place of the `@import` rule
+    assert_eq!(
+        inlined,
+        "<html><head><style>@media print {\nh1 { color: black; }\n} </style></head><body><h1 style=\"color: blue;\">Hello</h1></body></html>"
+    );
+}
+
+#[test]
+fn follow_imports_cycle() {
+    let resolver = ImportResolver::new(vec![
+        (
+            "https://example.com/emails/a.css",
+            "@import url(b.css); h1 { color: red; }",
+        ),
+        (
+            "https://example.com/emails/b.css",
+            "@import url(a.css); h1 { margin: 0; }",
+        ),
+    ]);
+    let html = html!("@import url(a.css);", "<h1>Hello</h1>");
+    assert_eq!(
+        inline_with_imports(&resolver, &html),
+        html!(r#"<h1 style="margin: 0;color: red;">Hello</h1>"#)
+    );
+    assert_eq!(
+        resolver.requests(),
+        vec![
+            "https://example.com/emails/a.css",
+            "https://example.com/emails/b.css",
+        ]
+    );
+}
+
+#[test]
+fn follow_imports_depth_limit() {
+    /// Every stylesheet imports a new one.
+    struct EndlessResolver {
+        hits: std::sync::Mutex<usize>,
+    }
+
+    impl css_inline::StylesheetResolver for EndlessResolver {
+        fn retrieve(&self, _: &str) -> css_inline::Result<String> {
+            let mut hits = self.hits.lock().expect("Lock is poisoned");
+            *hits += 1;
+            Ok(format!(
+                "@import url(next.css?{hits}); h1 {{ color: red; }}"
+            ))
+        }
+    }
+
+    let resolver = Arc::new(EndlessResolver {
+        hits: std::sync::Mutex::default(),
+    });
+    let html = html!("@import url(next.css);", "<h1>Hello</h1>");
+    let inlined = CSSInliner::options()
+        .follow_imports(true)
+        .base_url(Some(
+            Url::parse("https://example.com/emails/").expect("Invalid URL"),
+        ))
+        .resolver(resolver.clone())
+        .build()
+        .inline(&html)
+        .expect("Inlining failed");
+    assert_eq!(inlined, html!(r#"<h1 style="color: red;">Hello</h1>"#));
+    assert_eq!(*resolver.hits.lock().expect("Lock is poisoned"), 16);
+}
+
+#[test]
+fn follow_imports_report() {
+    let resolver = ImportResolver::new(vec![
+        ("https://example.com/emails/base.css", ""),
+        ("https://example.com/emails/extra.css", "h1 { color: red; }"),
+    ]);
+    let html = html!(
+        "@import url(base.css); @import 'extra.css';",
+        "<h1>Hello</h1>"
+    );
+    let (_, report) = CSSInliner::options()
+        .follow_imports(true)
+        .base_url(Some(
+            Url::parse("https://example.com/emails/").expect("Invalid URL"),
+        ))
+        .resolver(resolver)
+        .build()
+        .inline_with_report(&html)
+        .expect("Inlining failed");
+    assert_eq!(
+        report.resolved_stylesheets,
+        vec![
+            "https://example.com/emails/base.css",
+            "https://example.com/emails/extra.css"
+        ]
+    );
+    assert_eq!(
+        report.empty_stylesheets,
+        vec!["https://example.com/emails/base.css"]
+    );
+    assert!(report.dropped_at_rules.is_empty());
+}
+
+#[test]
+fn imports_not_followed_by_default() {
+    let resolver = ImportResolver::new(vec![]);
+    let html = html!(
+        "@import url(base.css); h1 { color: red; }",
+        "<h1>Hello</h1>"
+    );
+    let inlined = CSSInliner::options()
+        .resolver(resolver.clone())
+        .build()
+        .inline(&html)
+        .expect("Inlining failed");
+    assert_eq!(inlined, html!(r#"<h1 style="color: red;">Hello</h1>"#));
+    assert!(resolver.requests().is_empty());
+}
+
+#[test]
+fn follow_imports_local_paths() {
+    let resolver = ImportResolver::new(vec![
+        (
+            "styles/main.css",
+            "@import 'reset.css'; h1 { color: blue; }",
+        ),
+        ("styles/reset.css", "h1 { margin: 0; }"),
+    ]);
+    let html = r#"<html><head><link href="styles/main.css" rel="stylesheet"></head><body><h1>Hello</h1></body></html>"#;
+    let inlined = CSSInliner::options()
+        .follow_imports(true)
+        .resolver(resolver)
+        .build()
+        .inline(html)
+        .expect("Inlining failed");
+    assert_eq!(
+        inlined,
+        r#"<html><head></head><body><h1 style="margin: 0;color: blue;">Hello</h1></body></html>"#
+    );
+}
+
+#[test]
+fn relative_urls_in_linked_stylesheets() {
+    let resolver = ImportResolver::new(vec![(
+        "https://cdn.example.com/css/main.css",
+        "h1 { background-image: url(../img/bg.png); } @font-face { font-family: x; src: url('fonts/x.woff2'); }",
+    )]);
+    let html = r#"<html><head><link href="https://cdn.example.com/css/main.css" rel="stylesheet"></head><body><h1>Hello</h1></body></html>"#;
+    let inlined = CSSInliner::options()
+        .keep_at_rules(true)
+        .absolutize_urls(true)
+        .base_url(Some(
+            Url::parse("https://example.com/emails/").expect("Invalid URL"),
+        ))
+        .resolver(resolver)
+        .build()
+        .inline(html)
+        .expect("Inlining failed");
+    assert_eq!(
+        inlined,
+        r#"<html><head><style>@font-face { font-family: x; src: url(https://cdn.example.com/css/fonts/x.woff2); } </style></head><body><h1 style="background-image: url(https://cdn.example.com/img/bg.png);">Hello</h1></body></html>"#
+    );
+}
+
+#[test]
+fn relative_urls_in_imported_stylesheets() {
+    let resolver = ImportResolver::new(vec![
+        (
+            "https://cdn.example.com/css/main.css",
+            "@import url(parts/header.css); p { background: url(p.png); }",
+        ),
+        (
+            "https://cdn.example.com/css/parts/header.css",
+            "h1 { background: url(h1.png); }",
+        ),
+    ]);
+    let html = r#"<html><head><link href="https://cdn.example.com/css/main.css" rel="stylesheet"></head><body><h1>Hello</h1><p>World</p></body></html>"#;
+    let inlined = CSSInliner::options()
+        .follow_imports(true)
+        .absolutize_urls(true)
+        .resolver(resolver)
+        .build()
+        .inline(html)
+        .expect("Inlining failed");
+    assert_eq!(
+        inlined,
+        r#"<html><head></head><body><h1 style="background: url(https://cdn.example.com/css/parts/h1.png);">Hello</h1><p style="background: url(https://cdn.example.com/css/p.png);">World</p></body></html>"#
+    );
+}
+
+#[test]
+fn relative_urls_with_base_url() {
+    let html = html!(
+        "h1 { background: url(img/bg.png), url(https://other.com/a.png); }",
+        "<h1>Hello</h1>"
+    );
+    let inlined = CSSInliner::options()
+        .absolutize_urls(true)
+        .base_url(Some(
+            Url::parse("https://example.com/emails/").expect("Invalid URL"),
+        ))
+        .extra_css(Some("h1 { list-style-image: url('/dot.png'); }".into()))
+        .build()
+        .inline(&html)
+        .expect("Inlining failed");
+    assert_eq!(
+        inlined,
+        html!(
+            r#"<h1 style="background: url(https://example.com/emails/img/bg.png), url(https://other.com/a.png);list-style-image: url(https://example.com/dot.png);">Hello</h1>"#
+        )
+    );
+}
+
+#[test]
+fn relative_urls_kept_by_default() {
+    let resolver = ImportResolver::new(vec![(
+        "https://cdn.example.com/css/main.css",
+        "h1 { background-image: url(../img/bg.png); }",
+    )]);
+    let html = r#"<html><head><link href="https://cdn.example.com/css/main.css" rel="stylesheet"></head><body><h1>Hello</h1></body></html>"#;
+    let inlined = CSSInliner::options()
+        .resolver(resolver)
+        .build()
+        .inline(html)
+        .expect("Inlining failed");
+    assert_eq!(
+        inlined,
+        r#"<html><head></head><body><h1 style="background-image: url(../img/bg.png);">Hello</h1></body></html>"#
+    );
+}
+
+#[test]
+fn relative_urls_strict_positions() {
+    let html = "<html><head><style>h1 { background: url(a.png); } h2 { background:
+url(
+  b.png); } p:unknown { color: red; }</style></head><body><h1>Hello</h1></body></html>";
+    let error = CSSInliner::options()
+        .strict(true)
+        .absolutize_urls(true)
+        .base_url(Some(
+            Url::parse("https://example.com/emails/").expect("Invalid URL"),
+        ))
+        .build()
+        .inline(html)
+        .expect_err("Should fail");
+    // Positions are reported in the CSS before URLs were rewritten
+    assert_eq!(
+        error.to_string(),
+        "Unsupported pseudo-class or pseudo-element `unknown` at line 3, column 13: p:unknown"
+    );
+}
+
+#[test]
+fn relative_urls_without_base_url() {
+    let html = html!(
+        "h1 { background-image: url(img/bg.png); }",
+        "<h1>Hello</h1>"
+    );
+    assert_eq!(
+        inline(&html).expect("Inlining failed"),
+        html!(r#"<h1 style="background-image: url(img/bg.png);">Hello</h1>"#)
+    );
+}
+
+#[test]
+fn absolutize_urls() {
+    let html = r#"<html><head></head><body><a href="/about?a=1&amp;b=2">About</a><a href="mailto:hi@example.com">Mail</a><a href="&#35;top">Top</a><img src="img/logo.png" srcset="img/logo.png 1x, img/logo@2x.png 2x"><table background="bg.png"><tr><td>Cell</td></tr></table><video poster="poster.jpg" src="https://cdn.example.com/v.mp4"></video><svg><image href="a.png"></image></svg></body></html>"#;
+    let inlined = CSSInliner::options()
+        .absolutize_urls(true)
+        .base_url(Some(
+            Url::parse("https://example.com/emails/").expect("Invalid URL"),
+        ))
+        .build()
+        .inline(html)
+        .expect("Inlining failed");
+    assert_eq!(
+        inlined,
+        r##"<html><head></head><body><a href="https://example.com/about?a=1&amp;b=2">About</a><a href="mailto:hi@example.com">Mail</a><a href="#top">Top</a><img src="https://example.com/emails/img/logo.png" srcset="https://example.com/emails/img/logo.png 1x, https://example.com/emails/img/logo@2x.png 2x"><table background="https://example.com/emails/bg.png"><tbody><tr><td>Cell</td></tr></tbody></table><video poster="https://example.com/emails/poster.jpg" src="https://cdn.example.com/v.mp4"></video><svg><image href="a.png"></image></svg></body></html>"##
+    );
+}
+
+#[test]
+fn absolutize_urls_with_base_element() {
+    let html = r#"<html><head><base href="https://example.com/brand/"><base href="https://example.com/ignored/"></head><body><a href="page.html">Page</a></body></html>"#;
+    let inlined = CSSInliner::options()
+        .absolutize_urls(true)
+        .build()
+        .inline(html)
+        .expect("Inlining failed");
+    assert_eq!(
+        inlined,
+        r#"<html><head><base href="https://example.com/brand/"><base href="https://example.com/ignored/"></head><body><a href="https://example.com/brand/page.html">Page</a></body></html>"#
+    );
+}
+
+#[test]
+fn absolutize_urls_without_base() {
+    let html = r#"<html><head></head><body><a href="page.html">Page</a></body></html>"#;
+    let inlined = CSSInliner::options()
+        .absolutize_urls(true)
+        .build()
+        .inline(html)
+        .expect("Inlining failed");
+    assert_eq!(inlined, html);
+}
+
+#[test]
+fn relative_attribute_urls_kept_by_default() {
+    let html = r#"<html><head></head><body><a href="page.html">Page</a></body></html>"#;
+    let inlined = CSSInliner::options()
+        .base_url(Some(
+            Url::parse("https://example.com/emails/").expect("Invalid URL"),
+        ))
+        .build()
+        .inline(html)
+        .expect("Inlining failed");
+    assert_eq!(inlined, html);
+}
+
+fn inline_with_base_element(options: css_inline::InlineOptions<'_>) -> (String, Vec<String>) {
+    let resolver = ImportResolver::new(vec![
+        (
+            "https://cdn.example.com/css/main.css",
+            "h1 { color: blue; }",
+        ),
+        (
+            "https://example.com/emails/css/main.css",
+            "h1 { color: red; }",
+        ),
+    ]);
+    let html = r#"<html><head><base href="https://cdn.example.com/"><base href="https://example.com/ignored/"><link href="css/main.css" rel="stylesheet"></head><body><h1>Hello</h1></body></html>"#;
+    let inlined = options
+        .resolver(resolver.clone())
+        .build()
+        .inline(html)
+        .expect("Inlining failed");
+    (inlined, resolver.requests())
+}
+
+#[test]
+fn base_element() {
+    let (inlined, requests) = inline_with_base_element(CSSInliner::options());
+    assert_eq!(
+        inlined,
+        r#"<html><head><base href="https://cdn.example.com/"><base href="https://example.com/ignored/"></head><body><h1 style="color: blue;">Hello</h1></body></html>"#
+    );
+    // Only the first `base` element is used
+    assert_eq!(requests, vec!["https://cdn.example.com/css/main.css"]);
+}
+
+#[test]
+fn base_url_takes_precedence_over_base_element() {
+    let (_, requests) = inline_with_base_element(CSSInliner::options().base_url(Some(
+        Url::parse("https://example.com/emails/").expect("Invalid URL"),
+    )));
+    assert_eq!(requests, vec!["https://example.com/emails/css/main.css"]);
+}
+
+#[test]
+fn prefer_document_base_url() {
+    let (_, requests) = inline_with_base_element(
+        CSSInliner::options()
+            .base_url(Some(
+                Url::parse("https://example.com/emails/").expect("Invalid URL"),
+            ))
+            .prefer_document_base_url(true),
+    );
+    assert_eq!(requests, vec!["https://cdn.example.com/css/main.css"]);
+}
+
+#[test]
+fn relative_base_element() {
+    let resolver = ImportResolver::new(vec![(
+        "https://example.com/assets/css/main.css",
+        "h1 { color: blue; }",
+    )]);
+    let html = r#"<html><head><base href="/assets/"><link href="css/main.css" rel="stylesheet"></head><body><h1>Hello</h1></body></html>"#;
+    CSSInliner::options()
+        .base_url(Some(
+            Url::parse("https://example.com/emails/").expect("Invalid URL"),
+        ))
+        .prefer_document_base_url(true)
+        .resolver(resolver.clone())
+        .build()
+        .inline(html)
+        .expect("Inlining failed");
+    // A relative `base` is resolved against `base_url`
+    assert_eq!(
+        resolver.requests(),
+        vec!["https://example.com/assets/css/main.css"]
+    );
+}
+
+#[test]
+fn xhtml() {
+    let html = r#"<!DOCTYPE html PUBLIC "-//W3C//DTD XHTML 1.0 Transitional//EN" "http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd"><html><head><style>td { padding: 0; }</style></head><body><table><tr><td nowrap>A&nbsp;B<br><img src="a.png" alt=""><input type="checkbox" checked></td></tr></table><hr></body></html>"#;
+    let inlined = CSSInliner::options()
+        .xhtml(true)
+        .build()
+        .inline(html)
+        .expect("Inlining failed");
+    assert_eq!(
+        inlined,
+        r#"<!DOCTYPE html PUBLIC "-//W3C//DTD XHTML 1.0 Transitional//EN" "http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd"><html xmlns="http://www.w3.org/1999/xhtml"><head></head><body><table><tbody><tr><td nowrap="nowrap" style="padding: 0;">A&#160;B<br /><img src="a.png" alt="" /><input type="checkbox" checked="checked" /></td></tr></tbody></table><hr /></body></html>"#
+    );
+}
+
+#[test]
+fn xhtml_existing_namespace() {
+    let html = r#"<html xmlns="http://www.w3.org/1999/xhtml"><head></head><body><svg><path d="M0"></path></svg></body></html>"#;
+    let inlined = CSSInliner::options()
+        .xhtml(true)
+        .build()
+        .inline(html)
+        .expect("Inlining failed");
+    assert_eq!(inlined, html);
+}
+
+#[test]
+fn xhtml_well_formed() {
+    let html = r#"<!DOCTYPE html PUBLIC "-//W3C//DTD XHTML 1.0 Transitional//EN" "http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd"><html><head><style>/* a < b */ p { color: red; }</style><script>if (a < b && c) { d = "]]>"; }</script></head><body><p class="x&amp;y" title="a<b" style="background: url(a.png?x=1&amp;y=2)">A &lt; B</p><noscript><img src="a.png"></noscript></body></html>"#;
+    let inlined = CSSInliner::options()
+        .xhtml(true)
+        .keep_style_tags(true)
+        .build()
+        .inline(html)
+        .expect("Inlining failed");
+    assert_eq!(
+        inlined,
+        r#"<!DOCTYPE html PUBLIC "-//W3C//DTD XHTML 1.0 Transitional//EN" "http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd"><html xmlns="http://www.w3.org/1999/xhtml"><head><style>/*<![CDATA[*//* a < b */ p { color: red; }/*]]>*/</style><script>//<![CDATA[
+if (a < b && c) { d = "]]]]><![CDATA[>"; }
+//]]></script></head><body><p class="x&amp;y" style="color: red;background: url(a.png?x=1&amp;y=2)" title="a&lt;b">A &lt; B</p><noscript>&lt;img src="a.png"&gt;</noscript></body></html>"#
+    );
+    let document = roxmltree::Document::parse_with_options(
+        &inlined,
+        roxmltree::ParsingOptions {
+            allow_dtd: true,
+            ..roxmltree::ParsingOptions::default()
+        },
+    )
+    .expect("Invalid XML");
+    // Script text is the same for XML and HTML parsers, apart from the commented markers
+    let script: String = document
+        .descendants()
+        .find(|node| node.has_tag_name("script"))
+        .expect("Missing script")
+        .children()
+        .filter_map(|node| node.text())
+        .collect();
+    assert_eq!(script, "//\nif (a < b && c) { d = \"]]>\"; }\n//");
+    let paragraph = document
+        .descendants()
+        .find(|node| node.has_tag_name("p"))
+        .expect("Missing paragraph");
+    assert_eq!(paragraph.attribute("title"), Some("a<b"));
+    assert_eq!(paragraph.attribute("class"), Some("x&y"));
+}
+
+fn inline_template(html: &str) -> String {
+    CSSInliner::options()
+        .template_delimiters(vec![
+            ("{{".to_string(), "}}".to_string()),
+            ("{%".to_string(), "%}".to_string()),
+        ])
+        .absolutize_urls(true)
+        .base_url(Some(
+            Url::parse("https://example.com/emails/").expect("Invalid URL"),
+        ))
+        .build()
+        .inline(html)
+        .expect("Inlining failed")
+}
+
+#[test]
+fn template_delimiters() {
+    let html = r#"<html><head><style>td { padding: 0; } a { color: {{ brand_color }}; }</style></head><body><table>{% for item in items %}<tr><td>{{ item.name | upper }}</td></tr>{% endfor %}</table><a href="{{ url }}?a=1&b=2" {% if new_tab %}target="_blank"{% endif %}>{{ 1 < 2 }}</a></body></html>"#;
+    assert_eq!(
+        inline_template(html),
+        r#"<html><head></head><body><table>{% for item in items %}<tbody><tr><td style="padding: 0;">{{ item.name | upper }}</td></tr>{% endfor %}</tbody></table><a href="{{ url }}?a=1&amp;b=2" {% if new_tab %} target="_blank" {% endif %} style="color: {{ brand_color }};">{{ 1 < 2 }}</a></body></html>"#
+    );
+}
+
+#[test]
+fn template_delimiters_in_urls() {
+    let html = r#"<html><head><style>h1 { background: url({{ image }}); }</style></head><body><h1>Hello</h1><img src="{{ cdn }}/logo.png"></body></html>"#;
+    assert_eq!(
+        inline_template(html),
+        r#"<html><head></head><body><h1 style="background: url({{ image }});">Hello</h1><img src="{{ cdn }}/logo.png"></body></html>"#
+    );
+}
+
+#[test]
+fn template_delimiters_not_set() {
+    let html = r#"<html><head></head><body><table>{% for item in items %}<tr><td>A</td></tr>{% endfor %}</table></body></html>"#;
+    assert_eq!(
+        inline(html).expect("Inlining failed"),
+        r#"<html><head></head><body>{% for item in items %}{% endfor %}<table><tbody><tr><td>A</td></tr></tbody></table></body></html>"#
+    );
+}
+
+#[test]
+fn conditional_comments() {
+    let html = r#"<html><head><style>.button { color: red; } table { border: 0; }</style></head><body><!--[if mso]><table role="presentation"><tr><td><a class="button">Click</a></td></tr></table><![endif]--><!--[if !mso]><!--><a class="button">Click</a><!--<![endif]--></body></html>"#;
+    assert_eq!(
+        inline(html).expect("Inlining failed"),
+        r#"<html><head></head><body><!--[if mso]><table role="presentation" style="border: 0;"><tbody><tr><td><a class="button" style="color: red;">Click</a></td></tr></tbody></table><![endif]--><!--[if !mso]><!--><a class="button" style="color: red;">Click</a><!--<![endif]--></body></html>"#
+    );
+}
+
+#[test]
+fn conditional_comments_unclosed_elements() {
+    // "Ghost tables" are split across multiple conditional comments
+    let html = r#"<html><head><style>td { padding: 0; }</style></head><body><!--[if mso]><table><tr><td width="300"><![endif]--><div>A</div><!--[if mso]></td></tr></table><![endif]--></body></html>"#;
+    assert_eq!(
+        inline(html).expect("Inlining failed"),
+        r#"<html><head></head><body><!--[if mso]><table><tr><td width="300"><![endif]--><div>A</div><!--[if mso]></td></tr></table><![endif]--></body></html>"#
+    );
+}
+
+#[test]
+fn conditional_comments_use_document_rules() {
+    // Stylesheets inside the comment are kept and not loaded, at-rules stay in the document
+    let html = r#"<html><head><style>td { padding: 0; } @media (max-width: 600px) { td { padding: 4px; } }</style></head><body><!--[if mso]><link href="tests/missing.css" rel="stylesheet"><style>td { color: red; }</style><table><tr><td>A</td></tr></table><![endif]--></body></html>"#;
+    let inlined = CSSInliner::options()
+        .extra_css(Some("td { margin: 0; }".into()))
+        .keep_at_rules(true)
+        .build()
+        .inline(html)
+        .expect("Inlining failed");
+    assert_eq!(
+        inlined,
+        r#"<html><head><style>@media (max-width: 600px) { td { padding: 4px; } } </style></head><body><!--[if mso]><link href="tests/missing.css" rel="stylesheet"><style>td { color: red; }</style><table><tbody><tr><td style="padding: 0;margin: 0;">A</td></tr></tbody></table><![endif]--></body></html>"#
+    );
+}
+
+#[test]
+fn conditional_comments_without_ancestors() {
+    // Elements outside of the comment are not known when its contents are inlined
+    let html = r#"<html><head><style>.wrapper td { color: red; } td { padding: 0; }</style></head><body><div class="wrapper"><!--[if mso]><table><tr><td>A</td></tr></table><![endif]--></div></body></html>"#;
+    assert_eq!(
+        inline(html).expect("Inlining failed"),
+        r#"<html><head></head><body><div class="wrapper"><!--[if mso]><table><tbody><tr><td style="padding: 0;">A</td></tr></tbody></table><![endif]--></div></body></html>"#
+    );
+}
+
+#[test]
+fn remove_conditional_comments() {
+    let html = r#"<html><head></head><body><!--[if mso]><table><tr><td><![endif]--><!--[if !mso]><!--><div>A</div><!--<![endif]--><!-- Regular --></body></html>"#;
+    let inlined = CSSInliner::options()
+        .remove_conditional_comments(true)
+        .build()
+        .inline(html)
+        .expect("Inlining failed");
+    assert_eq!(
+        inlined,
+        "<html><head></head><body><div>A</div><!-- Regular --></body></html>"
+    );
 }